@@ -1,7 +1,8 @@
 //! Used to extract useful data from the AST, for example to work out what types are in a document.
 
 use super::{
-    Definition, Document, GraphQLType, Name, NamedType, SchemaDefinition, TypeSystemDefinition,
+    Definition, Document, GraphQLType, Name, NamedType, OperationType, SchemaDefinition,
+    TypeDefinition, TypeSystemDefinition,
 };
 
 /// I know it's strange to stick impls in a separate file, but this seemed to be a sensible move
@@ -11,35 +12,107 @@ impl Document {
         self.get_type(name).is_some()
     }
     pub fn get_type(&self, name: &Name) -> Option<&Definition> {
-        self.0
-            .iter()
-            .filter(|definition| match definition {
-                Definition::TypeSystemDefinition(type_system_definition) => {
-                    match type_system_definition {
-                        TypeSystemDefinition::TypeDefinition(type_definition) => {
-                            &Name::from(type_definition.clone()) == name
-                        }
-                        _ => false,
+        self.0.iter().find(|definition| match definition {
+            Definition::TypeSystemDefinition(TypeSystemDefinition::TypeDefinition(
+                type_definition,
+            )) => &Name::from(type_definition.clone()) == name,
+            _ => false,
+        })
+    }
+    /// Iterates over every type definition (objects, interfaces, enums, etc) in the document.
+    pub fn type_definitions(&self) -> impl Iterator<Item = &TypeDefinition> {
+        self.0.iter().filter_map(|definition| match definition {
+            Definition::TypeSystemDefinition(TypeSystemDefinition::TypeDefinition(
+                type_definition,
+            )) => Some(type_definition),
+            _ => None,
+        })
+    }
+    /// Like `get_type`, but skips the layers of wrapping around the `TypeDefinition`.
+    pub fn get_type_definition(&self, name: &Name) -> Option<&TypeDefinition> {
+        self.type_definitions()
+            .find(|type_definition| type_definition.name() == name)
+    }
+    /// Returns the names of the object types which a value of the named type could have at runtime.
+    /// For a union these are its members, for an interface these are the objects which implement
+    /// it and for an object this is just the object itself.
+    ///
+    /// Returns `None` if the type doesn't exist or can't contain objects (e.g. it's an enum).
+    pub fn possible_types(&self, name: &Name) -> Option<Vec<&Name>> {
+        match self.get_type_definition(name)? {
+            TypeDefinition::ObjectTypeDefinition(object) => Some(vec![&object.name]),
+            TypeDefinition::UnionTypeDefinition(union) => Some(
+                union
+                    .union_member_types
+                    .iter()
+                    .flat_map(|members| members.0.iter())
+                    .map(|member| &member.0)
+                    .collect(),
+            ),
+            TypeDefinition::InterfaceTypeDefinition(_) => Some(
+                self.type_definitions()
+                    .filter_map(|type_definition| match type_definition {
+                        TypeDefinition::ObjectTypeDefinition(object) => object
+                            .implements_interfaces
+                            .iter()
+                            .flat_map(|interfaces| interfaces.0.iter())
+                            .find(|interface| &interface.0 == name)
+                            .map(|_| &object.name),
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+    /// Returns the name of the root type for an operation. If the document doesn't contain a
+    /// `schema { ... }` block then (as the specification says) types called `Query`, `Mutation`
+    /// and `Subscription` are used, if they exist.
+    pub fn root_operation_type(&self, operation_type: OperationType) -> Option<Name> {
+        match self.get_schema_definition() {
+            Some(schema) => match operation_type {
+                OperationType::Query => schema.query,
+                OperationType::Mutation => schema.mutation,
+                OperationType::Subscription => schema.subscription,
+            }
+            .map(|root| root.named_type.0),
+            None => {
+                let name = Name(
+                    match operation_type {
+                        OperationType::Query => "Query",
+                        OperationType::Mutation => "Mutation",
+                        OperationType::Subscription => "Subscription",
                     }
-                }
-                _ => false,
-            })
-            .next()
+                    .to_string(),
+                );
+                self.get_type_definition(&name).map(|_| name)
+            }
+        }
     }
     pub fn get_schema_definition(&self) -> Option<SchemaDefinition> {
         self.0
             .iter()
-            .filter_map(|definition| match definition {
-                Definition::TypeSystemDefinition(type_system_definition) => {
-                    match type_system_definition {
-                        TypeSystemDefinition::SchemaDefinition(def) => Some(def),
-                        _ => None,
-                    }
+            .find_map(|definition| match definition {
+                Definition::TypeSystemDefinition(TypeSystemDefinition::SchemaDefinition(def)) => {
+                    Some(def)
                 }
                 _ => None,
             })
-            .next()
-            .map(Clone::clone)
+            .cloned()
+    }
+}
+
+impl TypeDefinition {
+    /// Returns the name of the type being defined.
+    pub fn name(&self) -> &Name {
+        match self {
+            Self::ScalarTypeDefinition(def) => &def.name,
+            Self::ObjectTypeDefinition(def) => &def.name,
+            Self::InterfaceTypeDefinition(def) => &def.name,
+            Self::UnionTypeDefinition(def) => &def.name,
+            Self::EnumTypeDefinition(def) => &def.name,
+            Self::InputObjectTypeDefinition(def) => &def.name,
+        }
     }
 }
 
@@ -78,16 +151,122 @@ mod test_check_type_exists {
 
 #[cfg(test)]
 pub mod test_get_type_fields {
+    use crate::ast::{Name, TypeDefinition};
+    use crate::parse_string;
+
     #[test]
     fn check_can_get_object_fields() {
-        todo!()
+        let parsed = parse_string(
+            r#"type Post { id: Int! title: String }
+            type Comment { id: Int! }"#,
+        )
+        .expect("Parse error");
+        let post = match parsed.get_type_definition(&Name("Post".to_string())) {
+            Some(TypeDefinition::ObjectTypeDefinition(post)) => post,
+            _ => panic!("failed to find `Post`"),
+        };
+        let fields = post
+            .fields_definition
+            .as_ref()
+            .expect("`Post` should have fields")
+            .0
+            .iter()
+            .map(|field| field.name.0.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(fields, vec!["id", "title"]);
     }
 }
 
 #[cfg(test)]
 pub mod test_get_schema_definition {
+    use crate::parse_string;
+
     #[test]
     fn test_can_get_schema_definition() {
-        todo!()
+        let parsed = parse_string(
+            r#"schema { query: MyQueryRootType }
+            type MyQueryRootType { someField: String }"#,
+        )
+        .expect("Parse error");
+        let schema = parsed
+            .get_schema_definition()
+            .expect("failed to find the schema definition");
+        assert_eq!(
+            schema.query.expect("missing query root").named_type.0 .0,
+            "MyQueryRootType"
+        );
+        assert!(parse_string("type Query { someField: String }")
+            .expect("Parse error")
+            .get_schema_definition()
+            .is_none());
+    }
+
+    #[test]
+    fn test_root_operation_types() {
+        use crate::ast::OperationType;
+        let parsed = parse_string(
+            r#"schema { query: MyQueryRootType }
+            type MyQueryRootType { someField: String }
+            type Mutation { someField: String }"#,
+        )
+        .expect("Parse error");
+        assert_eq!(
+            parsed.root_operation_type(OperationType::Query).unwrap().0,
+            "MyQueryRootType"
+        );
+        // there's a `schema` block, so `Mutation` isn't used
+        assert!(parsed
+            .root_operation_type(OperationType::Mutation)
+            .is_none());
+        let parsed = parse_string("type Query { someField: String }").expect("Parse error");
+        assert_eq!(
+            parsed.root_operation_type(OperationType::Query).unwrap().0,
+            "Query"
+        );
+        assert!(parsed
+            .root_operation_type(OperationType::Subscription)
+            .is_none());
+    }
+}
+
+#[cfg(test)]
+mod test_possible_types {
+    use crate::{ast::Name, parse_string};
+
+    fn possible_types(schema: &str, name: &str) -> Option<Vec<String>> {
+        let parsed = parse_string(schema).expect("Parse error");
+        parsed
+            .possible_types(&Name(name.to_string()))
+            .map(|names| names.into_iter().map(|name| name.0.clone()).collect())
+    }
+
+    #[test]
+    fn test_union_members() {
+        let schema = r#"type User { id: ID! }
+            type Post { id: ID! }
+            union SearchResult = User | Post"#;
+        assert_eq!(
+            possible_types(schema, "SearchResult"),
+            Some(vec!["User".to_string(), "Post".to_string()])
+        );
+        assert_eq!(
+            possible_types(schema, "User"),
+            Some(vec!["User".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_interface_implementations() {
+        let schema = r#"interface Node { id: ID! }
+            interface Named { name: String! }
+            type User implements Node & Named { id: ID! name: String! }
+            type Post implements Node { id: ID! }
+            type Tag implements Named { name: String! }
+            enum Colour { RED }"#;
+        assert_eq!(
+            possible_types(schema, "Node"),
+            Some(vec!["User".to_string(), "Post".to_string()])
+        );
+        assert_eq!(possible_types(schema, "Colour"), None);
     }
 }
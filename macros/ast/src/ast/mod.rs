@@ -75,17 +75,46 @@ where
     }
 }
 
+impl<T> Token<T> {
+    /// Returns the range of bytes in the input string which this token was parsed from.
+    ///
+    /// Unlike `span` this doesn't need a copy of the input, which makes it useful for errors which
+    /// are reported somewhere else (e.g. as a part of a string literal in a Rust macro).
+    pub fn range(&self) -> std::ops::Range<usize> {
+        self.span.start..self.span.stop
+    }
+}
+
+impl<T> std::ops::Deref for Token<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.token
+    }
+}
+
+impl<T> Display for Token<T>
+where
+    T: Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.token.fmt(f)
+    }
+}
+
 #[derive(Parser)]
 #[grammar = "graphql.pest"]
 /// A parser for GraphQL queries. This parser is procedurally generated by Pest.
 ///
 /// ```
-/// let x = "query { user { id } }";
-/// GraphQLParser::parse(input, Rule::document)
+/// use ast::ast::{GraphQLParser, Rule};
+/// use pest::Parser;
+/// let input = "query { user { id } }";
+/// GraphQLParser::parse(Rule::document, input).unwrap();
 /// ```
 pub struct GraphQLParser;
 
-#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
 /// A GraphQL name. This just wraps a string to make it possible to implement `TryFrom<Pair>` on it.
 ///
 /// http://spec.graphql.org/draft/#sec-Names
@@ -197,8 +226,8 @@ impl<'a> TryFrom<Pair<'a, Rule>> for RootOperationTypeDefinition {
 ///
 /// http://spec.graphql.org/draft/#Argument
 pub struct Argument {
-    name: Name,
-    value: Value,
+    pub name: Token<Name>,
+    pub value: Value,
 }
 
 impl Display for Argument {
@@ -215,7 +244,7 @@ impl<'a> TryFrom<Pair<'a, Rule>> for Argument {
     fn try_from(pair: Pair<Rule>) -> Result<Self, Self::Error> {
         let mut iterator = pair.into_inner();
         Ok(Self {
-            name: Name::try_from(iterator.next().unwrap())?,
+            name: Token::parse(iterator.next().unwrap())?,
             value: Value::try_from(iterator.next().unwrap())?,
         })
     }
@@ -259,13 +288,13 @@ impl<'a> TryFrom<Pair<'a, Rule>> for Arguments {
 ///
 /// http://spec.graphql.org/draft/#Directive
 pub struct Directive {
-    name: Name,
-    arguments: Option<Arguments>,
+    pub name: Name,
+    pub arguments: Option<Arguments>,
 }
 
 impl Display for Directive {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str("@ ")?;
+        f.write_str("@")?;
         self.name.fmt(f)?;
         write_option(self.arguments.as_ref(), f)
     }
@@ -290,7 +319,7 @@ impl<'a> TryFrom<Pair<'a, Rule>> for Directive {
 /// A list of GraphQL directives.
 ///
 /// http://spec.graphql.org/draft/#Directive
-pub struct Directives(Vec<Directive>);
+pub struct Directives(pub Vec<Directive>);
 
 impl Display for Directives {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -319,6 +348,7 @@ impl<'a> TryFrom<Pair<'a, Rule>> for Directives {
 /// A GraphQL schema definition.
 ///
 /// http://spec.graphql.org/draft/#SchemaDefinition
+#[derive(Default)]
 pub struct SchemaDefinition {
     /// Describes the schema.
     description: Option<Description>,
@@ -367,7 +397,7 @@ impl<'a> TryFrom<Pair<'a, Rule>> for SchemaDefinition {
             _ => unreachable!(),
         }
 
-        while let Some(field) = iterator.next() {
+        for field in iterator {
             let root_operation = RootOperationTypeDefinition::try_from(field.clone())?;
             match root_operation.operation_type {
                 OperationType::Subscription => {
@@ -411,17 +441,6 @@ impl<'a> TryFrom<Pair<'a, Rule>> for SchemaDefinition {
     }
 }
 
-impl Default for SchemaDefinition {
-    fn default() -> Self {
-        Self {
-            description: None,
-            directives: None,
-            query: None,
-            mutation: None,
-            subscription: None,
-        }
-    }
-}
 #[derive(Clone, Debug, Deserialize, Serialize)]
 /// A GraphQL type definition.
 ///
@@ -536,20 +555,13 @@ impl<'a> TryFrom<Pair<'a, Rule>> for ScalarTypeDefinition {
         let mut scalar_type_definition = ScalarTypeDefinition::default();
         let mut iterator = pair.into_inner();
         let possible_description = iterator.peek().unwrap();
-        match possible_description.as_rule() {
-            Rule::description => {
-                scalar_type_definition.description =
-                    Some(Description::try_from(possible_description)?);
-                iterator.next().unwrap();
-            }
-            _ => {}
+        if possible_description.as_rule() == Rule::description {
+            scalar_type_definition.description = Some(Description::try_from(possible_description)?);
+            iterator.next().unwrap();
         }
         scalar_type_definition.name = Name::try_from(iterator.next().unwrap())?;
-        match iterator.next() {
-            Some(t) => {
-                scalar_type_definition.directives = Some(Directives::try_from(t)?);
-            }
-            None => {}
+        if let Some(t) = iterator.next() {
+            scalar_type_definition.directives = Some(Directives::try_from(t)?);
         }
         Ok(scalar_type_definition)
     }
@@ -592,13 +604,13 @@ impl<'a> TryFrom<Pair<'a, Rule>> for ScalarTypeExtension {
 /// The interfaces implemented on a type.
 ///
 /// http://spec.graphql.org/draft/#ImplementsInterfaces
-pub struct ImplementsInterfaces(Vec<NamedType>);
+pub struct ImplementsInterfaces(pub Vec<NamedType>);
 
 impl Display for ImplementsInterfaces {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for (i, interface) in self.0.iter().enumerate() {
             interface.fmt(f)?;
-            if i != self.0.len() {
+            if i != self.0.len() - 1 {
                 f.write_str(" & ")?;
             }
         }
@@ -674,14 +686,17 @@ impl<'a> TryFrom<Pair<'a, Rule>> for GraphQLType {
     type Error = Error<Rule>;
 
     fn try_from(pair: Pair<Rule>) -> Result<Self, Self::Error> {
-        let graphql_type = pair.into_inner().next().unwrap();
-        match graphql_type.as_rule() {
-            Rule::named_type => Ok(Self::NamedType(NamedType::try_from(graphql_type)?)),
-            Rule::list_type => Ok(Self::ListType(Box::new(Self::try_from(graphql_type)?))),
-            Rule::non_null_type => Ok(Self::NonNullType(Box::new(Self::try_from({
-                graphql_type
-            })?))),
-            // ^^ maybe not 100% compliant with the spec
+        match pair.as_rule() {
+            Rule::named_type => Ok(Self::NamedType(NamedType::try_from(pair)?)),
+            // both of these wrap exactly one inner type (for lists this is a `graphql_type`, for
+            // non-null types it's either a `named_type` or a `list_type`)
+            Rule::list_type => Ok(Self::ListType(Box::new(Self::try_from(
+                pair.into_inner().next().unwrap(),
+            )?))),
+            Rule::non_null_type => Ok(Self::NonNullType(Box::new(Self::try_from(
+                pair.into_inner().next().unwrap(),
+            )?))),
+            Rule::graphql_type => Self::try_from(pair.into_inner().next().unwrap()),
             _ => unreachable!(),
         }
     }
@@ -721,7 +736,7 @@ impl<'a> TryFrom<Pair<'a, Rule>> for ArgumentsDefinition {
 ///
 /// http://spec.graphql.org/draft/#Alias
 pub struct Alias {
-    name: Name,
+    pub name: Name,
 }
 
 impl Display for Alias {
@@ -747,20 +762,24 @@ impl<'a> TryFrom<Pair<'a, Rule>> for Alias {
 ///
 /// http://spec.graphql.org/draft/#Field
 pub struct Field {
-    alias: Option<Alias>,
-    name: Name,
-    variable_definitions: Option<VariableDefinitions>,
-    directives: Option<Directives>,
-    selection_set: Option<SelectionSet>,
+    pub alias: Option<Alias>,
+    pub name: Token<Name>,
+    pub arguments: Option<Arguments>,
+    pub directives: Option<Directives>,
+    pub selection_set: Option<SelectionSet>,
 }
 
 impl Display for Field {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write_option(self.alias.as_ref(), f)?;
         self.name.fmt(f)?;
-        write_option(self.variable_definitions.as_ref(), f)?;
+        write_option(self.arguments.as_ref(), f)?;
         write_option(self.directives.as_ref(), f)?;
-        write_option(self.selection_set.as_ref(), f)
+        if let Some(selection_set) = &self.selection_set {
+            f.write_str(" ")?;
+            selection_set.fmt(f)?;
+        }
+        Ok(())
     }
 }
 
@@ -774,12 +793,10 @@ impl<'a> TryFrom<Pair<'a, Rule>> for Field {
                 Rule::alias => Some(Alias::try_from(iterator.next().unwrap())?),
                 _ => None,
             },
-            name: Name::try_from(iterator.next().unwrap())?,
-            variable_definitions: match iterator.peek() {
+            name: Token::parse(iterator.next().unwrap())?,
+            arguments: match iterator.peek() {
                 Some(item) => match item.as_rule() {
-                    Rule::variable_definitions => {
-                        Some(VariableDefinitions::try_from(iterator.next().unwrap())?)
-                    }
+                    Rule::arguments => Some(Arguments::try_from(iterator.next().unwrap())?),
                     _ => None,
                 },
                 None => None,
@@ -936,9 +953,7 @@ impl<'a> TryFrom<Pair<'a, Rule>> for ObjectTypeDefinition {
             directives: {
                 match iterator.peek() {
                     Some(item) => match item.as_rule() {
-                        Rule::implements_interfaces => {
-                            Some(Directives::try_from(iterator.next().unwrap())?)
-                        }
+                        Rule::directives => Some(Directives::try_from(iterator.next().unwrap())?),
                         _ => None,
                     },
                     None => None,
@@ -1002,23 +1017,23 @@ impl<'a> TryFrom<Pair<'a, Rule>> for InterfaceTypeDefinition {
                 }
             },
             name: Name::try_from(iterator.next().unwrap())?,
-            implements_interfaces: {
-                match iterator.peek() {
-                    Some(_) => Some(ImplementsInterfaces::try_from(iterator.next().unwrap())?),
-                    None => None,
+            implements_interfaces: match iterator.peek() {
+                Some(item) if item.as_rule() == Rule::implements_interfaces => {
+                    Some(ImplementsInterfaces::try_from(iterator.next().unwrap())?)
                 }
+                _ => None,
             },
-            directives: {
-                match iterator.peek() {
-                    Some(_) => Some(Directives::try_from(iterator.next().unwrap())?),
-                    None => None,
+            directives: match iterator.peek() {
+                Some(item) if item.as_rule() == Rule::directives => {
+                    Some(Directives::try_from(iterator.next().unwrap())?)
                 }
+                _ => None,
             },
-            fields_definition: {
-                match iterator.peek() {
-                    Some(_) => Some(FieldsDefinition::try_from(iterator.next().unwrap())?),
-                    None => None,
+            fields_definition: match iterator.peek() {
+                Some(item) if item.as_rule() == Rule::fields_definition => {
+                    Some(FieldsDefinition::try_from(iterator.next().unwrap())?)
                 }
+                _ => None,
             },
         })
     }
@@ -1029,10 +1044,10 @@ impl<'a> TryFrom<Pair<'a, Rule>> for InterfaceTypeDefinition {
 ///
 /// http://spec.graphql.org/draft/#UnionTypeDefinition
 pub struct UnionTypeDefinition {
-    description: Option<Description>,
-    name: Name,
-    directives: Option<Directives>,
-    union_member_types: Option<UnionMemberTypes>,
+    pub description: Option<Description>,
+    pub name: Name,
+    pub directives: Option<Directives>,
+    pub union_member_types: Option<UnionMemberTypes>,
 }
 
 impl Display for UnionTypeDefinition {
@@ -1057,20 +1072,22 @@ impl<'a> TryFrom<Pair<'a, Rule>> for UnionTypeDefinition {
     fn try_from(pair: Pair<Rule>) -> Result<Self, Self::Error> {
         let mut iterator = pair.into_inner();
         Ok(Self {
-            description: {
-                match iterator.peek() {
-                    Some(_) => Some(Description::try_from(iterator.next().unwrap())?),
-                    None => None,
-                }
+            description: match iterator.peek().unwrap().as_rule() {
+                Rule::description => Some(Description::try_from(iterator.next().unwrap())?),
+                _ => None,
             },
             name: Name::try_from(iterator.next().unwrap())?,
             directives: match iterator.peek() {
-                Some(_) => Some(Directives::try_from(iterator.next().unwrap())?),
-                None => None,
+                Some(item) if item.as_rule() == Rule::directives => {
+                    Some(Directives::try_from(iterator.next().unwrap())?)
+                }
+                _ => None,
             },
             union_member_types: match iterator.peek() {
-                Some(_) => Some(UnionMemberTypes::try_from(iterator.next().unwrap())?),
-                None => None,
+                Some(item) if item.as_rule() == Rule::union_member_types => {
+                    Some(UnionMemberTypes::try_from(iterator.next().unwrap())?)
+                }
+                _ => None,
             },
         })
     }
@@ -1112,10 +1129,10 @@ impl<'a> TryFrom<Pair<'a, Rule>> for UnionMemberTypes {
 ///
 /// http://spec.graphql.org/draft/#EnumTypeDefinition
 pub struct EnumTypeDefinition {
-    description: Option<Description>,
-    name: Name,
-    directives: Option<Directives>,
-    enum_values_definition: Option<EnumValuesDefinition>,
+    pub description: Option<Description>,
+    pub name: Name,
+    pub directives: Option<Directives>,
+    pub enum_values_definition: Option<EnumValuesDefinition>,
 }
 
 impl Display for EnumTypeDefinition {
@@ -1145,15 +1162,17 @@ impl<'a> TryFrom<Pair<'a, Rule>> for EnumTypeDefinition {
                 _ => None,
             },
             name: Name::try_from(iterator.next().unwrap())?,
-            directives: {
-                match iterator.peek() {
-                    Some(_) => Some(Directives::try_from(iterator.next().unwrap())?),
-                    None => None,
+            directives: match iterator.peek() {
+                Some(item) if item.as_rule() == Rule::directives => {
+                    Some(Directives::try_from(iterator.next().unwrap())?)
                 }
+                _ => None,
             },
             enum_values_definition: match iterator.peek() {
-                Some(_) => Some(EnumValuesDefinition::try_from(iterator.next().unwrap())?),
-                None => None,
+                Some(item) if item.as_rule() == Rule::enum_values_definition => {
+                    Some(EnumValuesDefinition::try_from(iterator.next().unwrap())?)
+                }
+                _ => None,
             },
         })
     }
@@ -1193,9 +1212,9 @@ impl<'a> TryFrom<Pair<'a, Rule>> for EnumValuesDefinition {
 ///
 /// http://spec.graphql.org/draft/#EnumValueDefinition
 pub struct EnumValueDefinition {
-    description: Option<Description>,
-    enum_value: EnumValue,
-    directives: Option<Directives>,
+    pub description: Option<Description>,
+    pub enum_value: EnumValue,
+    pub directives: Option<Directives>,
 }
 
 impl Display for EnumValueDefinition {
@@ -1212,18 +1231,14 @@ impl<'a> TryFrom<Pair<'a, Rule>> for EnumValueDefinition {
     fn try_from(pair: Pair<Rule>) -> Result<Self, Self::Error> {
         let mut iterator = pair.into_inner();
         Ok(Self {
-            description: {
-                match iterator.peek() {
-                    Some(_) => Some(Description::try_from(iterator.next().unwrap())?),
-                    None => None,
-                }
+            description: match iterator.peek().unwrap().as_rule() {
+                Rule::description => Some(Description::try_from(iterator.next().unwrap())?),
+                _ => None,
             },
             enum_value: EnumValue::try_from(iterator.next().unwrap())?,
-            directives: {
-                match iterator.peek() {
-                    Some(_) => Some(Directives::try_from(iterator.next().unwrap())?),
-                    None => None,
-                }
+            directives: match iterator.next() {
+                Some(item) => Some(Directives::try_from(item)?),
+                None => None,
             },
         })
     }
@@ -1252,10 +1267,10 @@ impl<'a> TryFrom<Pair<'a, Rule>> for EnumValue {
 ///
 /// http://spec.graphql.org/draft/#InputObjectTypeDefinition
 pub struct InputObjectTypeDefinition {
-    description: Option<Description>,
-    name: Name,
-    directives: Option<Directives>,
-    input_fields_definition: Option<InputFieldsDefinition>,
+    pub description: Option<Description>,
+    pub name: Name,
+    pub directives: Option<Directives>,
+    pub input_fields_definition: Option<InputFieldsDefinition>,
 }
 
 impl Display for InputObjectTypeDefinition {
@@ -1342,19 +1357,23 @@ impl<'a> TryFrom<Pair<'a, Rule>> for InputFieldsDefinition {
 ///
 /// http://spec.graphql.org/draft/#InputValueDefinition
 pub struct InputValueDefinition {
-    description: Option<Description>,
-    name: Name,
-    graphql_type: GraphQLType,
-    default_value: Option<DefaultValue>,
-    directives: Option<Directives>,
+    pub description: Option<Description>,
+    pub name: Name,
+    pub graphql_type: GraphQLType,
+    pub default_value: Option<DefaultValue>,
+    pub directives: Option<Directives>,
 }
 
 impl Display for InputValueDefinition {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write_option(self.description.as_ref(), f)?;
+        self.name.fmt(f)?;
         f.write_str(": ")?;
         self.graphql_type.fmt(f)?;
-        write_option(self.default_value.as_ref(), f)?;
+        if let Some(default_value) = &self.default_value {
+            f.write_str(" = ")?;
+            default_value.fmt(f)?;
+        }
         write_option(self.directives.as_ref(), f)
     }
 }
@@ -1373,17 +1392,15 @@ impl<'a> TryFrom<Pair<'a, Rule>> for InputValueDefinition {
             },
             name: Name::try_from(iterator.next().unwrap())?,
             graphql_type: { GraphQLType::try_from(iterator.next().unwrap())? },
-            default_value: {
-                match iterator.peek() {
-                    Some(_) => Some(DefaultValue::try_from(iterator.next().unwrap())?),
-                    None => None,
+            default_value: match iterator.peek() {
+                Some(item) if item.as_rule() == Rule::default_value => {
+                    Some(DefaultValue::try_from(iterator.next().unwrap())?)
                 }
+                _ => None,
             },
-            directives: {
-                match iterator.peek() {
-                    Some(_) => Some(Directives::try_from(iterator.next().unwrap())?),
-                    None => None,
-                }
+            directives: match iterator.next() {
+                Some(item) => Some(Directives::try_from(item)?),
+                None => None,
             },
         })
     }
@@ -1408,15 +1425,16 @@ impl<'a> TryFrom<Pair<'a, Rule>> for DefaultValue {
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct ListValue(Vec<Value>);
+pub struct ListValue(pub Vec<Value>);
 
 impl Display for ListValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("[")?;
         for value in &self.0 {
             value.fmt(f)?;
             f.write_str(" ")?;
         }
-        Ok(())
+        f.write_str("]")
     }
 }
 
@@ -1434,12 +1452,15 @@ impl<'a> TryFrom<Pair<'a, Rule>> for ListValue {
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct ObjectValue(pub ObjectField);
+pub struct ObjectValue(pub Vec<ObjectField>);
 
 impl Display for ObjectValue {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str("{")?;
-        f.write_str(&self.0.to_string())?;
+        for field in &self.0 {
+            field.fmt(f)?;
+            f.write_str(" ")?;
+        }
         f.write_str("}")
     }
 }
@@ -1448,16 +1469,18 @@ impl<'a> TryFrom<Pair<'a, Rule>> for ObjectValue {
     type Error = Error<Rule>;
 
     fn try_from(pair: Pair<Rule>) -> Result<Self, Self::Error> {
-        Ok(Self(ObjectField::try_from(
-            pair.into_inner().next().unwrap(),
-        )?))
+        let mut output = vec![];
+        for field in pair.into_inner() {
+            output.push(ObjectField::try_from(field)?);
+        }
+        Ok(Self(output))
     }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct ObjectField {
-    name: Name,
-    value: Value,
+    pub name: Name,
+    pub value: Value,
 }
 
 impl Display for ObjectField {
@@ -1473,10 +1496,10 @@ impl<'a> TryFrom<Pair<'a, Rule>> for ObjectField {
 
     fn try_from(pair: Pair<Rule>) -> Result<Self, Self::Error> {
         let mut values = pair.into_inner();
-        return Ok(Self {
+        Ok(Self {
             name: Name::try_from(values.next().unwrap())?,
             value: Value::try_from(values.next().unwrap())?,
-        });
+        })
     }
 }
 
@@ -1485,7 +1508,8 @@ pub struct Variable(pub Name);
 
 impl Display for Variable {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&self.0.to_string())
+        f.write_str("$")?;
+        self.0.fmt(f)
     }
 }
 
@@ -1532,9 +1556,7 @@ impl<'a> TryFrom<Pair<'a, Rule>> for Value {
     fn try_from(pair: Pair<Rule>) -> Result<Self, Self::Error> {
         let pair = pair.into_inner().next().unwrap();
         match pair.as_rule() {
-            Rule::variable => Ok(Self::Variable(Variable::try_from(
-                pair.into_inner().next().unwrap(),
-            )?)),
+            Rule::variable => Ok(Self::Variable(Variable::try_from(pair)?)),
             Rule::int_value => Ok(Self::Int(match pair.as_str().parse::<i64>() {
                 Ok(i) => Ok(i),
                 Err(_) => Err(Error::new_from_span(
@@ -1829,12 +1851,12 @@ impl<'a> TryFrom<Pair<'a, Rule>> for ObjectTypeExtension {
             }
         }
         let fields_definition = FieldsDefinition::try_from(iterator.next().unwrap())?;
-        return Ok(Self::WithFields {
+        Ok(Self::WithFields {
             name,
             implements_interfaces,
             directives,
             fields_definition,
-        });
+        })
     }
 }
 
@@ -1888,11 +1910,11 @@ impl<'a> TryFrom<Pair<'a, Rule>> for EnumTypeExtension {
                 return Ok(Self::WithDirectives { name, directives });
             }
         }
-        return Ok(Self::WithEnumValuesDefinition {
+        Ok(Self::WithEnumValuesDefinition {
             name,
             directives,
             enum_values_definition: EnumValuesDefinition::try_from(iterator.next().unwrap())?,
-        });
+        })
     }
 }
 
@@ -2035,7 +2057,6 @@ impl<'a> TryFrom<Pair<'a, Rule>> for DirectiveDefinition {
 
     fn try_from(pair: Pair<Rule>) -> Result<Self, Self::Error> {
         let mut iterator = pair.into_inner();
-        println!("{:?}", iterator);
         Ok(Self {
             description: {
                 match iterator.peek().unwrap().as_rule() {
@@ -2178,12 +2199,11 @@ pub enum TypeSystemDirectiveLocation {
     Scalar,
     Object,
     FieldDefinition,
-    ArgumentDescription,
+    ArgumentDefinition,
     Interface,
     Union,
     Enum,
     EnumValue,
-    InputValue,
     InputObject,
     InputFieldDefinition,
 }
@@ -2195,12 +2215,11 @@ impl Display for TypeSystemDirectiveLocation {
             Self::Scalar => "SCALAR",
             Self::Object => "OBJECT",
             Self::FieldDefinition => "FIELD_DEFINITION",
-            Self::ArgumentDescription => "ARGUMENT_DESCRIPTION",
+            Self::ArgumentDefinition => "ARGUMENT_DEFINITION",
             Self::Interface => "INTERFACE",
             Self::Union => "UNION",
             Self::Enum => "ENUM",
             Self::EnumValue => "ENUM_VALUE",
-            Self::InputValue => "INPUT_VALUE",
             Self::InputObject => "INPUT_OBJECT",
             Self::InputFieldDefinition => "INPUT_FIELD_DEFINITION",
         })
@@ -2216,12 +2235,11 @@ impl<'a> TryFrom<Pair<'a, Rule>> for TypeSystemDirectiveLocation {
             "SCALAR" => Ok(Self::Scalar),
             "OBJECT" => Ok(Self::Object),
             "FIELD_DEFINITION" => Ok(Self::FieldDefinition),
-            "ARGUMENT_DESCRIPTION" => Ok(Self::ArgumentDescription),
+            "ARGUMENT_DEFINITION" => Ok(Self::ArgumentDefinition),
             "INTERFACE" => Ok(Self::Interface),
             "UNION" => Ok(Self::Union),
             "ENUM" => Ok(Self::Enum),
             "ENUM_VALUE" => Ok(Self::EnumValue),
-            "INPUT_VALUE" => Ok(Self::InputValue),
             "INPUT_OBJECT" => Ok(Self::InputObject),
             "INPUT_FIELD_DEFINITION" => Ok(Self::InputFieldDefinition),
             _ => unreachable!(),
@@ -2231,17 +2249,22 @@ impl<'a> TryFrom<Pair<'a, Rule>> for TypeSystemDirectiveLocation {
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct VariableDefinition {
-    variable: Variable,
-    graphql_type: GraphQLType,
-    default_value: Option<DefaultValue>,
-    directives: Option<Directives>,
+    pub variable: Variable,
+    pub graphql_type: GraphQLType,
+    pub default_value: Option<DefaultValue>,
+    pub directives: Option<Directives>,
 }
 
 impl Display for VariableDefinition {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(" ")?;
         f.write_str(&self.variable.to_string())?;
-        write_option(self.default_value.as_ref(), f)?;
+        f.write_str(": ")?;
+        self.graphql_type.fmt(f)?;
+        if let Some(default_value) = &self.default_value {
+            f.write_str(" = ")?;
+            default_value.fmt(f)?;
+        }
         write_option(self.directives.as_ref(), f)?;
         f.write_str(" ")
     }
@@ -2256,17 +2279,13 @@ impl<'a> TryFrom<Pair<'a, Rule>> for VariableDefinition {
             variable: Variable::try_from(iterator.next().unwrap())?,
             graphql_type: GraphQLType::try_from(iterator.next().unwrap())?,
             default_value: match iterator.peek() {
-                Some(item) => match item.as_rule() {
-                    Rule::default_value => Some(DefaultValue::try_from(iterator.next().unwrap())?),
-                    _ => unreachable!(),
-                },
-                None => None,
+                Some(item) if item.as_rule() == Rule::default_value => {
+                    Some(DefaultValue::try_from(iterator.next().unwrap())?)
+                }
+                _ => None,
             },
-            directives: match iterator.peek() {
-                Some(item) => match item.as_rule() {
-                    Rule::directives => Some(Directives::try_from(iterator.next().unwrap())?),
-                    _ => unreachable!(),
-                },
+            directives: match iterator.next() {
+                Some(item) => Some(Directives::try_from(item)?),
                 None => None,
             },
         })
@@ -2337,13 +2356,33 @@ impl<'a> TryFrom<Pair<'a, Rule>> for OperationDefinition {
 
     fn try_from(pair: Pair<Rule>) -> Result<Self, Self::Error> {
         let mut iterator = pair.into_inner();
+        let first = iterator.peek().unwrap();
+        if first.as_rule() == Rule::selection_set {
+            // the shorthand `{ ... }`, which is an anonymous query
+            let start = first.as_span().start();
+            return Ok(Self {
+                operation_type: Token {
+                    token: OperationType::Query,
+                    span: PrivateASTNodeSpan { start, stop: start },
+                },
+                name: None,
+                variable_definitions: None,
+                directives: None,
+                selection_set: SelectionSet::try_from(iterator.next().unwrap())?,
+            });
+        }
         Ok(Self {
             operation_type: Token::parse(iterator.next().unwrap())?,
             name: match iterator.peek().unwrap().as_rule() {
                 Rule::name => Some(Name::try_from(iterator.next().unwrap())?),
                 _ => None,
             },
-            variable_definitions: None,
+            variable_definitions: match iterator.peek().unwrap().as_rule() {
+                Rule::variable_definitions => {
+                    Some(VariableDefinitions::try_from(iterator.next().unwrap())?)
+                }
+                _ => None,
+            },
             directives: match iterator.peek().unwrap().as_rule() {
                 Rule::directives => Some(Directives::try_from(iterator.next().unwrap())?),
                 _ => None,
@@ -2376,11 +2415,12 @@ impl<'a> TryFrom<Pair<'a, Rule>> for FragmentName {
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct TypeCondition {
-    named_type: NamedType,
+    pub named_type: Token<NamedType>,
 }
 
 impl Display for TypeCondition {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("on ")?;
         self.named_type.fmt(f)
     }
 }
@@ -2391,15 +2431,15 @@ impl<'a> TryFrom<Pair<'a, Rule>> for TypeCondition {
     fn try_from(pair: Pair<Rule>) -> Result<Self, Self::Error> {
         let mut iterator = pair.into_inner();
         Ok(Self {
-            named_type: NamedType::try_from(iterator.next().unwrap())?,
+            named_type: Token::parse(iterator.next().unwrap())?,
         })
     }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct FragmentSpread {
-    fragment_name: FragmentName,
-    directives: Option<Directives>,
+    pub fragment_name: Token<FragmentName>,
+    pub directives: Option<Directives>,
 }
 
 impl Display for FragmentSpread {
@@ -2416,7 +2456,7 @@ impl<'a> TryFrom<Pair<'a, Rule>> for FragmentSpread {
     fn try_from(pair: Pair<'a, Rule>) -> Result<Self, Self::Error> {
         let mut iterator = pair.into_inner();
         Ok(Self {
-            fragment_name: FragmentName::try_from(iterator.next().unwrap())?,
+            fragment_name: Token::parse(iterator.next().unwrap())?,
             directives: match iterator.next() {
                 Some(t) => Some(Directives::try_from(t)?),
                 None => None,
@@ -2427,14 +2467,14 @@ impl<'a> TryFrom<Pair<'a, Rule>> for FragmentSpread {
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct InlineFragment {
-    type_condition: Option<TypeCondition>,
-    directives: Option<Directives>,
-    selection_set: SelectionSet,
+    pub type_condition: Option<TypeCondition>,
+    pub directives: Option<Directives>,
+    pub selection_set: SelectionSet,
 }
 
 impl Display for InlineFragment {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str("...")?;
+        f.write_str("... ")?;
         write_option(self.type_condition.as_ref(), f)?;
         write_option(self.directives.as_ref(), f)?;
         self.selection_set.fmt(f)
@@ -2493,7 +2533,7 @@ impl<'a> TryFrom<Pair<'a, Rule>> for Selection {
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct SelectionSet(Vec<Selection>);
+pub struct SelectionSet(pub Vec<Selection>);
 
 impl Display for SelectionSet {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -2510,15 +2550,9 @@ impl<'a> TryFrom<Pair<'a, Rule>> for SelectionSet {
     type Error = Error<Rule>;
 
     fn try_from(pair: Pair<Rule>) -> Result<Self, Self::Error> {
-        let mut iterator = pair.into_inner();
-        // ignore opening bracket
-        iterator.next().unwrap();
         let mut output = vec![];
-        for item in iterator {
-            match item.as_rule() {
-                Rule::selection => output.push(Selection::try_from(item)?),
-                _ => return Ok(Self(output)),
-            }
+        for item in pair.into_inner() {
+            output.push(Selection::try_from(item)?);
         }
         Ok(Self(output))
     }
@@ -2527,16 +2561,17 @@ impl<'a> TryFrom<Pair<'a, Rule>> for SelectionSet {
 #[derive(Clone, Debug, Deserialize, Serialize)]
 /// A GraphQL fragment.
 pub struct FragmentDefinition {
-    fragment_name: FragmentName,
-    type_condition: TypeCondition,
-    directives: Option<Directives>,
-    selection_set: SelectionSet,
+    pub fragment_name: FragmentName,
+    pub type_condition: TypeCondition,
+    pub directives: Option<Directives>,
+    pub selection_set: SelectionSet,
 }
 
 impl Display for FragmentDefinition {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str("fragment ")?;
         self.fragment_name.fmt(f)?;
+        f.write_str(" ")?;
         self.type_condition.fmt(f)?;
         write_option(self.directives.as_ref(), f)?;
         self.selection_set.fmt(f)
@@ -2771,7 +2806,7 @@ impl<'a> TryFrom<Pair<'a, Rule>> for Document {
     fn try_from(pair: Pair<Rule>) -> Result<Self, Self::Error> {
         let iterator = pair.into_inner();
         let mut definitions = vec![];
-        for item in iterator {
+        for item in iterator.filter(|item| item.as_rule() != Rule::EOI) {
             definitions.push(Definition::try_from(item)?);
         }
         Ok(Self(definitions))
@@ -2779,40 +2814,48 @@ impl<'a> TryFrom<Pair<'a, Rule>> for Document {
 }
 
 #[derive(ThisError, Debug)]
-/// An error encountered when saving a document to the cache.
+/// An error encountered when saving a document to (or loading a document from) the cache.
 pub enum CacheError {
     #[error("io error")]
     IoError(std::io::Error),
     #[error("data serialization error")]
-    SerializeError(serde_json::Error),
+    SerializeError(bincode::Error),
 }
 
 impl Document {
+    /// Writes this document to a file (in a compact binary format), replacing whatever was there.
     pub fn save_to_cache<P>(&self, path: P) -> Result<(), CacheError>
     where
         P: AsRef<Path>,
     {
-        let file = match std::fs::File::open(path) {
+        let file = match std::fs::File::create(path) {
             Ok(t) => t,
             Err(e) => return Err(CacheError::IoError(e)),
         };
-        match serde_json::to_writer(file, self) {
+        match bincode::Options::serialize_into(
+            bincode::options(),
+            std::io::BufWriter::new(file),
+            self,
+        ) {
             Ok(_) => Ok(()),
-            Err(e) => return Err(CacheError::SerializeError(e)),
+            Err(e) => Err(CacheError::SerializeError(e)),
         }
     }
 
+    /// Reads a document which was written by `save_to_cache`.
     pub fn retrieve_from_cache<P>(path: P) -> Result<Self, CacheError>
     where
         P: AsRef<Path>,
     {
-        let file = match std::fs::File::open(path) {
+        let bytes = match std::fs::read(path) {
             Ok(t) => t,
             Err(e) => return Err(CacheError::IoError(e)),
         };
-        match serde_json::from_reader(file) {
+        // limiting the size means that a corrupted file can't make us allocate lots of memory
+        let options = bincode::Options::with_limit(bincode::options(), bytes.len() as u64);
+        match bincode::Options::deserialize(options, &bytes) {
             Ok(t) => Ok(t),
-            Err(e) => return Err(CacheError::SerializeError(e)),
+            Err(e) => Err(CacheError::SerializeError(e)),
         }
     }
 }
@@ -0,0 +1,258 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+
+//! An on-disk cache of parsed schemas.
+//!
+//! Every derive macro needs the schema, and although each compiler process only parses it once
+//! (see the macros crate), `cargo check` starts a new process for every crate and every time
+//! something changes. Parsing a large schema takes much longer than reading the parsed form back,
+//! so parsed schemas are stored (using `bincode`) in files named after a hash of their source.
+//! The cache is only used when there's somewhere safe to put it (see
+//! `SchemaCache::from_environment`) – the easiest way to get one is to use `myoxine_build`.
+//!
+//! Entries are only used if they were written by the same version of this crate from exactly the
+//! same source, so there's no need to ever clear the cache by hand (although deleting it is always
+//! safe).
+
+use std::collections::hash_map::DefaultHasher;
+use std::ffi::OsString;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use bincode::Options;
+use serde::{Deserialize, Serialize};
+
+use crate::ast::{CacheError, Document, Rule};
+
+/// The environment variable which says where the cache should go (see
+/// `SchemaCache::from_environment`).
+pub const CACHE_DIR_VARIABLE: &str = "MYOXINE_CACHE_DIR";
+
+/// Changes whenever the format of cached documents might have changed.
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// What is actually stored in each file. The key is stored as well as being used in the name of the
+/// file, so that (unlikely as it is) two schemas with the same hash can't be confused.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    version: String,
+    hash: u64,
+    length: usize,
+    document: Document,
+}
+
+/// A directory containing parsed schemas.
+#[derive(Debug, Clone)]
+pub struct SchemaCache {
+    directory: PathBuf,
+}
+
+impl SchemaCache {
+    pub fn new<P>(directory: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    /// The cache which is used when compiling a crate. This is `$MYOXINE_CACHE_DIR` (which
+    /// `myoxine_build::Builder::compile` points at a directory in `OUT_DIR`) or, failing that,
+    /// `myoxine/schemas` inside `$CARGO_TARGET_DIR`.
+    ///
+    /// Cargo doesn't tell the compiler where the target directory is, and guessing goes wrong –
+    /// for a dependency from crates.io the "workspace" is somewhere in `~/.cargo/registry`, which
+    /// mustn't be written to – so if neither of them is set nothing is cached. Nothing is ever
+    /// cached inside Cargo's home directory either.
+    pub fn from_environment() -> Option<Self> {
+        Self::from_variables(|name| std::env::var_os(name))
+    }
+
+    /// Does the work for `from_environment`, getting environment variables from `var`.
+    fn from_variables<F>(var: F) -> Option<Self>
+    where
+        F: Fn(&str) -> Option<OsString>,
+    {
+        let directory = match var(CACHE_DIR_VARIABLE) {
+            Some(directory) => PathBuf::from(directory),
+            None => PathBuf::from(var("CARGO_TARGET_DIR")?)
+                .join("myoxine")
+                .join("schemas"),
+        };
+        if !directory.is_absolute() {
+            // (relative paths would depend on whichever directory the compiler happens to be in)
+            return None;
+        }
+        let cargo_home = var("CARGO_HOME")
+            .map(PathBuf::from)
+            .or_else(|| var("HOME").map(|home| PathBuf::from(home).join(".cargo")));
+        if cargo_home.is_some_and(|cargo_home| directory.starts_with(cargo_home)) {
+            return None;
+        }
+        Some(Self::new(directory))
+    }
+
+    /// Parses a schema, using the cached version if there is one (and caching the result if there
+    /// isn't). Problems with the cache are ignored, because the schema can always be parsed again.
+    pub fn parse(&self, source: &str) -> Result<Document, pest::error::Error<Rule>> {
+        if let Some(document) = self.get(source) {
+            return Ok(document);
+        }
+        let document = crate::parse_string(source)?;
+        let _ = self.insert(source, &document);
+        Ok(document)
+    }
+
+    /// Retrieves the parsed form of `source`, if it has been cached.
+    pub fn get(&self, source: &str) -> Option<Document> {
+        let bytes = std::fs::read(self.path(source)).ok()?;
+        // the limit stops corrupted files (which might claim to contain an enormous string) from
+        // using up all of the memory on the system
+        let entry: Entry = bincode::options()
+            .with_limit(bytes.len() as u64)
+            .deserialize(&bytes)
+            .ok()?;
+        if entry.version == VERSION && entry.hash == hash(source) && entry.length == source.len() {
+            Some(entry.document)
+        } else {
+            None
+        }
+    }
+
+    /// Stores the parsed form of `source`.
+    pub fn insert(&self, source: &str, document: &Document) -> Result<(), CacheError> {
+        std::fs::create_dir_all(&self.directory).map_err(CacheError::IoError)?;
+        let entry = Entry {
+            version: VERSION.to_string(),
+            hash: hash(source),
+            length: source.len(),
+            document: document.clone(),
+        };
+        // several copies of the compiler can be running at once, so the entry is written to a
+        // temporary file and then moved into place (so nobody ever sees half of an entry)
+        let path = self.path(source);
+        let temporary = path.with_extension(format!("{}.tmp", std::process::id()));
+        let file = std::fs::File::create(&temporary).map_err(CacheError::IoError)?;
+        bincode::options()
+            .serialize_into(std::io::BufWriter::new(file), &entry)
+            .map_err(CacheError::SerializeError)?;
+        std::fs::rename(&temporary, &path).map_err(CacheError::IoError)
+    }
+
+    /// The directory the cache is stored in.
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    fn path(&self, source: &str) -> PathBuf {
+        self.directory.join(format!("{:016x}.bin", hash(source)))
+    }
+}
+
+/// Hashes the source of a schema (along with the version of this crate, so that different versions
+/// don't keep overwriting each other's entries).
+fn hash(source: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    VERSION.hash(&mut hasher);
+    source.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod test_cache {
+    use super::*;
+
+    fn cache(name: &str) -> SchemaCache {
+        let directory = std::env::temp_dir()
+            .join(format!("myoxine-test-{}", std::process::id()))
+            .join(name);
+        let _ = std::fs::remove_dir_all(&directory);
+        SchemaCache::new(directory)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let cache = cache("round-trip");
+        let source = "type Query { user(id: ID!): User } type User { id: ID! }";
+        assert!(cache.get(source).is_none());
+        let parsed = cache.parse(source).unwrap();
+        let cached = cache.get(source).expect("should have been cached");
+        assert_eq!(cached.to_string(), parsed.to_string());
+        // a different schema is a different entry
+        assert!(cache.get("type Query { id: ID! }").is_none());
+        // and errors aren't cached
+        assert!(cache.parse("type Query {").is_err());
+        assert_eq!(std::fs::read_dir(cache.directory()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_location() {
+        let location = |variables: &[(&str, &str)]| {
+            SchemaCache::from_variables(|name| {
+                variables
+                    .iter()
+                    .find(|(variable, _)| *variable == name)
+                    .map(|(_, value)| OsString::from(value))
+            })
+            .map(|cache| cache.directory().to_path_buf())
+        };
+        let home = [("HOME", "/home/jane")];
+        // without being told where to go, nothing is cached (rather than guessing)
+        assert_eq!(location(&home), None);
+        assert_eq!(
+            location(&[("HOME", "/home/jane"), ("CARGO_TARGET_DIR", "/work/target")]),
+            Some(PathBuf::from("/work/target/myoxine/schemas"))
+        );
+        assert_eq!(
+            location(&[
+                ("CARGO_TARGET_DIR", "/work/target"),
+                (
+                    CACHE_DIR_VARIABLE,
+                    "/work/target/debug/build/app/out/schemas"
+                ),
+            ]),
+            Some(PathBuf::from("/work/target/debug/build/app/out/schemas"))
+        );
+        assert_eq!(location(&[("CARGO_TARGET_DIR", "target")]), None);
+        // Cargo's home directory (e.g. the sources of dependencies) is never written to
+        assert_eq!(
+            location(&[
+                ("HOME", "/home/jane"),
+                (CACHE_DIR_VARIABLE, "/home/jane/.cargo/registry/src/myoxine"),
+            ]),
+            None
+        );
+        assert_eq!(
+            location(&[
+                ("CARGO_HOME", "/opt/cargo"),
+                ("CARGO_TARGET_DIR", "/opt/cargo/git/checkouts/app/target"),
+            ]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_stale_entries_are_ignored() {
+        let cache = cache("stale");
+        let source = "type Query { id: ID! }";
+        let path = cache.path(source);
+        cache.parse(source).unwrap();
+        // an entry from another version (or a corrupted one) is treated as a miss...
+        let mut entry: Entry = bincode::options()
+            .deserialize(&std::fs::read(&path).unwrap())
+            .unwrap();
+        entry.version = "0.0.0-old".to_string();
+        std::fs::write(&path, bincode::options().serialize(&entry).unwrap()).unwrap();
+        assert!(cache.get(source).is_none());
+        std::fs::write(&path, b"nonsense").unwrap();
+        assert!(cache.get(source).is_none());
+        // ...and replaced the next time the schema is parsed
+        cache.parse(source).unwrap();
+        assert!(cache.get(source).is_some());
+    }
+}
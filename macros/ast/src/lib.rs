@@ -21,6 +21,11 @@ use std::fs::read_to_string;
 use std::path::Path;
 
 pub mod ast;
+pub mod cache;
+pub mod sources;
+pub mod suggest;
+pub mod tokens;
+pub mod validate;
 
 pub mod prelude {
     pub use crate::ast::*;
@@ -38,7 +43,7 @@ pub enum ParseFileError {
 
 /// Parse a schema from a provided file.
 ///
-/// Schemas must be contained in a single source file.
+/// This only reads one file – see `sources` for schemas which are split across several.
 ///
 /// If you already have a GraphQL server you can automatically generate a schema, without the need
 /// to create one by hand. This is strongly advised as it will save you a lot of time.
@@ -50,7 +55,7 @@ where
         Ok(string) => string,
         Err(e) => return Err(ParseFileError::FileOpenError(e)),
     };
-    parse_string(string).map_err(|error| ParseFileError::ParseError(error))
+    parse_string(string).map_err(ParseFileError::ParseError)
 }
 
 pub fn parse_string<P>(
@@ -65,3 +70,54 @@ where
 
     Document::try_from(parsed)
 }
+
+/// Finds where a type is defined in the source of a schema, returning the line and column (both
+/// starting at one) of the start of its definition. This is used to point people at the relevant
+/// part of their schema in error messages.
+pub fn type_definition_position(source: &str, name: &str) -> Option<(usize, usize)> {
+    definition_position(
+        source,
+        &[
+            ast::Rule::scalar_type_definition,
+            ast::Rule::object_type_definition,
+            ast::Rule::interface_type_definition,
+            ast::Rule::union_type_definition,
+            ast::Rule::enum_type_definition,
+            ast::Rule::input_object_type_definition,
+        ],
+        name,
+    )
+}
+
+/// Like `type_definition_position`, but for fragments.
+pub fn fragment_definition_position(source: &str, name: &str) -> Option<(usize, usize)> {
+    definition_position(source, &[ast::Rule::fragment_definition], name)
+}
+
+fn definition_position(source: &str, rules: &[ast::Rule], name: &str) -> Option<(usize, usize)> {
+    let document = GraphQLParser::parse(ast::Rule::document, source).ok()?;
+    document
+        .flatten()
+        .filter(|pair| rules.contains(&pair.as_rule()))
+        .find(|pair| {
+            pair.clone()
+                .into_inner()
+                .find(|inner| {
+                    inner.as_rule() == ast::Rule::name
+                        || inner.as_rule() == ast::Rule::fragment_name
+                })
+                .map(|inner| inner.as_str() == name)
+                .unwrap_or(false)
+        })
+        .map(|pair| pair.as_span().start_pos().line_col())
+}
+
+/// Converts a byte offset into `source` into a line and column (both starting at one).
+///
+/// This is useful when we can't point directly at the part of a query which is wrong, so have to
+/// describe where it is in the error message instead.
+pub fn line_col(source: &str, offset: usize) -> (usize, usize) {
+    pest::Position::new(source, offset.min(source.len()))
+        .map(|position| position.line_col())
+        .unwrap_or((1, 1))
+}
@@ -0,0 +1,306 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+
+//! Support for GraphQL which is split across several files – e.g. a schema with a file for each
+//! part of the domain (`schema/*.graphql`), or fragments which are shared between queries.
+//!
+//! The files are parsed separately and then merged into one document. Every file is kept around
+//! so that error messages can say which file (and where in it) something came from.
+
+use std::path::{Path, PathBuf};
+
+use thiserror::Error as ThisError;
+
+use crate::ast::{Definition, Document, ExecutableDefinition, Rule, TypeSystemDefinition};
+
+#[derive(ThisError, Debug)]
+pub enum SourceError {
+    #[error("couldn't read `{path}`: {error}")]
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    #[error("`{0}` didn't match any files")]
+    NoMatches(PathBuf),
+    #[error("couldn't parse `{path}`:\n{message}")]
+    Parse { path: PathBuf, message: String },
+    #[error("{0}")]
+    Duplicate(String),
+}
+
+/// A file containing some GraphQL.
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub source: String,
+}
+
+/// Expands a pattern into the paths it matches, sorted so that the output is deterministic.
+///
+/// `*` matches any number of characters and `?` any single character, but only within one
+/// component of the path (so `schema/*.graphql` doesn't look inside subdirectories of `schema`).
+/// Paths without either are returned as they are, whether or not they exist.
+pub fn glob(pattern: &Path) -> Result<Vec<PathBuf>, SourceError> {
+    let is_pattern = |component: &str| component.contains('*') || component.contains('?');
+    if !is_pattern(&pattern.to_string_lossy()) {
+        return Ok(vec![pattern.to_path_buf()]);
+    }
+    let mut matches = vec![PathBuf::new()];
+    for component in pattern.components() {
+        let component = component.as_os_str().to_string_lossy();
+        if !is_pattern(&component) {
+            for path in &mut matches {
+                path.push(component.as_ref());
+            }
+            continue;
+        }
+        let mut next = vec![];
+        for directory in matches {
+            let listing = if directory.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                &directory
+            };
+            let entries = match std::fs::read_dir(listing) {
+                Ok(entries) => entries,
+                // directories matched by an earlier component might not have anything inside them
+                Err(_) => continue,
+            };
+            for entry in entries {
+                let entry = entry.map_err(|error| SourceError::Io {
+                    path: listing.to_path_buf(),
+                    error,
+                })?;
+                if matches_pattern(&component, &entry.file_name().to_string_lossy()) {
+                    next.push(directory.join(entry.file_name()));
+                }
+            }
+        }
+        matches = next;
+    }
+    if matches.is_empty() {
+        return Err(SourceError::NoMatches(pattern.to_path_buf()));
+    }
+    matches.sort();
+    Ok(matches)
+}
+
+/// Checks whether a file name matches a pattern containing `*` and `?`.
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    // `matched[j]` is whether the pattern so far matches the first `j` characters of the name
+    let mut matched = vec![false; name.len() + 1];
+    matched[0] = true;
+    for p in pattern {
+        let mut next = vec![false; name.len() + 1];
+        for j in 0..=name.len() {
+            next[j] = match p {
+                '*' => matched[j] || (j > 0 && next[j - 1]),
+                '?' => j > 0 && matched[j - 1],
+                c => j > 0 && matched[j - 1] && name[j - 1] == c,
+            };
+        }
+        matched = next;
+    }
+    matched[name.len()]
+}
+
+/// Reads every file matched by any of the patterns (see `glob`), leaving out duplicates.
+pub fn read<P>(patterns: &[P]) -> Result<Vec<SourceFile>, SourceError>
+where
+    P: AsRef<Path>,
+{
+    let mut files: Vec<SourceFile> = vec![];
+    for pattern in patterns {
+        for path in glob(pattern.as_ref())? {
+            if files.iter().any(|file| file.path == path) {
+                continue;
+            }
+            let source = std::fs::read_to_string(&path).map_err(|error| SourceError::Io {
+                path: path.clone(),
+                error,
+            })?;
+            files.push(SourceFile { path, source });
+        }
+    }
+    Ok(files)
+}
+
+/// Parses each file (using `parse`, so that callers can cache the results) and merges them into a
+/// single document. A type or fragment defined in one file can't be defined again in another.
+pub fn parse<F>(files: &[SourceFile], parse: F) -> Result<Document, SourceError>
+where
+    F: FnMut(&str) -> Result<Document, pest::error::Error<Rule>>,
+{
+    let mut merged = Document::default();
+    for document in parse_each(files, parse)? {
+        merged.0.extend(document.0);
+    }
+    Ok(merged)
+}
+
+/// Like `parse`, but returns the document from each file separately (in the same order as
+/// `files`).
+pub fn parse_each<F>(files: &[SourceFile], mut parse: F) -> Result<Vec<Document>, SourceError>
+where
+    F: FnMut(&str) -> Result<Document, pest::error::Error<Rule>>,
+{
+    let mut documents = vec![];
+    // the name of each definition, along with the file it came from
+    let mut defined: Vec<(String, &SourceFile)> = vec![];
+    for file in files {
+        let document = parse(&file.source).map_err(|error| SourceError::Parse {
+            path: file.path.clone(),
+            message: error
+                .with_path(&file.path.display().to_string())
+                .to_string(),
+        })?;
+        for definition in &document.0 {
+            let name = match definition_name(definition) {
+                Some(name) => name,
+                None => continue,
+            };
+            // (duplicates inside one file are left for the server to complain about)
+            if let Some((_, first)) = defined
+                .iter()
+                .find(|(defined, other)| defined == name && other.path != file.path)
+            {
+                return Err(SourceError::Duplicate(format!(
+                    "`{}` is defined more than once (at {} and at {}).",
+                    name,
+                    location(first, name),
+                    location(file, name)
+                )));
+            }
+            defined.push((name.to_string(), file));
+        }
+        documents.push(document);
+    }
+    Ok(documents)
+}
+
+/// The name of a type or fragment definition (other kinds of definition are allowed to appear more
+/// than once, or don't have names).
+fn definition_name(definition: &Definition) -> Option<&str> {
+    match definition {
+        Definition::TypeSystemDefinition(TypeSystemDefinition::TypeDefinition(definition)) => {
+            Some(&definition.name().0)
+        }
+        Definition::ExecutableDefinition(ExecutableDefinition::FragmentDefinition(definition)) => {
+            Some(&definition.fragment_name.name.0)
+        }
+        _ => None,
+    }
+}
+
+/// Finds the file (along with the line and column) where a type or fragment is defined.
+pub fn find_definition<'a>(
+    files: &'a [SourceFile],
+    name: &str,
+) -> Option<(&'a Path, usize, usize)> {
+    files.iter().find_map(|file| {
+        crate::type_definition_position(&file.source, name)
+            .or_else(|| crate::fragment_definition_position(&file.source, name))
+            .map(|(line, column)| (file.path.as_path(), line, column))
+    })
+}
+
+/// Describes where something is defined in a file (e.g. `schema/users.graphql:3:1`).
+fn location(file: &SourceFile, name: &str) -> String {
+    let position = crate::type_definition_position(&file.source, name)
+        .or_else(|| crate::fragment_definition_position(&file.source, name));
+    match position {
+        Some((line, column)) => format!("{}:{}:{}", file.path.display(), line, column),
+        None => file.path.display().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test_sources {
+    use super::*;
+
+    fn file(path: &str, source: &str) -> SourceFile {
+        SourceFile {
+            path: path.into(),
+            source: source.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_patterns() {
+        assert!(matches_pattern("*.graphql", "users.graphql"));
+        assert!(matches_pattern("user?.graphql", "users.graphql"));
+        assert!(matches_pattern("*", ""));
+        assert!(!matches_pattern("*.graphql", "users.graphql.bak"));
+        assert!(!matches_pattern("user?.graphql", "user.graphql"));
+    }
+
+    #[test]
+    fn test_glob() {
+        let directory = std::env::temp_dir().join(format!("myoxine-glob-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(directory.join("schema")).unwrap();
+        for name in &["users.graphql", "posts.graphql", "README.md"] {
+            std::fs::write(directory.join("schema").join(name), "").unwrap();
+        }
+        assert_eq!(
+            glob(&directory.join("schema/*.graphql")).unwrap(),
+            vec![
+                directory.join("schema/posts.graphql"),
+                directory.join("schema/users.graphql")
+            ]
+        );
+        assert_eq!(
+            glob(&directory.join("*/users.*")).unwrap(),
+            vec![directory.join("schema/users.graphql")]
+        );
+        assert!(matches!(
+            glob(&directory.join("schema/*.json")),
+            Err(SourceError::NoMatches(_))
+        ));
+    }
+
+    #[test]
+    fn test_merging() {
+        let files = vec![
+            file("schema/query.graphql", "type Query { user(id: ID!): User }"),
+            file("schema/users.graphql", "type User { id: ID! }"),
+        ];
+        let document = parse(&files, |source| crate::parse_string(source)).unwrap();
+        assert_eq!(document.type_definitions().count(), 2);
+        assert_eq!(
+            find_definition(&files, "User"),
+            Some((Path::new("schema/users.graphql"), 1, 1))
+        );
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let files = vec![
+            file("a.graphql", "type User { id: ID! }"),
+            file(
+                "b.graphql",
+                "type Post { id: ID! }\n\ntype User { name: String }",
+            ),
+        ];
+        assert_eq!(
+            parse(&files, |source| crate::parse_string(source))
+                .unwrap_err()
+                .to_string(),
+            "`User` is defined more than once (at a.graphql:1:1 and at b.graphql:3:1)."
+        );
+        let files = vec![
+            file("a.graphql", "fragment UserFields on User { id }"),
+            file("b.graphql", "fragment UserFields on User { name }"),
+        ];
+        assert!(matches!(
+            parse(&files, |source| crate::parse_string(source)),
+            Err(SourceError::Duplicate(_))
+        ));
+    }
+}
@@ -0,0 +1,104 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+
+//! "Did you mean ...?" suggestions for error messages, for when someone has misspelled the name of
+//! a field or a type.
+
+/// Computes the Levenshtein (edit) distance between two strings – the number of characters which
+/// need to be inserted, removed or replaced to turn one into the other. Swapping two adjacent
+/// characters also counts as a single edit (this is the "optimal string alignment" variant), as
+/// transposed letters are one of the most common typos.
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    // distances[i][j] is the distance between the first `i` characters of `a` and the first `j`
+    // characters of `b`
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+/// Finds the candidate which is closest to `name`, as long as it is close enough to plausibly be
+/// what was meant (differences in case are ignored when deciding this, because `userId` vs `userID`
+/// is a very easy mistake to make).
+pub fn did_you_mean<'a, I>(name: &str, candidates: I) -> Option<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let threshold = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .map(|candidate| {
+            (
+                levenshtein(&name.to_lowercase(), &candidate.to_lowercase()),
+                candidate,
+            )
+        })
+        .filter(|(distance, _)| *distance <= threshold)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Formats a suggestion as a sentence to be appended to an error message (or returns an empty
+/// string if there is nothing to suggest).
+pub fn suggestion<'a, I>(name: &str, candidates: I) -> String
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    match did_you_mean(name, candidates) {
+        Some(candidate) => format!(" Did you mean `{}`?", candidate),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod test_suggestions {
+    use super::*;
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(levenshtein("same", "same"), 0);
+        assert_eq!(levenshtein("frist", "first"), 1);
+    }
+
+    #[test]
+    fn test_did_you_mean() {
+        let fields = ["id", "username", "address"];
+        assert_eq!(
+            did_you_mean("usrname", fields.iter().copied()),
+            Some("username")
+        );
+        assert_eq!(
+            did_you_mean("userName", fields.iter().copied()),
+            Some("username")
+        );
+        assert_eq!(did_you_mean("email", fields.iter().copied()), None);
+        assert_eq!(
+            suggestion("adress", fields.iter().copied()),
+            " Did you mean `address`?"
+        );
+    }
+}
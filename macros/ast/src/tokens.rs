@@ -0,0 +1,208 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+
+//! Parses GraphQL which has been written directly inside a Rust macro (e.g.
+//! `query! { query UserPage($id: ID!) { user(id: $id) { username } } }`) rather than in a string.
+//!
+//! This works by writing the tokens back out as GraphQL source (remembering which part of the
+//! source each token became) and then using the normal parser. Because we know where every token
+//! came from, anything which has a range in the source (parse errors, the `Token`s in the AST and
+//! validation errors) can be mapped back to a `Span` which points at real Rust source code.
+//!
+//! There are a few things which can't be written this way, because Rust tokenizes them differently
+//! – `#` comments (use `//` comments instead) and block strings (`"""`) are the main ones.
+
+use std::ops::Range;
+
+use proc_macro2::{Delimiter, Spacing, Span, TokenStream, TokenTree};
+
+use crate::ast::{Document, Rule};
+
+/// GraphQL source which has been reconstructed from a `TokenStream`, along with the span of every
+/// token in it.
+#[derive(Debug, Clone)]
+pub struct TokenSource {
+    source: String,
+    spans: Vec<(Range<usize>, Span)>,
+}
+
+/// A problem parsing the tokens. `span` points at the token which the parser didn't expect.
+#[derive(Debug, Clone)]
+pub struct TokenParseError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl std::fmt::Display for TokenParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for TokenParseError {}
+
+/// Parses a GraphQL document from a `TokenStream`, returning it along with the `TokenSource` it
+/// was parsed from (which is needed to find the spans of things in the document).
+pub fn parse_tokens(tokens: TokenStream) -> Result<(Document, TokenSource), TokenParseError> {
+    let source = TokenSource::new(tokens);
+    match crate::parse_string(&source.source) {
+        Ok(document) => Ok((document, source)),
+        Err(error) => Err(TokenParseError {
+            message: format!("Couldn't parse this GraphQL: {}", error.variant.message()),
+            span: source.error_span(&error),
+        }),
+    }
+}
+
+impl TokenSource {
+    pub fn new(tokens: TokenStream) -> Self {
+        let mut source = Self {
+            source: String::new(),
+            spans: vec![],
+        };
+        source.push_stream(tokens, &mut false);
+        source
+    }
+
+    /// The GraphQL source which the tokens were turned into.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Finds the span of a range of bytes in the source. If the range covers more than one token
+    /// the spans are joined (on compilers which support this; elsewhere the span of the first
+    /// token is used).
+    pub fn span(&self, range: Range<usize>) -> Span {
+        let mut overlapping = self.spans.iter().filter(|(token, _)| {
+            token.start < range.end.max(range.start + 1) && range.start < token.end
+        });
+        let first = match overlapping.next() {
+            Some((_, span)) => *span,
+            // this happens for things at the end of the input (e.g. a missing closing brace)
+            None => match self.spans.last() {
+                Some((_, span)) => return *span,
+                None => return Span::call_site(),
+            },
+        };
+        match overlapping.next_back() {
+            Some((_, last)) => first.join(*last).unwrap_or(first),
+            None => first,
+        }
+    }
+
+    /// Finds the span which a parse error is about.
+    pub fn error_span(&self, error: &pest::error::Error<Rule>) -> Span {
+        match error.location {
+            pest::error::InputLocation::Pos(position) => self.span(position..position + 1),
+            pest::error::InputLocation::Span((start, end)) => self.span(start..end),
+        }
+    }
+
+    /// Writes out a sequence of tokens. `glue` is set when the next token should be written
+    /// without any whitespace before it (e.g. the name after the `$` of a variable).
+    fn push_stream(&mut self, tokens: TokenStream, glue: &mut bool) {
+        for tree in tokens {
+            match tree {
+                TokenTree::Group(group) => {
+                    let (open, close) = match group.delimiter() {
+                        Delimiter::Brace => ("{", "}"),
+                        Delimiter::Parenthesis => ("(", ")"),
+                        Delimiter::Bracket => ("[", "]"),
+                        Delimiter::None => ("", ""),
+                    };
+                    self.push(open, group.span_open(), glue);
+                    self.push_stream(group.stream(), glue);
+                    self.push(close, group.span_close(), glue);
+                }
+                TokenTree::Ident(ref ident) => {
+                    let ident = ident.to_string();
+                    // `r#type` is how you would write a field called `type` in Rust, but there's
+                    // no need to do this in GraphQL
+                    let name = ident.strip_prefix("r#").unwrap_or(&ident);
+                    self.push(name, tree.span(), glue);
+                }
+                TokenTree::Punct(ref punct) => {
+                    self.push(&punct.as_char().to_string(), tree.span(), glue);
+                    // `...` is three joint `.` tokens, `$` always goes with the name of the
+                    // variable and `-` with the number it negates
+                    *glue =
+                        punct.spacing() == Spacing::Joint || matches!(punct.as_char(), '$' | '-');
+                }
+                TokenTree::Literal(literal) => {
+                    self.push(&literal.to_string(), literal.span(), glue);
+                }
+            }
+        }
+    }
+
+    fn push(&mut self, text: &str, span: Span, glue: &mut bool) {
+        if text.is_empty() {
+            return;
+        }
+        if !*glue && !self.source.is_empty() {
+            self.source.push(' ');
+        }
+        *glue = false;
+        let start = self.source.len();
+        self.source.push_str(text);
+        self.spans.push((start..self.source.len(), span));
+    }
+}
+
+#[cfg(test)]
+mod test_tokens {
+    use super::*;
+    use crate::validate::validate;
+
+    fn tokens(source: &str) -> TokenStream {
+        source.parse().expect("failed to tokenize")
+    }
+
+    /// The line and column (starting at one and zero respectively) where a span starts.
+    fn start(span: Span) -> (usize, usize) {
+        (span.start().line, span.start().column)
+    }
+
+    #[test]
+    fn test_reconstructed_source() {
+        let source = TokenSource::new(tokens(
+            r#"query UserPage($id: ID!, $first: Int = -1) {
+                user(id: $id) { ...UserFields ... on User { r#type } }
+                search(term: "x y", first: $first) @include(if: true) { __typename }
+            }"#,
+        ));
+        assert_eq!(
+            source.source(),
+            "query UserPage ( $id : ID !, $first : Int = -1 ) { user ( id : $id ) { ... UserFields \
+            ... on User { type } } search ( term : \"x y\" , first : $first ) @ include ( if : \
+            true ) { __typename } }"
+        );
+        crate::parse_string(source.source()).expect("should parse");
+    }
+
+    #[test]
+    fn test_spans_of_parse_errors() {
+        let error = parse_tokens(tokens("{ user(id: 1) { id } }\n  nonsense")).unwrap_err();
+        assert!(error.message.starts_with("Couldn't parse this GraphQL"));
+        assert_eq!(start(error.span), (2, 2));
+    }
+
+    #[test]
+    fn test_spans_of_validation_errors() {
+        let schema = crate::parse_string(
+            "type Query { user(id: ID!): User } type User { id: ID! username: String! }",
+        )
+        .unwrap();
+        let (document, source) =
+            parse_tokens(tokens("query { user(id: \"1\") {\n    usrname\n} }")).unwrap();
+        let errors = validate(&schema, &document);
+        assert_eq!(errors.len(), 1);
+        let range = errors[0].span.clone().unwrap();
+        assert_eq!(&source.source()[range.clone()], "usrname");
+        assert_eq!(start(source.span(range)), (2, 4));
+    }
+}
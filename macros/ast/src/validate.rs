@@ -0,0 +1,563 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+
+//! Checks that queries (and mutations, and fragments) make sense for a given schema.
+//!
+//! This doesn't implement every rule in the "Validation" section of the specification (yet), but
+//! it does catch the mistakes which are easiest to make – misspelled fields and arguments, missing
+//! or unnecessary selection sets, fragments on types which don't exist and undefined variables.
+
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::ops::Range;
+
+use crate::ast::{
+    Arguments, Definition, Document, ExecutableDefinition, FieldDefinition, FragmentDefinition,
+    Name, OperationDefinition, Selection, SelectionSet, TypeDefinition, Value,
+};
+use crate::suggest::suggestion;
+
+/// Something which is wrong with a query.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationError {
+    pub message: String,
+    /// The range of bytes in the query which the error is about, if we know it. This is used to
+    /// point at the exact part of the query which is wrong (rather than the whole thing).
+    pub span: Option<Range<usize>>,
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Validates every operation and fragment in `executable` against `schema`, returning all of the
+/// problems found (so an empty `Vec` means that everything is fine).
+pub fn validate(schema: &Document, executable: &Document) -> Vec<ValidationError> {
+    validate_with_fragments(schema, executable, &[])
+}
+
+/// Like `validate`, but the operations in `executable` can also use fragments from `imported`
+/// (which are usually defined in other files). The imported fragments should be validated
+/// separately – this only checks that they exist and aren't defined twice.
+pub fn validate_with_fragments(
+    schema: &Document,
+    executable: &Document,
+    imported: &[&FragmentDefinition],
+) -> Vec<ValidationError> {
+    let mut validator = Validator {
+        schema,
+        fragments: executable.fragments().collect(),
+        imported: imported.to_vec(),
+        errors: vec![],
+        variables: None,
+        visited_fragments: HashSet::new(),
+    };
+    validator.check_unique_names(executable);
+    for definition in &executable.0 {
+        match definition {
+            Definition::ExecutableDefinition(ExecutableDefinition::OperationDefinition(op)) => {
+                validator.validate_operation(op)
+            }
+            Definition::ExecutableDefinition(ExecutableDefinition::FragmentDefinition(def)) => {
+                validator.variables = None;
+                validator.visited_fragments.clear();
+                validator.validate_fragment(def)
+            }
+            _ => {}
+        }
+    }
+    validator.errors
+}
+
+impl Document {
+    /// Iterates over the fragments defined in this document.
+    pub fn fragments(&self) -> impl Iterator<Item = &FragmentDefinition> {
+        self.0.iter().filter_map(|definition| match definition {
+            Definition::ExecutableDefinition(ExecutableDefinition::FragmentDefinition(def)) => {
+                Some(def)
+            }
+            _ => None,
+        })
+    }
+    /// Iterates over the operations (queries, mutations and subscriptions) in this document.
+    pub fn operations(&self) -> impl Iterator<Item = &OperationDefinition> {
+        self.0.iter().filter_map(|definition| match definition {
+            Definition::ExecutableDefinition(ExecutableDefinition::OperationDefinition(def)) => {
+                Some(def)
+            }
+            _ => None,
+        })
+    }
+}
+
+struct Validator<'a> {
+    schema: &'a Document,
+    fragments: Vec<&'a FragmentDefinition>,
+    /// Fragments from other documents, which can be used but aren't validated here.
+    imported: Vec<&'a FragmentDefinition>,
+    errors: Vec<ValidationError>,
+    /// The variables defined by the operation being validated (`None` inside a fragment
+    /// definition, where we can't know which variables will be available).
+    variables: Option<HashSet<&'a Name>>,
+    /// Used to avoid following fragment spreads around in circles.
+    visited_fragments: HashSet<&'a Name>,
+}
+
+impl<'a> Validator<'a> {
+    fn error(&mut self, message: String) {
+        self.errors.push(ValidationError {
+            message,
+            span: None,
+        })
+    }
+
+    fn error_at(&mut self, span: Range<usize>, message: String) {
+        self.errors.push(ValidationError {
+            message,
+            span: Some(span),
+        })
+    }
+
+    fn check_unique_names(&mut self, executable: &'a Document) {
+        let mut operations = HashSet::new();
+        for operation in executable.operations() {
+            if let Some(name) = &operation.name {
+                if !operations.insert(name) {
+                    self.error(format!(
+                        "There is more than one operation called `{}`.",
+                        name
+                    ));
+                }
+            }
+        }
+        let mut fragments = HashSet::new();
+        for fragment in executable.fragments() {
+            if !fragments.insert(&fragment.fragment_name.name) {
+                self.error(format!(
+                    "There is more than one fragment called `{}`.",
+                    fragment.fragment_name
+                ));
+            }
+            if self
+                .imported
+                .iter()
+                .any(|imported| imported.fragment_name.name == fragment.fragment_name.name)
+            {
+                self.error(format!(
+                    "The fragment `{}` is defined here, but is also imported from somewhere else.",
+                    fragment.fragment_name
+                ));
+            }
+        }
+    }
+
+    fn validate_operation(&mut self, operation: &'a OperationDefinition) {
+        self.variables = Some(
+            operation
+                .variable_definitions
+                .iter()
+                .flat_map(|definitions| definitions.0.iter())
+                .map(|definition| &definition.variable.0)
+                .collect(),
+        );
+        self.visited_fragments.clear();
+        match self
+            .schema
+            .root_operation_type(operation.operation_type.token)
+        {
+            Some(root) => self.validate_selection_set(&root, &operation.selection_set),
+            None => self.error_at(
+                operation.operation_type.range(),
+                format!(
+                    "The schema doesn't support `{}` operations.",
+                    operation.operation_type.token
+                ),
+            ),
+        }
+    }
+
+    fn validate_fragment(&mut self, fragment: &'a FragmentDefinition) {
+        let type_name = &fragment.type_condition.named_type.0;
+        if self.check_composite(type_name, fragment.type_condition.named_type.range()) {
+            self.visited_fragments.insert(&fragment.fragment_name.name);
+            self.validate_selection_set(type_name, &fragment.selection_set);
+        }
+    }
+
+    /// Checks that a type exists and can have fields selected on it (i.e. is an object, interface
+    /// or union).
+    fn check_composite(&mut self, type_name: &Name, span: Range<usize>) -> bool {
+        match self.schema.get_type_definition(type_name) {
+            Some(TypeDefinition::ObjectTypeDefinition(_))
+            | Some(TypeDefinition::InterfaceTypeDefinition(_))
+            | Some(TypeDefinition::UnionTypeDefinition(_)) => true,
+            Some(_) => {
+                self.error_at(
+                    span,
+                    format!(
+                        "`{}` is not an object, interface or union, so fragments can't be used \
+                        on it.",
+                        type_name
+                    ),
+                );
+                false
+            }
+            None => {
+                let suggestion = suggestion(
+                    &type_name.0,
+                    self.schema
+                        .type_definitions()
+                        .map(|definition| definition.name().0.as_str()),
+                );
+                self.error_at(
+                    span,
+                    format!(
+                        "The type `{}` does not exist in the schema.{}",
+                        type_name, suggestion
+                    ),
+                );
+                false
+            }
+        }
+    }
+
+    /// Returns the fields of an object or interface.
+    fn fields_of(&self, type_name: &Name) -> Option<&'a [FieldDefinition]> {
+        match self.schema.get_type_definition(type_name)? {
+            TypeDefinition::ObjectTypeDefinition(object) => object
+                .fields_definition
+                .as_ref()
+                .map(|fields| fields.0.as_slice()),
+            TypeDefinition::InterfaceTypeDefinition(interface) => interface
+                .fields_definition
+                .as_ref()
+                .map(|fields| fields.0.as_slice()),
+            _ => None,
+        }
+    }
+
+    fn validate_selection_set(&mut self, type_name: &Name, selection_set: &'a SelectionSet) {
+        for selection in &selection_set.0 {
+            match selection {
+                Selection::Field(field) => {
+                    if field.name.0 == "__typename" {
+                        if field.selection_set.is_some() {
+                            self.error_at(
+                                field.name.range(),
+                                "`__typename` can't have a selection set.".to_string(),
+                            );
+                        }
+                        continue;
+                    }
+                    let definition = match self
+                        .fields_of(type_name)
+                        .and_then(|fields| fields.iter().find(|def| def.name == *field.name))
+                    {
+                        Some(definition) => definition,
+                        None => {
+                            let suggestion = suggestion(
+                                &field.name.0,
+                                self.fields_of(type_name)
+                                    .unwrap_or_default()
+                                    .iter()
+                                    .map(|def| def.name.0.as_str()),
+                            );
+                            self.error_at(
+                                field.name.range(),
+                                format!(
+                                    "The type `{}` has no field called `{}`.{}",
+                                    type_name, field.name, suggestion
+                                ),
+                            );
+                            continue;
+                        }
+                    };
+                    self.validate_arguments(
+                        definition,
+                        field.name.range(),
+                        field.arguments.as_ref(),
+                    );
+                    let field_type = &definition.graphql_type.extract_name().0;
+                    let composite = self.schema.possible_types(field_type).is_some();
+                    match (&field.selection_set, composite) {
+                        (Some(selection_set), true) => {
+                            self.validate_selection_set(field_type, selection_set)
+                        }
+                        (None, true) => self.error_at(
+                            field.name.range(),
+                            format!(
+                                "The field `{}` (of type `{}`) needs a selection set.",
+                                field.name, definition.graphql_type
+                            ),
+                        ),
+                        (Some(_), false) => self.error_at(
+                            field.name.range(),
+                            format!(
+                                "The field `{}` (of type `{}`) can't have a selection set.",
+                                field.name, definition.graphql_type
+                            ),
+                        ),
+                        (None, false) => {}
+                    }
+                }
+                Selection::InlineFragment(fragment) => {
+                    let condition = match &fragment.type_condition {
+                        Some(condition) => &condition.named_type.0,
+                        None => type_name,
+                    };
+                    let span = fragment
+                        .type_condition
+                        .as_ref()
+                        .map(|condition| condition.named_type.range())
+                        .unwrap_or_default();
+                    if self.check_composite(condition, span) {
+                        self.validate_selection_set(condition, &fragment.selection_set);
+                    }
+                }
+                Selection::FragmentSpread(spread) => {
+                    let name = &spread.fragment_name.name;
+                    match self
+                        .fragments
+                        .iter()
+                        .find(|fragment| &fragment.fragment_name.name == name)
+                    {
+                        Some(fragment) => {
+                            if self.visited_fragments.insert(name) {
+                                self.validate_fragment(fragment);
+                            }
+                        }
+                        None if self
+                            .imported
+                            .iter()
+                            .any(|fragment| &fragment.fragment_name.name == name) => {}
+                        None => {
+                            let suggestion = suggestion(
+                                &name.0,
+                                self.fragments
+                                    .iter()
+                                    .chain(self.imported.iter())
+                                    .map(|fragment| fragment.fragment_name.name.0.as_str()),
+                            );
+                            self.error_at(
+                                spread.fragment_name.range(),
+                                format!("The fragment `{}` is not defined.{}", name, suggestion),
+                            )
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn validate_arguments(
+        &mut self,
+        definition: &'a FieldDefinition,
+        field_span: Range<usize>,
+        arguments: Option<&'a Arguments>,
+    ) {
+        let defined = definition
+            .arguments_definition
+            .as_ref()
+            .map(|arguments| arguments.0.as_slice())
+            .unwrap_or_default();
+        let provided = arguments
+            .map(|arguments| arguments.0.as_slice())
+            .unwrap_or_default();
+        for argument in provided {
+            if !defined.iter().any(|def| def.name == *argument.name) {
+                let suggestion = suggestion(
+                    &argument.name.0,
+                    defined.iter().map(|def| def.name.0.as_str()),
+                );
+                self.error_at(
+                    argument.name.range(),
+                    format!(
+                        "The field `{}` has no argument called `{}`.{}",
+                        definition.name, argument.name, suggestion
+                    ),
+                );
+            }
+            self.validate_value(argument.name.range(), &argument.value);
+        }
+        for def in defined {
+            let required = matches!(def.graphql_type, crate::ast::GraphQLType::NonNullType(_))
+                && def.default_value.is_none();
+            if required && !provided.iter().any(|argument| *argument.name == def.name) {
+                self.error_at(
+                    field_span.clone(),
+                    format!(
+                        "The argument `{}` of the field `{}` is required.",
+                        def.name, definition.name
+                    ),
+                );
+            }
+        }
+    }
+
+    /// Checks that any variables used in a value have been defined. Values don't have spans of
+    /// their own, so errors point at the name of the argument the value was passed to.
+    fn validate_value(&mut self, span: Range<usize>, value: &'a Value) {
+        match value {
+            Value::Variable(variable) => {
+                if let Some(variables) = &self.variables {
+                    if !variables.contains(&variable.0) {
+                        let message = format!("The variable `{}` is not defined.", variable);
+                        self.error_at(span, message);
+                    }
+                }
+            }
+            Value::List(list) => {
+                for value in &list.0 {
+                    self.validate_value(span.clone(), value)
+                }
+            }
+            Value::Object(object) => {
+                for field in &object.0 {
+                    self.validate_value(span.clone(), &field.value)
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_validation {
+    use super::*;
+    use crate::parse_string;
+
+    const SCHEMA: &str = r#"
+        type Query { user(id: ID!): User search(term: String!, first: Int = 10): [SearchResult!]! }
+        type User { id: ID! username: String! friends: [User!]! }
+        type Post { id: ID! title: String! }
+        union SearchResult = User | Post
+    "#;
+
+    fn errors(query: &str) -> Vec<String> {
+        let schema = parse_string(SCHEMA).expect("Parse error");
+        let query = parse_string(query).expect("Parse error");
+        validate(&schema, &query)
+            .into_iter()
+            .map(|error| error.message)
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_query() {
+        assert_eq!(
+            errors(
+                r#"query UserPage($id: ID!) {
+                    user(id: $id) { __typename ...UserFields friends { id } }
+                    search(term: "x") { ... on Post { title } ... on User { username } }
+                }
+                fragment UserFields on User { id username }"#
+            ),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_unknown_fields_and_arguments() {
+        assert_eq!(
+            errors(r#"{ user(id: "1", name: "x") { email } }"#),
+            vec![
+                "The field `user` has no argument called `name`.".to_string(),
+                "The type `User` has no field called `email`.".to_string()
+            ]
+        );
+        assert_eq!(
+            errors(r#"{ user { id } }"#),
+            vec!["The argument `id` of the field `user` is required.".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_suggestions() {
+        assert_eq!(
+            errors(r#"{ user(id: "1") { usrname } }"#),
+            vec![
+                "The type `User` has no field called `usrname`. Did you mean `username`?"
+                    .to_string()
+            ]
+        );
+        assert_eq!(
+            errors(r#"{ search(term: "x", frist: 1) { ... on Psot { id } } }"#),
+            vec![
+                "The field `search` has no argument called `frist`. Did you mean `first`?"
+                    .to_string(),
+                "The type `Psot` does not exist in the schema. Did you mean `Post`?".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_error_spans() {
+        let schema = parse_string(SCHEMA).expect("Parse error");
+        let query = r#"{ user(id: "1", nme: $x) { usrname ...Missing } }"#;
+        let spans = validate(&schema, &parse_string(query).expect("Parse error"))
+            .into_iter()
+            .map(|error| &query[error.span.expect("missing span")])
+            .collect::<Vec<_>>();
+        assert_eq!(spans, vec!["nme", "nme", "usrname", "Missing"]);
+    }
+
+    #[test]
+    fn test_imported_fragments() {
+        let schema = parse_string(SCHEMA).expect("Parse error");
+        let fragments = parse_string("fragment UserFields on User { id username }").unwrap();
+        let imported = fragments.fragments().collect::<Vec<_>>();
+        let errors = |query: &str| {
+            validate_with_fragments(&schema, &parse_string(query).unwrap(), &imported)
+                .into_iter()
+                .map(|error| error.message)
+                .collect::<Vec<_>>()
+        };
+        assert!(errors(r#"{ user(id: "1") { ...UserFields } }"#).is_empty());
+        assert_eq!(
+            errors(r#"{ user(id: "1") { ...UserFeilds } }"#),
+            vec![
+                "The fragment `UserFeilds` is not defined. Did you mean `UserFields`?".to_string()
+            ]
+        );
+        assert_eq!(
+            errors(r#"{ user(id: "1") { ...UserFields } } fragment UserFields on User { id }"#),
+            vec![
+                "The fragment `UserFields` is defined here, but is also imported from somewhere \
+                else."
+                    .to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_selection_sets() {
+        assert_eq!(errors(r#"{ user(id: "1") }"#).len(), 1);
+        assert_eq!(errors(r#"{ user(id: "1") { id { value } } }"#).len(), 1);
+        // fields can't be selected directly on unions
+        assert_eq!(errors(r#"{ search(term: "x") { id } }"#).len(), 1);
+    }
+
+    #[test]
+    fn test_fragments_and_variables() {
+        assert_eq!(
+            errors(r#"{ user(id: $id) { ...Missing ... on Comment { id } } }"#),
+            vec![
+                "The variable `$id` is not defined.".to_string(),
+                "The fragment `Missing` is not defined.".to_string(),
+                "The type `Comment` does not exist in the schema.".to_string(),
+            ]
+        );
+        assert_eq!(
+            errors("query A { __typename } query A { __typename }"),
+            vec!["There is more than one operation called `A`.".to_string()]
+        );
+    }
+}
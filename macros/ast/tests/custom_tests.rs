@@ -36,3 +36,11 @@ fn test_directives_cache_control() {
       }"#,
     );
 }
+
+#[test]
+fn test_type_definition_position() {
+    let schema = "type Query {\n    user: User\n}\n\n  type User {\n    id: ID!\n}\n";
+    assert_eq!(ast::type_definition_position(schema, "User"), Some((5, 3)));
+    assert_eq!(ast::type_definition_position(schema, "Query"), Some((1, 1)));
+    assert_eq!(ast::type_definition_position(schema, "Post"), None);
+}
@@ -1,2 +1,80 @@
 //! If specific bugs are found in the ast tests should be added here to stop them from cropping
 //! again.
+
+use ast::ast::{Definition, Name, TypeDefinition};
+use ast::parse_string;
+
+#[test]
+fn test_trailing_input_is_rejected() {
+    // the parser used to stop at the first thing it didn't understand and report success
+    assert!(parse_string("type User { id: ID! } this is not graphql").is_err());
+}
+
+#[test]
+fn test_enum_values_are_parsed() {
+    let parsed = parse_string(
+        r#"enum Direction {
+            "Up"
+            NORTH @deprecated
+            EAST
+        }"#,
+    )
+    .expect("Parse error");
+    let values = match parsed.get_type_definition(&Name("Direction".to_string())) {
+        Some(TypeDefinition::EnumTypeDefinition(def)) => def
+            .enum_values_definition
+            .as_ref()
+            .expect("the values of the enum were dropped")
+            .0
+            .iter()
+            .map(|value| value.enum_value.0 .0.clone())
+            .collect::<Vec<_>>(),
+        _ => panic!("failed to find the enum"),
+    };
+    assert_eq!(values, vec!["NORTH", "EAST"]);
+}
+
+#[test]
+fn test_nested_list_types() {
+    assert!(parse_string("type Matrix { rows: [[Int!]!]! }").is_ok());
+}
+
+#[test]
+fn test_first_selection_is_kept() {
+    let parsed = parse_string("query { user { id name } }").expect("Parse error");
+    let printed = parsed.to_string();
+    assert!(printed.contains("id"));
+    assert!(printed.contains("name"));
+}
+
+#[test]
+fn test_inline_fragments_are_not_fragment_spreads() {
+    let parsed = parse_string("query { search { ... on User { name } ...PostFields } }")
+        .expect("Parse error");
+    let printed = parsed.to_string();
+    assert!(printed.contains("... on User"));
+    assert!(printed.contains("...PostFields"));
+}
+
+#[test]
+fn test_variables_and_default_values() {
+    let parsed = parse_string(
+        "query User($id: ID!, $first: Int = 10) { user(id: $id) { friends(first: $first) { id } } }",
+    )
+    .expect("Parse error");
+    match &parsed.0[0] {
+        Definition::ExecutableDefinition(def) => {
+            let printed = def.to_string();
+            assert!(printed.contains("$id: ID!"));
+            assert!(printed.contains("$first: Int = 10"));
+            assert!(printed.contains("user(id: $id"));
+        }
+        _ => panic!("expected an operation"),
+    }
+}
+
+#[test]
+fn test_query_shorthand() {
+    let parsed = parse_string("{ user { id } }").expect("Parse error");
+    assert!(parsed.to_string().contains("query {user {id } }"));
+}
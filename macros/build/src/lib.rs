@@ -0,0 +1,295 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+//! Compiles GraphQL schemas (and queries) from a `build.rs`, rather than in a procedural macro.
+//!
+//! Procedural macros can't tell Cargo which files they depend on, and they have to redo all their
+//! work every time they are expanded. Doing the work in a build script instead means that it only
+//! happens when one of the input files actually changes (and keeps editors nice and fast).
+//!
+//! ```no_run
+//! // in the `main` function of your build.rs
+//! myoxine_build::Builder::new()
+//!     .schema("schema.graphql")
+//!     .queries("queries")
+//!     .compile()
+//!     .unwrap();
+//! ```
+//!
+//! and then, somewhere in your crate
+//!
+//! ```ignore
+//! include!(concat!(env!("OUT_DIR"), "/schema.rs"));
+//! ```
+//!
+//! which gives you the same module that `graphql_schema!` would, along with a `queries` module
+//! containing a constant for every operation in the query directories (e.g. `query UserPage` becomes
+//! `schema::queries::USER_PAGE`) with any fragments it uses included.
+//!
+//! Schemas can be split across several files (`.schema("schema/*.graphql")`), and a query can use
+//! fragments from any of the query files – each type and fragment just has to be defined exactly
+//! once.
+//!
+//! `compile` also gives the procedural macros in your crate a directory (in `OUT_DIR`) to cache
+//! parsed schemas in, so that the derive macros don't parse the whole schema again every time
+//! `cargo check` runs.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use ast::ast::{Document, FragmentDefinition};
+use ast::sources::{self, SourceError};
+use codegen::case::to_snake_case;
+use codegen::query::operation_text;
+use quote::quote;
+use thiserror::Error as ThisError;
+
+#[derive(ThisError, Debug)]
+pub enum BuildError {
+    #[error("no schema was provided (use `Builder::schema`)")]
+    NoSchema,
+    #[error("`OUT_DIR` isn't set – `compile` should be called from a build script, or `Builder::out_dir` used")]
+    MissingOutDir,
+    #[error("couldn't read `{path}`: {error}")]
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    #[error("couldn't parse `{path}`:\n{message}")]
+    Parse { path: PathBuf, message: String },
+    #[error("`{path}` isn't valid:\n{}", .messages.join("\n"))]
+    Invalid {
+        path: PathBuf,
+        messages: Vec<String>,
+    },
+    #[error("`{0}` didn't match any files")]
+    NoMatches(PathBuf),
+    /// A type or fragment was defined in more than one file.
+    #[error("{0}")]
+    Duplicate(String),
+}
+
+impl From<SourceError> for BuildError {
+    fn from(error: SourceError) -> Self {
+        match error {
+            SourceError::Io { path, error } => BuildError::Io { path, error },
+            SourceError::NoMatches(pattern) => BuildError::NoMatches(pattern),
+            SourceError::Parse { path, message } => BuildError::Parse { path, message },
+            SourceError::Duplicate(message) => BuildError::Duplicate(message),
+        }
+    }
+}
+
+/// Configures what should be compiled. See the crate-level documentation for an example.
+#[derive(Debug, Clone)]
+pub struct Builder {
+    schemas: Vec<PathBuf>,
+    query_directories: Vec<PathBuf>,
+    module: String,
+    out_dir: Option<PathBuf>,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self {
+            schemas: vec![],
+            query_directories: vec![],
+            module: "schema".to_string(),
+            out_dir: None,
+        }
+    }
+    /// Adds a schema file, or a pattern matching several (e.g. `schema/*.graphql`). If more than
+    /// one file is provided, their definitions are combined.
+    pub fn schema<P>(mut self, path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.schemas.push(path.into());
+        self
+    }
+    /// Adds a directory containing queries (every `.graphql` file in the directory is used).
+    pub fn queries<P>(mut self, directory: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.query_directories.push(directory.into());
+        self
+    }
+    /// Sets the name of the generated module (and of the file it is written to). Defaults to
+    /// `schema`.
+    pub fn module(mut self, name: &str) -> Self {
+        self.module = name.to_string();
+        self
+    }
+    /// Sets the directory the generated code is written to. Defaults to `$OUT_DIR`.
+    pub fn out_dir<P>(mut self, directory: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.out_dir = Some(directory.into());
+        self
+    }
+    /// Validates everything and writes the generated code to `<out_dir>/<module>.rs`, returning the
+    /// path of the file written.
+    pub fn compile(self) -> Result<PathBuf, BuildError> {
+        if self.schemas.is_empty() {
+            return Err(BuildError::NoSchema);
+        }
+        let out_dir = match self.out_dir.clone() {
+            Some(out_dir) => out_dir,
+            None => std::env::var_os("OUT_DIR")
+                .map(PathBuf::from)
+                .ok_or(BuildError::MissingOutDir)?,
+        };
+
+        // so that the procedural macros in the crate have somewhere to cache parsed schemas
+        println!(
+            "cargo:rustc-env={}={}",
+            ast::cache::CACHE_DIR_VARIABLE,
+            out_dir.join("myoxine-schemas").display()
+        );
+
+        for pattern in &self.schemas {
+            // (for patterns this is the directory they look in, so that new files are noticed)
+            rerun_if_changed(&without_wildcards(pattern));
+        }
+        let schema_files = sources::read(&self.schemas)?;
+        for file in &schema_files {
+            rerun_if_changed(&file.path);
+        }
+        let schema = sources::parse(&schema_files, |source| ast::parse_string(source))?;
+
+        let mut query_paths = vec![];
+        for directory in &self.query_directories {
+            // so that adding new files to the directory triggers a rebuild
+            rerun_if_changed(directory);
+            query_paths.extend(graphql_files(directory)?);
+        }
+        let query_files = sources::read(&query_paths)?;
+        // fragments can be used from any file, so they are all parsed before anything is checked
+        let documents = sources::parse_each(&query_files, |source| ast::parse_string(source))?;
+        let mut queries = vec![];
+        let mut names = HashSet::new();
+        for (index, (file, document)) in query_files.iter().zip(&documents).enumerate() {
+            rerun_if_changed(&file.path);
+            let imported = documents
+                .iter()
+                .enumerate()
+                .filter(|(other, _)| *other != index)
+                .flat_map(|(_, document)| document.fragments())
+                .collect::<Vec<_>>();
+            let errors = ast::validate::validate_with_fragments(&schema, document, &imported);
+            if !errors.is_empty() {
+                return Err(BuildError::Invalid {
+                    path: file.path.clone(),
+                    messages: errors.into_iter().map(|error| error.message).collect(),
+                });
+            }
+            let fragments = document.fragments().chain(imported).collect::<Vec<_>>();
+            queries.extend(query_constants(
+                &file.path, document, &fragments, &mut names,
+            )?);
+        }
+
+        let module = codegen::case::ident(&self.module);
+        let schema_path = self
+            .schemas
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let output = codegen::schema::schema_module(
+            &schema,
+            &module,
+            &schema_path,
+            quote! {
+                /// The operations from your query files, along with any fragments they use.
+                pub mod queries {
+                    #(#queries)*
+                }
+            },
+        );
+        let out_file = out_dir.join(format!("{}.rs", self.module));
+        std::fs::write(&out_file, output.to_string()).map_err(|error| BuildError::Io {
+            path: out_file.clone(),
+            error,
+        })?;
+        Ok(out_file)
+    }
+}
+
+fn rerun_if_changed(path: &Path) {
+    println!("cargo:rerun-if-changed={}", path.display());
+}
+
+/// The part of a path before the first component containing a wildcard.
+fn without_wildcards(pattern: &Path) -> PathBuf {
+    pattern
+        .components()
+        .take_while(|component| {
+            let component = component.as_os_str().to_string_lossy();
+            !component.contains('*') && !component.contains('?')
+        })
+        .collect()
+}
+
+/// Lists the `.graphql` files in a directory (sorted, so that the output is deterministic).
+fn graphql_files(directory: &Path) -> Result<Vec<PathBuf>, BuildError> {
+    let io_error = |error| BuildError::Io {
+        path: directory.to_path_buf(),
+        error,
+    };
+    let mut files = vec![];
+    for entry in std::fs::read_dir(directory).map_err(io_error)? {
+        let path = entry.map_err(io_error)?.path();
+        if path.extension().map(|e| e == "graphql").unwrap_or(false) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Emits a constant for every operation in a file. `fragments` are the fragments which the
+/// operations can use (from this file and all the others) and `names` contains the constants which
+/// have already been emitted (for other files).
+fn query_constants(
+    path: &Path,
+    document: &Document,
+    fragments: &[&FragmentDefinition],
+    names: &mut HashSet<syn::Ident>,
+) -> Result<Vec<proc_macro2::TokenStream>, BuildError> {
+    let mut output = vec![];
+    for operation in document.operations() {
+        let name = operation.name.as_ref().ok_or_else(|| BuildError::Invalid {
+            path: path.to_path_buf(),
+            messages: vec!["Operations in query files must have names.".to_string()],
+        })?;
+        let text = operation_text(operation, fragments);
+        let ident = quote::format_ident!("{}", to_snake_case(&name.0).to_uppercase());
+        if !names.insert(ident.clone()) {
+            return Err(BuildError::Invalid {
+                path: path.to_path_buf(),
+                messages: vec![format!(
+                    "There is more than one operation called `{}`.",
+                    name
+                )],
+            });
+        }
+        let doc = format!(" The `{}` operation from `{}`.", name, path.display());
+        output.push(quote! {
+            #[doc = #doc]
+            pub const #ident: &str = #text;
+        });
+    }
+    Ok(output)
+}
@@ -0,0 +1,125 @@
+use std::path::PathBuf;
+
+use myoxine_build::{BuildError, Builder};
+
+fn fixture(path: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(path)
+}
+
+fn out_dir(name: &str) -> PathBuf {
+    let out_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    std::fs::create_dir_all(&out_dir).unwrap();
+    out_dir
+}
+
+#[test]
+fn test_compiles_schema_and_queries() {
+    let out_file = Builder::new()
+        .schema(fixture("schema.graphql"))
+        .queries(fixture("queries"))
+        .module("api")
+        .out_dir(out_dir("compiles"))
+        .compile()
+        .expect("failed to compile");
+    assert!(out_file.ends_with("api.rs"));
+    let output = std::fs::read_to_string(out_file).unwrap();
+    assert!(output.contains("pub mod api"));
+    assert!(output.contains("pub enum Role"));
+    assert!(output.contains("pub const USER_PAGE : & str"));
+    assert!(output.contains("pub const SEARCH : & str"));
+    // fragments are only included if the query uses them
+    assert!(output.contains("fragment UserFields on User"));
+    assert!(!output.contains("fragment Unused"));
+}
+
+#[test]
+fn test_invalid_queries_are_rejected() {
+    let error = Builder::new()
+        .schema(fixture("schema.graphql"))
+        .queries(fixture("invalid"))
+        .out_dir(out_dir("invalid"))
+        .compile()
+        .unwrap_err();
+    match error {
+        BuildError::Invalid { path, messages } => {
+            assert!(path.ends_with("user.graphql"));
+            assert_eq!(
+                messages,
+                vec!["The type `User` has no field called `email`.".to_string()]
+            );
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+}
+
+#[test]
+fn test_split_schemas_and_shared_fragments() {
+    let out_file = Builder::new()
+        .schema(fixture("split/*.graphql"))
+        .queries(fixture("queries"))
+        .queries(fixture("shared"))
+        .out_dir(out_dir("split"))
+        .compile()
+        .expect("failed to compile");
+    let output = std::fs::read_to_string(out_file).unwrap();
+    assert!(output.contains("pub enum Role"));
+    // `SearchUsers` uses a fragment from another file, which is included in its text
+    let search_users = output
+        .split("pub const SEARCH_USERS : & str = ")
+        .nth(1)
+        .expect("missing `SEARCH_USERS`");
+    assert!(search_users
+        .split(';')
+        .next()
+        .unwrap()
+        .contains("fragment UserFields on User"));
+}
+
+#[test]
+fn test_duplicate_definitions_are_rejected() {
+    let error = Builder::new()
+        .schema(fixture("split/*.graphql"))
+        .schema(fixture("schema.graphql"))
+        .out_dir(out_dir("duplicate-types"))
+        .compile()
+        .unwrap_err();
+    assert!(matches!(error, BuildError::Duplicate(_)));
+    let error = Builder::new()
+        .schema(fixture("schema.graphql"))
+        .queries(fixture("duplicate"))
+        .out_dir(out_dir("duplicate-fragments"))
+        .compile()
+        .unwrap_err();
+    match error {
+        BuildError::Duplicate(message) => {
+            assert!(message.starts_with("`UserFields` is defined more than once"));
+            assert!(message.contains("a.graphql:1:1"));
+            assert!(message.contains("b.graphql:7:1"));
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+}
+
+#[test]
+fn test_missing_files_are_reported() {
+    assert!(matches!(
+        Builder::new()
+            .schema(fixture("does-not-exist.graphql"))
+            .out_dir(out_dir("missing"))
+            .compile(),
+        Err(BuildError::Io { .. })
+    ));
+    assert!(matches!(
+        Builder::new()
+            .schema(fixture("split/*.json"))
+            .out_dir(out_dir("missing"))
+            .compile(),
+        Err(BuildError::NoMatches(_))
+    ));
+    assert!(matches!(
+        Builder::new().out_dir(out_dir("missing")).compile(),
+        Err(BuildError::NoSchema)
+    ));
+}
@@ -0,0 +1,182 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+
+//! Conversions between the naming conventions of GraphQL (`camelCase` fields and
+//! `SCREAMING_SNAKE_CASE` enum values) and Rust (`snake_case` fields and `PascalCase` variants).
+
+/// Splits a name into its constituent words. Both underscores and changes of case count as word
+/// boundaries (so `userID`, `user_id` and `USER_ID` all become `["user", "ID"]`-ish words).
+fn words(input: &str) -> Vec<String> {
+    let characters = input.chars().collect::<Vec<_>>();
+    let mut words = vec![];
+    let mut current = String::new();
+    for (i, &character) in characters.iter().enumerate() {
+        if character == '_' {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        if character.is_uppercase() && !current.is_empty() {
+            let previous = characters[i - 1];
+            let next_is_lowercase = characters
+                .get(i + 1)
+                .map(|next| next.is_lowercase())
+                .unwrap_or(false);
+            // `fooBar` -> `foo`, `Bar` and `HTTPServer` -> `HTTP`, `Server`
+            if previous.is_lowercase()
+                || previous.is_numeric()
+                || (previous.is_uppercase() && next_is_lowercase)
+            {
+                words.push(std::mem::take(&mut current));
+            }
+        }
+        current.push(character);
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+/// Converts the name of a Rust variant into the name we expect it to have in the schema (so
+/// `NewHope` becomes `NEW_HOPE`).
+pub fn to_screaming_snake_case(input: &str) -> String {
+    let mut output = String::new();
+    for (i, character) in input.chars().enumerate() {
+        if character.is_uppercase() && i != 0 {
+            output.push('_');
+        }
+        output.extend(character.to_uppercase());
+    }
+    output
+}
+
+/// Converts a name to `snake_case`, e.g. `firstName` becomes `first_name`.
+pub fn to_snake_case(input: &str) -> String {
+    words(input)
+        .iter()
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// Converts a name to `PascalCase`, e.g. `NEW_HOPE` becomes `NewHope`.
+pub fn to_pascal_case(input: &str) -> String {
+    words(input)
+        .iter()
+        .map(|word| {
+            let mut characters = word.chars();
+            match characters.next() {
+                Some(first) => first
+                    .to_uppercase()
+                    .chain(characters.flat_map(|c| c.to_lowercase()))
+                    .collect::<String>(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// Converts a name to `camelCase`, e.g. `created_at` becomes `createdAt`.
+pub fn to_camel_case(input: &str) -> String {
+    let pascal = to_pascal_case(input);
+    let mut characters = pascal.chars();
+    match characters.next() {
+        Some(first) => first.to_lowercase().chain(characters).collect(),
+        None => String::new(),
+    }
+}
+
+/// Applies a `rename_all` rule (named in the same way as serde's ones) to a name, returning `None`
+/// if the rule isn't one we know about.
+pub fn apply_rename_rule(rule: &str, name: &str) -> Option<String> {
+    Some(match rule {
+        "camelCase" => to_camel_case(name),
+        "snake_case" => to_snake_case(name),
+        "PascalCase" => to_pascal_case(name),
+        "SCREAMING_SNAKE_CASE" => to_snake_case(name).to_uppercase(),
+        "lowercase" => name.to_lowercase(),
+        "UPPERCASE" => name.to_uppercase(),
+        _ => return None,
+    })
+}
+
+/// The rules accepted by `apply_rename_rule` (for error messages).
+pub const RENAME_RULES: &[&str] = &[
+    "camelCase",
+    "snake_case",
+    "PascalCase",
+    "SCREAMING_SNAKE_CASE",
+    "lowercase",
+    "UPPERCASE",
+];
+
+/// Creates an identifier, escaping it if it happens to be a Rust keyword (so a field called `type`
+/// becomes `r#type`).
+pub fn ident(name: &str) -> syn::Ident {
+    match name {
+        // these can't be used as raw identifiers
+        "self" | "Self" | "super" | "crate" | "_" => quote::format_ident!("{}_", name),
+        _ => syn::parse_str::<syn::Ident>(name)
+            .unwrap_or_else(|_| syn::Ident::new_raw(name, proc_macro2::Span::call_site())),
+    }
+}
+
+#[cfg(test)]
+mod test_case_conversion {
+    use super::*;
+
+    #[test]
+    fn test_screaming_snake_case() {
+        assert_eq!(to_screaming_snake_case("NewHope"), "NEW_HOPE");
+        assert_eq!(to_screaming_snake_case("Admin"), "ADMIN");
+    }
+
+    #[test]
+    fn test_snake_case() {
+        assert_eq!(to_snake_case("firstName"), "first_name");
+        assert_eq!(to_snake_case("id"), "id");
+        assert_eq!(to_snake_case("avatarURL"), "avatar_url");
+        assert_eq!(to_snake_case("HTTPServer"), "http_server");
+        assert_eq!(to_snake_case("line2"), "line2");
+    }
+
+    #[test]
+    fn test_pascal_case() {
+        assert_eq!(to_pascal_case("NEW_HOPE"), "NewHope");
+        assert_eq!(to_pascal_case("ADMIN"), "Admin");
+        assert_eq!(to_pascal_case("inProgress"), "InProgress");
+    }
+
+    #[test]
+    fn test_camel_case() {
+        assert_eq!(to_camel_case("created_at"), "createdAt");
+        assert_eq!(to_camel_case("avatar_url"), "avatarUrl");
+        assert_eq!(to_camel_case("id"), "id");
+    }
+
+    #[test]
+    fn test_rename_rules() {
+        assert_eq!(
+            apply_rename_rule("camelCase", "created_at").as_deref(),
+            Some("createdAt")
+        );
+        assert_eq!(
+            apply_rename_rule("SCREAMING_SNAKE_CASE", "created_at").as_deref(),
+            Some("CREATED_AT")
+        );
+        assert_eq!(apply_rename_rule("kebab-case", "created_at"), None);
+    }
+
+    #[test]
+    fn test_keywords_are_escaped() {
+        assert_eq!(ident("type").to_string(), "r#type");
+        assert_eq!(ident("self").to_string(), "self_");
+        assert_eq!(ident("name").to_string(), "name");
+    }
+}
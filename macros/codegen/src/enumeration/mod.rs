@@ -0,0 +1,48 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+
+//! Code generation for GraphQL enums.
+
+use quote::quote;
+
+/// Implements `Serialize` and `Deserialize` on an enum, given the GraphQL value each of its variants
+/// corresponds to and the name of the variant which stores unknown values.
+pub fn output_impls(
+    ident: &syn::Ident,
+    variants: &[(syn::Ident, String)],
+    fallback: &syn::Ident,
+) -> proc_macro2::TokenStream {
+    let variant_idents = variants.iter().map(|(ident, _)| ident).collect::<Vec<_>>();
+    let graphql_names = variants.iter().map(|(_, name)| name).collect::<Vec<_>>();
+    quote! {
+        impl ::myoxine::serde::Serialize for #ident {
+            fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+            where
+                S: ::myoxine::serde::Serializer,
+            {
+                serializer.serialize_str(match self {
+                    #(Self::#variant_idents => #graphql_names,)*
+                    Self::#fallback(value) => value.as_str(),
+                })
+            }
+        }
+        impl<'de> ::myoxine::serde::Deserialize<'de> for #ident {
+            fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+            where
+                D: ::myoxine::serde::Deserializer<'de>,
+            {
+                let value = <::std::string::String as ::myoxine::serde::Deserialize>::deserialize(
+                    deserializer,
+                )?;
+                Ok(match value.as_str() {
+                    #(#graphql_names => Self::#variant_idents,)*
+                    _ => Self::#fallback(value),
+                })
+            }
+        }
+    }
+}
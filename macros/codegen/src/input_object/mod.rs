@@ -0,0 +1,52 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+
+//! Code generation for GraphQL input objects.
+
+use quote::quote;
+
+/// Describes how a field should be serialized.
+pub enum FieldKind {
+    /// Always serialized.
+    Required,
+    /// Omitted when `None`.
+    Optional,
+}
+
+/// Implements `Serialize` and `InputObject` on a struct, given the name each field has in the
+/// schema and how it should be serialized.
+pub fn output_impls(
+    ident: &syn::Ident,
+    graphql_name: &str,
+    fields: &[(syn::Ident, String, FieldKind)],
+) -> proc_macro2::TokenStream {
+    let statements = fields.iter().map(|(ident, name, kind)| match kind {
+        FieldKind::Required => quote! {
+            ::myoxine::serde::ser::SerializeMap::serialize_entry(&mut map, #name, &self.#ident)?;
+        },
+        FieldKind::Optional => quote! {
+            if let ::std::option::Option::Some(value) = &self.#ident {
+                ::myoxine::serde::ser::SerializeMap::serialize_entry(&mut map, #name, value)?;
+            }
+        },
+    });
+    quote! {
+        impl ::myoxine::serde::Serialize for #ident {
+            fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+            where
+                S: ::myoxine::serde::Serializer,
+            {
+                let mut map = serializer.serialize_map(::std::option::Option::None)?;
+                #(#statements)*
+                ::myoxine::serde::ser::SerializeMap::end(map)
+            }
+        }
+        impl ::myoxine::input::InputObject for #ident {
+            const GRAPHQL_TYPE: &'static str = #graphql_name;
+        }
+    }
+}
@@ -0,0 +1,18 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+//! Code generation which is shared between our procedural macros and the build script API.
+//!
+//! Procedural macro crates can't export anything other than macros, so anything which needs to be
+//! used from a `build.rs` as well as from a macro lives here instead.
+
+pub mod case;
+pub mod enumeration;
+pub mod input_object;
+pub mod object;
+pub mod query;
+pub mod schema;
+pub mod types;
@@ -0,0 +1,160 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+
+//! Code generation for GraphQL objects.
+
+use ast::ast::{Directives, ObjectTypeDefinition, Value};
+use quote::quote;
+
+/// Implements `Serialize` and `Deserialize` on a struct, given the name each field has in the
+/// schema (this is what `#[graphql(serde)]` asks for). Doing this by hand, rather than with serde's
+/// derives, means the names used in JSON are always the same as the ones in the selection set.
+///
+/// `__typename` is included when serializing (so that the output looks like a response from the
+/// server) and any fields the struct doesn't have (including `__typename`) are ignored when
+/// deserializing.
+pub fn output_impls(
+    ident: &syn::Ident,
+    graphql_name: &str,
+    fields: &[(syn::Ident, String)],
+) -> proc_macro2::TokenStream {
+    let field_idents = fields.iter().map(|(ident, _)| ident).collect::<Vec<_>>();
+    let graphql_names = fields.iter().map(|(_, name)| name).collect::<Vec<_>>();
+    let len = fields.len() + 1;
+    quote! {
+        impl ::myoxine::serde::Serialize for #ident {
+            fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+            where
+                S: ::myoxine::serde::Serializer,
+            {
+                let mut state = serializer.serialize_struct(#graphql_name, #len)?;
+                ::myoxine::serde::ser::SerializeStruct::serialize_field(
+                    &mut state,
+                    "__typename",
+                    #graphql_name,
+                )?;
+                #(
+                    ::myoxine::serde::ser::SerializeStruct::serialize_field(
+                        &mut state,
+                        #graphql_names,
+                        &self.#field_idents,
+                    )?;
+                )*
+                ::myoxine::serde::ser::SerializeStruct::end(state)
+            }
+        }
+        impl<'de> ::myoxine::serde::Deserialize<'de> for #ident {
+            fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+            where
+                D: ::myoxine::serde::Deserializer<'de>,
+            {
+                let mut map = <::myoxine::serde_json::Map<
+                    ::std::string::String,
+                    ::myoxine::serde_json::Value,
+                > as ::myoxine::serde::Deserialize>::deserialize(deserializer)?;
+                Ok(Self {
+                    #(
+                        #field_idents: match map.remove(#graphql_names) {
+                            ::std::option::Option::Some(value) => {
+                                ::myoxine::serde_json::from_value(value)
+                                    .map_err(<D::Error as ::myoxine::serde::de::Error>::custom)?
+                            }
+                            // missing fields are fine, as long as they are nullable
+                            ::std::option::Option::None => ::myoxine::serde_json::from_value(
+                                ::myoxine::serde_json::Value::Null,
+                            )
+                            .map_err(|_| {
+                                <D::Error as ::myoxine::serde::de::Error>::missing_field(
+                                    #graphql_names,
+                                )
+                            })?,
+                        },
+                    )*
+                })
+            }
+        }
+    }
+}
+
+/// Turns the `@cacheControl` directives on an object type (and on the fields in `fields`, which
+/// are GraphQL names) into a value for `Object::CACHE_CONTROL`. Returns `Ok(None)` if there aren't
+/// any, so that the default can be used.
+pub fn cache_control(
+    type_def: &ObjectTypeDefinition,
+    fields: &[&str],
+) -> Result<Option<proc_macro2::TokenStream>, String> {
+    let hint = cache_hint(&type_def.directives)?;
+    let mut field_hints = vec![];
+    for field in type_def
+        .fields_definition
+        .iter()
+        .flat_map(|fields| &fields.0)
+    {
+        if !fields.contains(&field.name.0.as_str()) {
+            continue;
+        }
+        if let Some(field_hint) = cache_hint(&field.directives)? {
+            let name = &field.name.0;
+            field_hints.push(quote! { (#name, #field_hint) });
+        }
+    }
+    if hint.is_none() && field_hints.is_empty() {
+        return Ok(None);
+    }
+    let hint = hint.unwrap_or_else(|| quote! { ::myoxine::cache::control::CacheHint::NONE });
+    Ok(Some(quote! {
+        ::myoxine::cache::control::CacheControl {
+            hint: #hint,
+            fields: &[#(#field_hints),*],
+        }
+    }))
+}
+
+/// Reads a `@cacheControl(maxAge: ..., scope: ...)` directive, if there is one.
+fn cache_hint(directives: &Option<Directives>) -> Result<Option<proc_macro2::TokenStream>, String> {
+    let directive = match directives
+        .iter()
+        .flat_map(|directives| &directives.0)
+        .find(|directive| directive.name.0 == "cacheControl")
+    {
+        Some(directive) => directive,
+        None => return Ok(None),
+    };
+    let mut max_age = quote! { ::std::option::Option::None };
+    let mut scope = quote! { ::myoxine::cache::control::CacheScope::Public };
+    for argument in directive
+        .arguments
+        .iter()
+        .flat_map(|arguments| &arguments.0)
+    {
+        match (argument.name.0.as_str(), &argument.value) {
+            ("maxAge", Value::Int(seconds)) if *seconds >= 0 && *seconds <= i64::from(u32::MAX) => {
+                let seconds = *seconds as u32;
+                max_age = quote! { ::std::option::Option::Some(#seconds) };
+            }
+            ("scope", Value::Enum(name)) if name.0 == "PUBLIC" => {}
+            ("scope", Value::Enum(name)) if name.0 == "PRIVATE" => {
+                scope = quote! { ::myoxine::cache::control::CacheScope::Private };
+            }
+            // (this only affects how the server works out the hints for a whole response)
+            ("inheritMaxAge", _) => {}
+            (name, value) => {
+                return Err(format!(
+                    "`{}: {}` isn't a valid argument to `@cacheControl` (it takes `maxAge`, a \
+                    number of seconds, and `scope`, which is `PUBLIC` or `PRIVATE`).",
+                    name, value
+                ))
+            }
+        }
+    }
+    Ok(Some(quote! {
+        ::myoxine::cache::control::CacheHint {
+            max_age: #max_age,
+            scope: #scope,
+        }
+    }))
+}
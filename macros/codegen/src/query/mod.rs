@@ -0,0 +1,62 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+
+//! Turns operations into the text which is sent to the server.
+
+use ast::ast::{FragmentDefinition, Name, OperationDefinition, Selection, SelectionSet};
+
+/// Collects the names of the fragments which a selection set uses (including those used by the
+/// fragments it uses, and so on).
+pub fn used_fragments<'a>(
+    selection_set: &'a SelectionSet,
+    fragments: &[&'a FragmentDefinition],
+    used: &mut Vec<&'a Name>,
+) {
+    for selection in &selection_set.0 {
+        match selection {
+            Selection::Field(field) => {
+                if let Some(selection_set) = &field.selection_set {
+                    used_fragments(selection_set, fragments, used);
+                }
+            }
+            Selection::InlineFragment(fragment) => {
+                used_fragments(&fragment.selection_set, fragments, used)
+            }
+            Selection::FragmentSpread(spread) => {
+                let name = &spread.fragment_name.name;
+                if !used.contains(&name) {
+                    used.push(name);
+                    if let Some(fragment) = fragments
+                        .iter()
+                        .find(|fragment| &fragment.fragment_name.name == name)
+                    {
+                        used_fragments(&fragment.selection_set, fragments, used);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Returns the text of an operation, followed by any of `fragments` which it uses (servers reject
+/// documents containing fragments which aren't used, so the others are left out).
+pub fn operation_text(
+    operation: &OperationDefinition,
+    fragments: &[&FragmentDefinition],
+) -> String {
+    let mut used = vec![];
+    used_fragments(&operation.selection_set, fragments, &mut used);
+    let mut text = operation.to_string();
+    for fragment in fragments
+        .iter()
+        .filter(|fragment| used.contains(&&fragment.fragment_name.name))
+    {
+        text.push(' ');
+        text.push_str(&fragment.to_string());
+    }
+    text
+}
@@ -0,0 +1,365 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+
+//! Turns a whole schema into a Rust module (this is what `graphql_schema!` and the build script API
+//! use).
+//!
+//! The module contains
+//! * `SCHEMA` – metadata about every type in the schema (a `myoxine::schema::SchemaMeta`)
+//! * `types::<Name>` – the metadata for each individual type
+//! * a Rust enum for every GraphQL enum (as if `#[derive(Enum)]` had been used on it)
+//! * a Rust struct for every input object (as if `#[derive(InputObject)]` had been used on it)
+//! * a type alias for every custom scalar (to `serde_json::Value`, as we can't know what it is)
+//!
+//! Objects, interfaces and unions only get metadata, because the Rust types for them depend on what
+//! you select in your queries.
+
+use std::collections::HashSet;
+
+use ast::ast::{
+    Document, EnumTypeDefinition, GraphQLType, InputObjectTypeDefinition, Name, OperationType,
+    TypeDefinition,
+};
+use quote::quote;
+
+use crate::case::{ident, to_pascal_case, to_snake_case};
+use crate::input_object::FieldKind;
+use crate::types::is_nullable;
+
+/// Generates a module called `module` containing the types and metadata for a schema. `path` is
+/// the location of the schema, which is recorded in the metadata, and `extra` is added to the end
+/// of the module (the build script API uses this for queries).
+pub fn schema_module(
+    document: &Document,
+    module: &syn::Ident,
+    path: &str,
+    extra: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let mut metadata = vec![];
+    let mut items = vec![];
+    for type_definition in document.type_definitions() {
+        metadata.push(type_metadata(document, type_definition));
+        match type_definition {
+            TypeDefinition::EnumTypeDefinition(enum_definition) => {
+                items.push(output_enum(enum_definition))
+            }
+            TypeDefinition::InputObjectTypeDefinition(input_object) => {
+                items.push(output_input_object(document, input_object))
+            }
+            TypeDefinition::ScalarTypeDefinition(_) => {
+                let ident = ident(&type_definition.name().0);
+                items.push(quote! {
+                    /// A custom scalar. We don't know what it looks like, so it is left as JSON.
+                    pub type #ident = ::myoxine::serde_json::Value;
+                })
+            }
+            _ => {}
+        }
+    }
+    let type_idents = document
+        .type_definitions()
+        .map(|type_definition| ident(&type_definition.name().0))
+        .collect::<Vec<_>>();
+    let root = |operation_type| match document.root_operation_type(operation_type) {
+        Some(name) => {
+            let name = name.0;
+            quote!(::std::option::Option::Some(#name))
+        }
+        None => quote!(::std::option::Option::None),
+    };
+    let query_type = root(OperationType::Query);
+    let mutation_type = root(OperationType::Mutation);
+    let subscription_type = root(OperationType::Subscription);
+
+    quote! {
+        #[allow(dead_code)]
+        pub mod #module {
+            /// Metadata describing every type in the schema.
+            pub static SCHEMA: ::myoxine::schema::SchemaMeta = ::myoxine::schema::SchemaMeta {
+                path: #path,
+                types: &[#(types::#type_idents),*],
+                query_type: #query_type,
+                mutation_type: #mutation_type,
+                subscription_type: #subscription_type,
+            };
+            /// Metadata describing each type in the schema.
+            #[allow(non_upper_case_globals)]
+            pub mod types {
+                #(#metadata)*
+            }
+            #(#items)*
+            #extra
+        }
+    }
+}
+
+/// Converts a list of names into a token stream for a `&'static [&'static str]`.
+fn names<'a>(names: impl Iterator<Item = &'a Name>) -> proc_macro2::TokenStream {
+    let names = names.map(|name| &name.0);
+    quote!(&[#(#names),*])
+}
+
+/// Emits the `TypeMeta` constant for a type.
+fn type_metadata(
+    document: &Document,
+    type_definition: &TypeDefinition,
+) -> proc_macro2::TokenStream {
+    let name = &type_definition.name().0;
+    let ident = ident(name);
+    let kind = quote::format_ident!(
+        "{}",
+        match type_definition {
+            TypeDefinition::ScalarTypeDefinition(_) => "Scalar",
+            TypeDefinition::ObjectTypeDefinition(_) => "Object",
+            TypeDefinition::InterfaceTypeDefinition(_) => "Interface",
+            TypeDefinition::UnionTypeDefinition(_) => "Union",
+            TypeDefinition::EnumTypeDefinition(_) => "Enum",
+            TypeDefinition::InputObjectTypeDefinition(_) => "InputObject",
+        }
+    );
+    let fields = match type_definition {
+        TypeDefinition::ObjectTypeDefinition(object) => object
+            .fields_definition
+            .iter()
+            .flat_map(|fields| fields.0.iter())
+            .map(|field| (&field.name, &field.graphql_type))
+            .collect::<Vec<_>>(),
+        TypeDefinition::InterfaceTypeDefinition(interface) => interface
+            .fields_definition
+            .iter()
+            .flat_map(|fields| fields.0.iter())
+            .map(|field| (&field.name, &field.graphql_type))
+            .collect::<Vec<_>>(),
+        TypeDefinition::InputObjectTypeDefinition(input_object) => input_object
+            .input_fields_definition
+            .iter()
+            .flat_map(|fields| fields.0.iter())
+            .map(|field| (&field.name, &field.graphql_type))
+            .collect::<Vec<_>>(),
+        _ => vec![],
+    }
+    .into_iter()
+    .map(|(name, graphql_type)| {
+        let name = &name.0;
+        let graphql_type = graphql_type.to_string();
+        quote! {
+            ::myoxine::schema::FieldMeta { name: #name, graphql_type: #graphql_type }
+        }
+    });
+    let possible_types = names(
+        document
+            .possible_types(type_definition.name())
+            .unwrap_or_default()
+            .into_iter(),
+    );
+    let enum_values = match type_definition {
+        TypeDefinition::EnumTypeDefinition(enum_definition) => names(
+            enum_definition
+                .enum_values_definition
+                .iter()
+                .flat_map(|values| values.0.iter())
+                .map(|value| &value.enum_value.0),
+        ),
+        _ => quote!(&[]),
+    };
+    let cache_control = match type_definition {
+        TypeDefinition::ObjectTypeDefinition(object) => {
+            let fields = object
+                .fields_definition
+                .iter()
+                .flat_map(|fields| fields.0.iter())
+                .map(|field| field.name.0.as_str())
+                .collect::<Vec<_>>();
+            crate::object::cache_control(object, &fields)
+                .unwrap_or_else(|message| Some(quote!(::std::compile_error!(#message))))
+        }
+        _ => None,
+    }
+    .unwrap_or_else(|| quote!(::myoxine::cache::control::CacheControl::NONE));
+    quote! {
+        pub const #ident: ::myoxine::schema::TypeMeta = ::myoxine::schema::TypeMeta {
+            name: #name,
+            kind: ::myoxine::schema::TypeKind::#kind,
+            fields: &[#(#fields),*],
+            possible_types: #possible_types,
+            enum_values: #enum_values,
+            cache_control: #cache_control,
+        };
+    }
+}
+
+/// Emits a Rust enum for a GraphQL enum.
+fn output_enum(enum_definition: &EnumTypeDefinition) -> proc_macro2::TokenStream {
+    let values = enum_definition
+        .enum_values_definition
+        .iter()
+        .flat_map(|values| values.0.iter())
+        .map(|value| (value.enum_value.0).0.clone())
+        .collect::<Vec<_>>();
+    let variants = values
+        .iter()
+        .map(|value| (ident(&to_pascal_case(value)), value.clone()))
+        .collect::<Vec<_>>();
+    // it's quite common for enums to have an `UNKNOWN` value of their own
+    let fallback = if variants.iter().any(|(variant, _)| variant == "Unknown") {
+        quote::format_ident!("UnknownValue")
+    } else {
+        quote::format_ident!("Unknown")
+    };
+    let ident = ident(&enum_definition.name.0);
+    let variant_idents = variants.iter().map(|(ident, _)| ident);
+    let impls = crate::enumeration::output_impls(&ident, &variants, &fallback);
+    quote! {
+        #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+        pub enum #ident {
+            #(#variant_idents,)*
+            /// A value which wasn't in the schema when this code was generated.
+            #fallback(::std::string::String),
+        }
+        #impls
+    }
+}
+
+/// Checks whether the input object `from` contains `target` (without going through a list, which
+/// would provide the indirection needed to stop the type from being infinitely large).
+fn contains_input_object(
+    document: &Document,
+    from: &Name,
+    target: &Name,
+    visited: &mut HashSet<Name>,
+) -> bool {
+    if from == target {
+        return true;
+    }
+    if !visited.insert(from.clone()) {
+        return false;
+    }
+    match document.get_type_definition(from) {
+        Some(TypeDefinition::InputObjectTypeDefinition(input_object)) => input_object
+            .input_fields_definition
+            .iter()
+            .flat_map(|fields| fields.0.iter())
+            .filter_map(|field| direct_type(&field.graphql_type))
+            .any(|name| contains_input_object(document, name, target, visited)),
+        _ => false,
+    }
+}
+
+/// Returns the name of a type if it isn't a list.
+fn direct_type(graphql_type: &GraphQLType) -> Option<&Name> {
+    match graphql_type {
+        GraphQLType::NamedType(named) => Some(&named.0),
+        GraphQLType::NonNullType(inner) => direct_type(inner),
+        GraphQLType::ListType(_) => None,
+    }
+}
+
+/// Emits the Rust type for a GraphQL type. Named types which are not built-in scalars are assumed
+/// to be defined in the generated module. `boxed` is used to box recursive input objects.
+fn rust_type(graphql_type: &GraphQLType, boxed: bool) -> proc_macro2::TokenStream {
+    fn inner(graphql_type: &GraphQLType, boxed: bool) -> proc_macro2::TokenStream {
+        match graphql_type {
+            GraphQLType::NonNullType(graphql_type) => inner(graphql_type, boxed),
+            GraphQLType::ListType(item) => {
+                let item = rust_type(item, false);
+                quote!(::std::vec::Vec<#item>)
+            }
+            GraphQLType::NamedType(named) => {
+                let ty = match (named.0).0.as_str() {
+                    "Int" => quote!(i32),
+                    "Float" => quote!(f64),
+                    "String" => quote!(::std::string::String),
+                    "Boolean" => quote!(bool),
+                    "ID" => quote!(::myoxine::Id),
+                    other => {
+                        let ident = ident(other);
+                        quote!(#ident)
+                    }
+                };
+                if boxed {
+                    quote!(::std::boxed::Box<#ty>)
+                } else {
+                    ty
+                }
+            }
+        }
+    }
+    let ty = inner(graphql_type, boxed);
+    if is_nullable(graphql_type) {
+        quote!(::std::option::Option<#ty>)
+    } else {
+        ty
+    }
+}
+
+/// Emits a Rust struct for a GraphQL input object. Nullable fields and fields with default values
+/// become `Option`s, which are left out when they are `None`.
+fn output_input_object(
+    document: &Document,
+    input_object: &InputObjectTypeDefinition,
+) -> proc_macro2::TokenStream {
+    let mut fields = vec![];
+    let mut field_types = vec![];
+    for field in input_object
+        .input_fields_definition
+        .iter()
+        .flat_map(|fields| fields.0.iter())
+    {
+        let boxed = direct_type(&field.graphql_type)
+            .map(|name| {
+                contains_input_object(document, name, &input_object.name, &mut HashSet::new())
+            })
+            .unwrap_or(false);
+        let mut ty = rust_type(&field.graphql_type, boxed);
+        let kind = if is_nullable(&field.graphql_type) {
+            FieldKind::Optional
+        } else if field.default_value.is_some() {
+            ty = quote!(::std::option::Option<#ty>);
+            FieldKind::Optional
+        } else {
+            FieldKind::Required
+        };
+        field_types.push(ty);
+        fields.push((
+            ident(&to_snake_case(&field.name.0)),
+            field.name.0.clone(),
+            kind,
+        ));
+    }
+    let ident = ident(&input_object.name.0);
+    let field_idents = fields.iter().map(|(ident, _, _)| ident);
+    let impls = crate::input_object::output_impls(&ident, &input_object.name.0, &fields);
+    quote! {
+        #[derive(Clone, Debug, PartialEq)]
+        pub struct #ident {
+            #(pub #field_idents: #field_types,)*
+        }
+        #impls
+    }
+}
+
+#[cfg(test)]
+mod test_schema_module {
+    use super::*;
+
+    #[test]
+    fn test_recursive_input_objects_are_boxed() {
+        let document =
+            ast::parse_string(r#"input Filter { and: [Filter!] not: Filter name: String }"#)
+                .unwrap();
+        let filter = match document.get_type_definition(&Name("Filter".to_string())) {
+            Some(TypeDefinition::InputObjectTypeDefinition(filter)) => filter,
+            _ => unreachable!(),
+        };
+        let output = output_input_object(&document, filter).to_string();
+        assert!(output.contains(
+            "pub not : :: std :: option :: Option < :: std :: boxed :: Box < Filter > >"
+        ));
+        assert!(output
+            .contains("pub and : :: std :: option :: Option < :: std :: vec :: Vec < Filter > >"));
+    }
+}
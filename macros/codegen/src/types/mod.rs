@@ -0,0 +1,136 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+
+//! Checks that Rust types correspond to the GraphQL types in a schema.
+//!
+//! The rules are fairly simple: a non-null GraphQL type `T!` is represented as `T`, a nullable type
+//! `T` as `Option<T>` and a list `[T]` as `Vec<T>`. Built-in scalars map onto the Rust types listed
+//! in `scalar_types`; everything else (objects, enums, input objects and custom scalars) must be a
+//! Rust type with the same name as the GraphQL one.
+
+use ast::ast::GraphQLType;
+
+/// Returns the Rust types which we accept for each of the built-in GraphQL scalars. Note that
+/// `Id` is `myoxine::Id`, which is just a type alias for `String`.
+fn scalar_types(name: &str) -> Option<&'static [&'static str]> {
+    Some(match name {
+        "Int" => &["i32"],
+        "Float" => &["f64"],
+        "String" => &["String"],
+        "Boolean" => &["bool"],
+        "ID" => &["String", "Id"],
+        _ => return None,
+    })
+}
+
+/// If `ty` is `wrapper<T>` (e.g. `Option<T>`) returns `T`.
+pub fn unwrap_generic<'a>(ty: &'a syn::Type, wrapper: &str) -> Option<&'a syn::Type> {
+    let path = match ty {
+        syn::Type::Path(path) if path.qself.is_none() => path,
+        _ => return None,
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != wrapper {
+        return None;
+    }
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(arguments) if arguments.args.len() == 1 => {
+            match arguments.args.first() {
+                Some(syn::GenericArgument::Type(ty)) => Some(ty),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Checks whether a GraphQL type can be `null`.
+pub fn is_nullable(graphql_type: &GraphQLType) -> bool {
+    !matches!(graphql_type, GraphQLType::NonNullType(_))
+}
+
+/// Checks that the Rust type `ty` can be used to represent values of `graphql_type`.
+pub fn type_matches(graphql_type: &GraphQLType, ty: &syn::Type) -> bool {
+    match graphql_type {
+        GraphQLType::NonNullType(inner) => {
+            unwrap_generic(ty, "Option").is_none() && inner_type_matches(inner, ty)
+        }
+        nullable => match unwrap_generic(ty, "Option") {
+            Some(ty) => inner_type_matches(nullable, ty),
+            None => false,
+        },
+    }
+}
+
+/// Like `type_matches`, but ignores whether the outermost type is nullable.
+fn inner_type_matches(graphql_type: &GraphQLType, ty: &syn::Type) -> bool {
+    match graphql_type {
+        GraphQLType::NonNullType(inner) => inner_type_matches(inner, ty),
+        GraphQLType::ListType(item) => match unwrap_generic(ty, "Vec") {
+            Some(ty) => type_matches(item, ty),
+            None => false,
+        },
+        GraphQLType::NamedType(named) => {
+            let name = &(named.0).0;
+            let ident = match ty {
+                syn::Type::Path(path) if path.qself.is_none() => match path.path.segments.last() {
+                    Some(segment) if segment.arguments.is_empty() => segment.ident.to_string(),
+                    _ => return false,
+                },
+                _ => return false,
+            };
+            match scalar_types(name) {
+                Some(accepted) => accepted.contains(&ident.as_str()),
+                None => &ident == name,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_type_matching {
+    use super::*;
+    use ast::ast::{Name, NamedType};
+
+    fn named(name: &str) -> GraphQLType {
+        GraphQLType::NamedType(NamedType(Name(name.to_string())))
+    }
+
+    fn non_null(graphql_type: GraphQLType) -> GraphQLType {
+        GraphQLType::NonNullType(Box::new(graphql_type))
+    }
+
+    fn ty(input: &str) -> syn::Type {
+        syn::parse_str(input).unwrap()
+    }
+
+    #[test]
+    fn test_scalars() {
+        assert!(type_matches(&non_null(named("Int")), &ty("i32")));
+        assert!(type_matches(&non_null(named("ID")), &ty("myoxine::Id")));
+        assert!(type_matches(&named("String"), &ty("Option<String>")));
+        assert!(!type_matches(&named("String"), &ty("String")));
+        assert!(!type_matches(
+            &non_null(named("String")),
+            &ty("Option<String>")
+        ));
+        assert!(!type_matches(&non_null(named("Int")), &ty("i64")));
+    }
+
+    #[test]
+    fn test_lists_and_named_types() {
+        let list = non_null(GraphQLType::ListType(Box::new(non_null(named("User")))));
+        assert!(type_matches(&list, &ty("Vec<User>")));
+        assert!(!type_matches(&list, &ty("Vec<Option<User>>")));
+        assert!(!type_matches(&list, &ty("Vec<Post>")));
+        let nullable_list = GraphQLType::ListType(Box::new(named("Int")));
+        assert!(type_matches(
+            &nullable_list,
+            &ty("Option<Vec<Option<i32>>>")
+        ));
+    }
+}
@@ -32,11 +32,17 @@ impl QueryCheckingContext {
     }
 }
 
-fn call_site_spanned_error(error: &'static str) -> syn::Error {
-    syn::Error::new(proc_macro2::Span::call_site(), error)
+#[allow(dead_code)]
+impl QueryCheckingContext {
+    /// Creates an error which points at the attribute the query came from (the AST doesn't store
+    /// spans, so this is the closest we can get for now).
+    fn error(&self, error: &'static str) -> syn::Error {
+        syn::Error::new_spanned(&self.attribute, error)
+    }
 }
 
 /// A trait for ensuring that ASTs are well-formed queries.
+#[allow(dead_code)]
 pub trait CheckQuery<ERROR = syn::Error, CONTEXT = QueryCheckingContext> {
     fn check(&self, context: &CONTEXT) -> Result<(), ERROR>;
 }
@@ -54,12 +60,12 @@ impl CheckQuery for Definition {
     fn check(&self, context: &QueryCheckingContext) -> Result<(), syn::Error> {
         match self {
             Definition::ExecutableDefinition(def) => def.check(context),
-            Definition::TypeSystemDefinition(_) => Err(call_site_spanned_error(
-                "Type system definitions are not valid inside Myoxine queries.",
-            )),
-            Definition::TypeSystemExtension(_) => Err(call_site_spanned_error(
-                "Type system definitions are not valid inside Myoxine queries.",
-            )),
+            Definition::TypeSystemDefinition(_) => {
+                Err(context.error("Type system definitions are not valid inside Myoxine queries."))
+            }
+            Definition::TypeSystemExtension(_) => {
+                Err(context.error("Type system definitions are not valid inside Myoxine queries."))
+            }
         }
     }
 }
@@ -68,22 +74,24 @@ impl CheckQuery for ExecutableDefinition {
     fn check(&self, context: &QueryCheckingContext) -> Result<(), syn::Error> {
         match self {
             ExecutableDefinition::OperationDefinition(op_def) => op_def.check(context),
-            ExecutableDefinition::FragmentDefinition(_) => Err(call_site_spanned_error(
-                "Fragments are not yet supported, though support is planned.",
-            )),
+            ExecutableDefinition::FragmentDefinition(_) => {
+                Err(context.error("Fragments are not yet supported, though support is planned."))
+            }
         }
     }
 }
 
 impl CheckQuery for OperationDefinition {
-    fn check(&self, _: &QueryCheckingContext) -> Result<(), syn::Error> {
+    fn check(&self, context: &QueryCheckingContext) -> Result<(), syn::Error> {
         match self.operation_type.token {
             OperationType::Query => {}
             OperationType::Subscription | OperationType::Mutation => {
-                return Err(call_site_spanned_error("Mutations and subscriptions are not permitted inside queries.
+                return Err(context.error("Mutations and subscriptions are not permitted inside queries.
                            Subscriptions are also not currently supported, though support is on the long-term roadmap."))
             }
         };
-        todo!()
+        // everything else (fields, arguments, fragments) is checked against the schema by
+        // `ast::validate`, which needs the whole document rather than one operation
+        Ok(())
     }
 }
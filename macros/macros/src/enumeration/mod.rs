@@ -0,0 +1,247 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+
+//! Contains code with which one can derive `Enum` on a Rust enum corresponding to a GraphQL enum.
+//!
+//! Each unit variant is matched against a value of the GraphQL enum by converting its name to
+//! `SCREAMING_SNAKE_CASE` (so `NewHope` becomes `NEW_HOPE`). Every enum must also contain an
+//! `Unknown(String)` variant – servers are allowed to add values to an enum without this being
+//! considered a breaking change, and we'd rather not fail to deserialize a whole response because
+//! of it.
+
+use std::collections::HashSet;
+
+use ast::ast::{EnumTypeDefinition, TypeDefinition};
+use ast::suggest::suggestion;
+use codegen::case::{to_pascal_case, to_screaming_snake_case};
+
+use crate::search::{find_type_definition, load_schema, wrong_kind, Schema};
+
+/// The name of the variant used to store values which were not in the schema at compile time.
+const FALLBACK_VARIANT: &str = "Unknown";
+
+/// Derives `Enum` on the specified enum.
+pub fn derive_enum(input: syn::DeriveInput) -> Result<proc_macro2::TokenStream, syn::Error> {
+    let data = match &input.data {
+        syn::Data::Enum(data) => data,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                input.ident.clone(),
+                "`#[derive(Enum)]` can only be used on enums.",
+            ))
+        }
+    };
+    let schema = load_schema(&input)?;
+    match find_type_definition(&schema, &input)? {
+        TypeDefinition::EnumTypeDefinition(type_def) => {
+            output_enum(&schema, type_def, data, &input).map(|output| schema.tracked(output))
+        }
+        other => Err(wrong_kind(&schema, &input, other, "an enum")),
+    }
+}
+
+/// Checks that a variant is the `Unknown(String)` fallback variant.
+fn is_fallback(variant: &syn::Variant) -> bool {
+    match &variant.fields {
+        syn::Fields::Unnamed(fields) => {
+            variant.ident == FALLBACK_VARIANT
+                && fields.unnamed.len() == 1
+                && match &fields.unnamed[0].ty {
+                    syn::Type::Path(path) => path.path.is_ident("String"),
+                    _ => false,
+                }
+        }
+        _ => false,
+    }
+}
+
+fn output_enum(
+    schema: &Schema,
+    type_def: &EnumTypeDefinition,
+    data: &syn::DataEnum,
+    input: &syn::DeriveInput,
+) -> Result<proc_macro2::TokenStream, syn::Error> {
+    let schema_values = type_def
+        .enum_values_definition
+        .as_ref()
+        .map(|values| {
+            values
+                .0
+                .iter()
+                .map(|value| value.enum_value.0 .0.clone())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let mut variants = vec![];
+    let mut seen = HashSet::new();
+    let mut has_fallback = false;
+    for variant in &data.variants {
+        if is_fallback(variant) {
+            has_fallback = true;
+            continue;
+        }
+        if !matches!(variant.fields, syn::Fields::Unit) {
+            return Err(syn::Error::new_spanned(
+                variant,
+                "Variants of GraphQL enums can't contain any data (apart from the \
+                `Unknown(String)` variant).",
+            ));
+        }
+        let graphql_name = if schema_values.contains(&variant.ident.to_string()) {
+            variant.ident.to_string()
+        } else {
+            to_screaming_snake_case(&variant.ident.to_string())
+        };
+        if !schema_values.contains(&graphql_name) {
+            // suggest the name of the variant which would have worked
+            let variant_names = schema_values
+                .iter()
+                .map(|value| to_pascal_case(value))
+                .collect::<Vec<_>>();
+            return Err(syn::Error::new_spanned(
+                &variant.ident,
+                format!(
+                    "`{}` (`{}` in GraphQL) is not one of the values of the `{}` enum in your \
+                    schema.{}{}",
+                    variant.ident,
+                    graphql_name,
+                    type_def.name,
+                    suggestion(
+                        &variant.ident.to_string(),
+                        variant_names.iter().map(String::as_str)
+                    ),
+                    schema.note(&type_def.name)
+                ),
+            ));
+        }
+        if !seen.insert(graphql_name.clone()) {
+            return Err(syn::Error::new_spanned(
+                &variant.ident,
+                format!("More than one variant corresponds to `{}`.", graphql_name),
+            ));
+        }
+        variants.push((variant.ident.clone(), graphql_name));
+    }
+
+    if !has_fallback {
+        return Err(syn::Error::new_spanned(
+            input.ident.clone(),
+            "GraphQL enums must have an `Unknown(String)` variant. Servers can add new values to \
+            an enum at any time, and this variant stops those values from causing \
+            deserialization to fail.",
+        ));
+    }
+    let missing = schema_values
+        .iter()
+        .filter(|value| !seen.contains(*value))
+        .map(|value| format!("`{}`", value))
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        return Err(syn::Error::new_spanned(
+            input.ident.clone(),
+            format!(
+                "This enum is missing variants for the following values of `{}`: {}.{}",
+                type_def.name,
+                missing.join(", "),
+                schema.note(&type_def.name)
+            ),
+        ));
+    }
+
+    Ok(codegen::enumeration::output_impls(
+        &input.ident,
+        &variants,
+        &quote::format_ident!("{}", FALLBACK_VARIANT),
+    ))
+}
+
+#[cfg(test)]
+mod test_enum_derive_macro {
+    use super::*;
+
+    fn derive(input: &str) -> Result<proc_macro2::TokenStream, syn::Error> {
+        derive_enum(syn::parse_str(input).expect("failed to parse"))
+    }
+
+    #[test]
+    fn test_simple_enum_derivation() {
+        let output = derive(
+            r#"
+        #[derive(Enum)]
+        #[schema="schema.graphql"]
+        enum Role {
+            Admin,
+            Moderator,
+            Member,
+            Unknown(String)
+        }
+        "#,
+        )
+        .unwrap_or_else(|e| panic!("{}", e));
+        let output = output.to_string();
+        assert!(output.contains("Self :: Moderator => \"MODERATOR\""));
+        assert!(output.contains("\"MEMBER\" => Self :: Member"));
+        assert!(output.contains("_ => Self :: Unknown (value)"));
+    }
+
+    #[test]
+    fn test_enum_derivation_requires_fallback() {
+        assert!(derive(
+            r#"
+        #[schema="schema.graphql"]
+        enum Role { Admin, Moderator, Member }
+        "#,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_enum_derivation_checks_values() {
+        // `Owner` doesn't exist in the schema
+        assert!(derive(
+            r#"
+        #[schema="schema.graphql"]
+        enum Role { Admin, Moderator, Member, Owner, Unknown(String) }
+        "#,
+        )
+        .is_err());
+        // `MEMBER` isn't covered
+        assert!(derive(
+            r#"
+        #[schema="schema.graphql"]
+        enum Role { Admin, Moderator, Unknown(String) }
+        "#,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_enum_derivation_suggests_values() {
+        let error = derive(
+            r#"
+        #[schema="schema.graphql"]
+        enum Role { Admin, Moderator, Membr, Unknown(String) }
+        "#,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("Did you mean `Member`?"));
+        assert!(error.contains("`Role` is defined at schema.graphql:"));
+    }
+
+    #[test]
+    fn test_enum_derivation_on_wrong_type() {
+        assert!(derive(
+            r#"
+        #[schema="schema.graphql"]
+        enum User { Unknown(String) }
+        "#,
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,293 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+
+//! Contains code with which one can derive `InputObject` on a Rust struct corresponding to a
+//! GraphQL input object.
+//!
+//! The struct is checked against the input object in the schema (see the `types` module for the
+//! rules) and a `Serialize` implementation is generated so that it can be used as a variable.
+//! Fields of type `Option<T>` are left out of the output when they are `None`, which is *not* the
+//! same as sending `null` (the server will use the field's default value, if it has one). If you
+//! need to send an explicit `null`, use `Option<Option<T>>` and set the field to `Some(None)`.
+//!
+//! Fields which are non-null in the schema but have a default value may also be given the type
+//! `Option<T>`, in which case leaving them as `None` means the server's default will be used.
+
+use ast::ast::{InputObjectTypeDefinition, InputValueDefinition, TypeDefinition};
+use codegen::input_object::FieldKind;
+use codegen::types::{is_nullable, type_matches, unwrap_generic};
+use syn::ext::IdentExt;
+
+use ast::suggest::suggestion;
+
+use crate::search::{find_type_definition, load_schema, wrong_kind, Schema};
+
+/// Derives `InputObject` on the specified struct.
+pub fn derive_input_object(
+    input: syn::DeriveInput,
+) -> Result<proc_macro2::TokenStream, syn::Error> {
+    let fields = match &input.data {
+        syn::Data::Struct(syn::DataStruct {
+            fields: syn::Fields::Named(fields),
+            ..
+        }) => fields,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                input.ident.clone(),
+                "`#[derive(InputObject)]` can only be used on structs with named fields.",
+            ))
+        }
+    };
+    let schema = load_schema(&input)?;
+    match find_type_definition(&schema, &input)? {
+        TypeDefinition::InputObjectTypeDefinition(type_def) => {
+            output_input_object(&schema, type_def, fields, &input)
+                .map(|output| schema.tracked(output))
+        }
+        other => Err(wrong_kind(&schema, &input, other, "an input object")),
+    }
+}
+
+/// Checks a single field against its definition in the schema.
+fn check_field(
+    schema: &Schema,
+    type_def: &InputObjectTypeDefinition,
+    definition: &InputValueDefinition,
+    field: &syn::Field,
+) -> Result<FieldKind, syn::Error> {
+    let ty = &field.ty;
+    let error = || {
+        syn::Error::new_spanned(
+            ty,
+            format!(
+                "The type of this field does not match the type of `{}` (`{}`) in the GraphQL \
+                schema you have provided.{}",
+                definition.name,
+                definition.graphql_type,
+                schema.note(&type_def.name)
+            ),
+        )
+    };
+    if is_nullable(&definition.graphql_type) {
+        if type_matches(&definition.graphql_type, ty) {
+            return Ok(FieldKind::Optional);
+        }
+        // `Option<Option<T>>`, for which `Some(None)` means an explicit `null`
+        match unwrap_generic(ty, "Option") {
+            Some(inner) if type_matches(&definition.graphql_type, inner) => Ok(FieldKind::Optional),
+            _ => Err(error()),
+        }
+    } else if type_matches(&definition.graphql_type, ty) {
+        Ok(FieldKind::Required)
+    } else {
+        match unwrap_generic(ty, "Option") {
+            Some(inner)
+                if definition.default_value.is_some()
+                    && type_matches(&definition.graphql_type, inner) =>
+            {
+                Ok(FieldKind::Optional)
+            }
+            _ => Err(error()),
+        }
+    }
+}
+
+fn output_input_object(
+    schema: &Schema,
+    type_def: &InputObjectTypeDefinition,
+    fields: &syn::FieldsNamed,
+    input: &syn::DeriveInput,
+) -> Result<proc_macro2::TokenStream, syn::Error> {
+    let definitions = type_def
+        .input_fields_definition
+        .as_ref()
+        .map(|definitions| definitions.0.as_slice())
+        .unwrap_or_default();
+
+    let mut output_fields = vec![];
+    for field in &fields.named {
+        let ident = field.ident.as_ref().expect("named fields have names");
+        let name = ident.unraw().to_string();
+        let definition = definitions
+            .iter()
+            .find(|definition| definition.name.0 == name)
+            .ok_or_else(|| {
+                syn::Error::new_spanned(
+                    ident,
+                    format!(
+                        "The input object `{}` has no field called `{}`.{}{}",
+                        type_def.name,
+                        name,
+                        suggestion(
+                            &name,
+                            definitions
+                                .iter()
+                                .map(|definition| definition.name.0.as_str())
+                        ),
+                        schema.note(&type_def.name)
+                    ),
+                )
+            })?;
+        output_fields.push((
+            ident.clone(),
+            name,
+            check_field(schema, type_def, definition, field)?,
+        ));
+    }
+
+    let missing = definitions
+        .iter()
+        .filter(|definition| {
+            !is_nullable(&definition.graphql_type) && definition.default_value.is_none()
+        })
+        .filter(|definition| {
+            !fields
+                .named
+                .iter()
+                .filter_map(|field| field.ident.as_ref())
+                .any(|ident| ident.unraw() == definition.name.0)
+        })
+        .map(|definition| format!("`{}`", definition.name))
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        return Err(syn::Error::new_spanned(
+            input.ident.clone(),
+            format!(
+                "This struct is missing the following required fields of `{}`: {}.{}",
+                type_def.name,
+                missing.join(", "),
+                schema.note(&type_def.name)
+            ),
+        ));
+    }
+
+    Ok(codegen::input_object::output_impls(
+        &input.ident,
+        &type_def.name.to_string(),
+        &output_fields,
+    ))
+}
+
+#[cfg(test)]
+mod test_input_object_derive_macro {
+    use super::*;
+
+    fn derive(input: &str) -> Result<proc_macro2::TokenStream, syn::Error> {
+        derive_input_object(syn::parse_str(input).expect("failed to parse"))
+    }
+
+    #[test]
+    fn test_simple_input_object_derivation() {
+        let output = derive(
+            r#"
+        #[derive(InputObject)]
+        #[schema="schema.graphql"]
+        struct AddressInput {
+            street: String,
+            city: String,
+            postcode: Option<String>
+        }
+        "#,
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+        .to_string();
+        assert!(output.contains("serialize_entry (& mut map , \"street\" , & self . street)"));
+        assert!(output
+            .contains("if let :: std :: option :: Option :: Some (value) = & self . postcode"));
+        assert!(output.contains("const GRAPHQL_TYPE : & 'static str = \"AddressInput\""));
+    }
+
+    #[test]
+    fn test_nested_input_objects_and_defaults() {
+        derive(
+            r#"
+        #[schema="schema.graphql"]
+        struct CreateUserInput {
+            username: String,
+            role: Option<Role>,
+            bio: Option<Option<String>>,
+            address: Option<AddressInput>,
+            tags: Option<Vec<String>>
+        }
+        "#,
+        )
+        .unwrap_or_else(|e| panic!("{}", e));
+        // fields with default values don't need to be provided
+        derive(
+            r#"
+        #[schema="schema.graphql"]
+        struct CreateUserInput {
+            username: String
+        }
+        "#,
+        )
+        .unwrap_or_else(|e| panic!("{}", e));
+    }
+
+    #[test]
+    fn test_input_object_derivation_suggests_fields() {
+        let error = derive(
+            r#"
+        #[schema="schema.graphql"]
+        struct CreateUserInput { usrname: String }
+        "#,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.starts_with(
+            "The input object `CreateUserInput` has no field called `usrname`. Did you mean \
+            `username`?"
+        ));
+    }
+
+    #[test]
+    fn test_input_object_derivation_checks_fields() {
+        // `username` is required
+        assert!(derive(
+            r#"
+        #[schema="schema.graphql"]
+        struct CreateUserInput { bio: Option<String> }
+        "#,
+        )
+        .is_err());
+        // `bio` is nullable
+        assert!(derive(
+            r#"
+        #[schema="schema.graphql"]
+        struct CreateUserInput { username: String, bio: String }
+        "#,
+        )
+        .is_err());
+        // `email` doesn't exist
+        assert!(derive(
+            r#"
+        #[schema="schema.graphql"]
+        struct CreateUserInput { username: String, email: String }
+        "#,
+        )
+        .is_err());
+        // `street` doesn't have a default value, so it can't be optional
+        assert!(derive(
+            r#"
+        #[schema="schema.graphql"]
+        struct AddressInput { street: Option<String>, city: String }
+        "#,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_input_object_derivation_on_wrong_type() {
+        assert!(derive(
+            r#"
+        #[schema="schema.graphql"]
+        struct User { id: i32 }
+        "#,
+        )
+        .is_err());
+    }
+}
@@ -7,9 +7,13 @@ A copy of the license can be found at the root of this Git repository.
 //! Useful macros for creating GraphQL queries inside applications.
 
 mod check;
+mod enumeration;
+mod input_object;
 mod mutation;
 mod object;
+mod polymorphic;
 mod query;
+mod schema;
 mod search;
 
 mod tests;
@@ -19,7 +23,52 @@ extern crate proc_macro;
 use crate::query::query_inner;
 use proc_macro::TokenStream;
 
-#[proc_macro_derive(Object, attributes(schema, id))]
+#[proc_macro_derive(Object, attributes(schema, id, graphql))]
+/// Implements `myoxine::objects::Object` (along with `Selection`) on a struct which corresponds to a
+/// GraphQL object. A `<Type>Fields` struct is also generated, which can be used to refetch only
+/// some of the fields.
+/// ```ignore
+/// #[derive(Object)]
+/// #[schema = "schema.graphql"]
+/// #[graphql(rename_all = "camelCase", serde)]
+/// struct Post {
+///     // this is the `id` field from the `Node` interface
+///     #[id]
+///     id: myoxine::Id,
+///     // `publishedAt` in the schema
+///     published_at: Option<String>,
+///     #[graphql(rename = "title")]
+///     heading: String,
+/// }
+/// ```
+/// Objects also have to implement `Serialize` and `Deserialize`, using the names the fields have in
+/// the schema. `#[graphql(serde)]` (as above) generates these from the GraphQL names; otherwise
+/// serde's own derives can be used, as long as they're given matching `#[serde(rename...)]`
+/// attributes (which is checked). Nested objects are only stored as records of their own if they
+/// serialize their `__typename`, so those should have `#[serde(tag = "__typename")]`.
+/// ```ignore
+/// #[derive(Object, Serialize, Deserialize)]
+/// #[schema = "schema.graphql"]
+/// #[graphql(rename_all = "camelCase")]
+/// #[serde(rename_all = "camelCase")]
+/// struct Post {
+///     #[id]
+///     id: myoxine::Id,
+///     published_at: Option<String>,
+/// }
+/// ```
+/// The path in `#[schema = "..."]` is relative to the crate's `Cargo.toml` (this goes for all of
+/// the macros). If every item uses the same schema the attribute can be left out and the
+/// `MYOXINE_SCHEMA` environment variable set instead, e.g. in `.cargo/config.toml`:
+/// ```toml
+/// [env]
+/// MYOXINE_SCHEMA = { value = "schema.graphql", relative = true }
+/// ```
+/// A schema which is split across several files can be given as a glob
+/// (`#[schema = "schema/*.graphql"]`) or a list (`#[schema("users.graphql", "posts.graphql")]`);
+/// the files are merged, and a type can only be defined in one of them. The compiler only notices
+/// changes to files it has already seen, so after adding a new file which matches a glob you need
+/// to touch one of the others (or `cargo clean -p` your crate).
 pub fn derive_object_on_struct(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input = syn::parse_macro_input!(input as syn::DeriveInput);
     match object::derive_object(input) {
@@ -28,8 +77,146 @@ pub fn derive_object_on_struct(input: proc_macro::TokenStream) -> proc_macro::To
     }
 }
 
+#[proc_macro_derive(ValueObject, attributes(schema, graphql))]
+/// Like `#[derive(Object)]`, but for objects which don't implement the `Node` interface (and so
+/// have no identity of their own). These implement `myoxine::objects::ValueObject` rather than
+/// `Object`; they can be used as fields of other objects (and are stored as part of them in the
+/// cache) but can't be refetched or cached by themselves. `Serialize` and `Deserialize` work in the
+/// same way as for `Object`.
+/// ```ignore
+/// #[derive(ValueObject, Serialize, Deserialize)]
+/// #[schema = "schema.graphql"]
+/// struct Address {
+///     street: String,
+///     city: String,
+/// }
+/// ```
+pub fn derive_value_object(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    match object::derive_value_object(input) {
+        Ok(t) => t.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+#[proc_macro_derive(Enum, attributes(schema))]
+/// Implements `Serialize` and `Deserialize` on an enum which corresponds to a GraphQL enum,
+/// checking that its variants match the values in the schema.
+/// ```ignore
+/// #[derive(Enum)]
+/// #[schema = "schema.graphql"]
+/// enum Episode {
+///     NewHope, // NEW_HOPE
+///     Empire,  // EMPIRE
+///     Jedi,    // JEDI
+///     Unknown(String),
+/// }
+/// ```
+pub fn derive_enum(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    match enumeration::derive_enum(input) {
+        Ok(t) => t.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+#[proc_macro_derive(InputObject, attributes(schema))]
+/// Implements `Serialize` and `myoxine::input::InputObject` on a struct which corresponds to a
+/// GraphQL input object, so that it can be sent to the server as a variable.
+/// ```ignore
+/// #[derive(InputObject)]
+/// #[schema = "schema.graphql"]
+/// struct CreateUserInput {
+///     username: String,
+///     // `None` leaves this out of the request entirely; `Some(None)` sends `null`
+///     bio: Option<Option<String>>,
+///     // nested input objects are serialized too
+///     address: Option<AddressInput>,
+/// }
+/// ```
+pub fn derive_input_object(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    match input_object::derive_input_object(input) {
+        Ok(t) => t.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+#[proc_macro_derive(Union, attributes(schema))]
+/// Implements `Serialize`, `Deserialize` and `Selection` on an enum which corresponds to a GraphQL
+/// union. Each variant corresponds to one of the union's members, and `__typename` is used to work
+/// out which one the server sent.
+/// ```ignore
+/// #[derive(Union)]
+/// #[schema = "schema.graphql"]
+/// enum SearchResult {
+///     User(User),
+///     Post(Post),
+///     Unknown(String), // stores the `__typename` of anything else
+/// }
+/// ```
+pub fn derive_union(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    match polymorphic::derive_polymorphic(input, polymorphic::Kind::Union) {
+        Ok(t) => t.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+#[proc_macro_derive(Interface, attributes(schema))]
+/// Like `#[derive(Union)]`, but for GraphQL interfaces. Each variant corresponds to an object which
+/// implements the interface.
+pub fn derive_interface(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    match polymorphic::derive_polymorphic(input, polymorphic::Kind::Interface) {
+        Ok(t) => t.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
 #[proc_macro]
-/// A derive macro which implements the `Query` trait on structs.
+/// Parses a schema once and turns it into a Rust module, containing metadata about every type
+/// along with Rust types for the schema's enums, input objects and custom scalars.
+/// ```ignore
+/// graphql_schema!("schema.graphql"); // generates `mod schema`
+/// graphql_schema!(github = "github.graphql"); // generates `mod github`
+///
+/// let role = schema::Role::Admin;
+/// assert_eq!(schema::SCHEMA.get_type("User").unwrap().kind, myoxine::schema::TypeKind::Object);
+/// ```
+pub fn graphql_schema(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as schema::SchemaInput);
+    match schema::graphql_schema(input) {
+        Ok(t) => t.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+#[proc_macro]
+/// Checks a query (written directly as GraphQL, rather than in a string) and expands to the text
+/// of the query, along with any fragments it uses.
+/// ```ignore
+/// use macros::query;
+/// const USER_PAGE: &str = query! {
+///     #[schema = "schema.graphql"] // optional, but if provided the query is checked against it
+///     query UserPage($id: ID!) {
+///         user(id: $id) { ...UserFields }
+///     }
+///     fragment UserFields on User { id username }
+/// };
+/// ```
+/// Fragments which are shared between queries can be imported from other files with
+/// `#[fragments = "fragments/*.graphql"]` (a fragment can only be defined once across the query
+/// and all of the files).
+///
+/// GraphQL's `#` comments can't be used inside the macro (use `//` instead), unless the query is
+/// written as a string literal:
+/// ```ignore
+/// const USER_PAGE: &str = query!(
+///     #[schema = "schema.graphql"]
+///     "query UserPage($id: ID!) { user(id: $id) { id username } } # ..."
+/// );
+/// ```
 pub fn query(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     TokenStream::from(query_inner(input.into()))
 }
@@ -50,6 +237,6 @@ pub fn query(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
 ///     }
 /// }
 /// ```
-pub fn mutation(_input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    todo!()
+pub fn mutation(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    TokenStream::from(mutation::mutation_inner(input.into()))
 }
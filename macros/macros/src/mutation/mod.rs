@@ -3,5 +3,10 @@ use proc_macro2::TokenStream;
 /// The `mutation` macros.
 #[allow(dead_code)]
 pub fn mutation_inner(_input: TokenStream) -> TokenStream {
-    todo!()
+    syn::Error::new(
+        proc_macro2::Span::call_site(),
+        "`mutation!` isn't implemented yet; use `myoxine-build` to compile `.graphql` files for \
+        now.",
+    )
+    .to_compile_error()
 }
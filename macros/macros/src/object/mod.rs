@@ -6,255 +6,666 @@ A copy of the license can be found at the root of this Git repository.
 */
 
 //! Contains code with which one can derive the `Object` trait on an item.
+//!
+//! Objects are refetched in the same way as Relay does it – through the `node` field on the query
+//! type – so the schema needs a `Node` interface (`interface Node { id: ID! }`) which the object
+//! implements, and the field of the struct containing the id needs to be marked with `#[id]`.
+//!
+//! Types which don't implement `Node` (addresses, amounts of money and so on) have no identity of
+//! their own, so they can't be refetched or cached by themselves. These can derive `ValueObject`
+//! instead, which checks them against the schema in exactly the same way but only implements
+//! `Selection` (and serde's traits), so that they can be used as fields of other objects.
+//!
+//! Fields are matched up with the schema by name. If the names differ, a field can be given its
+//! GraphQL name with `#[graphql(rename = "createdAt")]`, or every field can be converted with
+//! `#[graphql(rename_all = "camelCase")]` on the struct.
+//!
+//! Objects are read from and written to the cache with their GraphQL names, so their `Serialize`
+//! and `Deserialize` implementations have to use the same names. By default these come from
+//! serde's own derives (which have to be given matching `#[serde(rename...)]` attributes – this is
+//! checked), but `#[graphql(serde)]` on the struct generates them instead, from the GraphQL names.
 
 use ast::ast::{
-    Definition, Document, GraphQLType, Name, ObjectTypeDefinition,
-    TypeDefinition, TypeSystemDefinition,
+    FieldDefinition, GraphQLType, Name, ObjectTypeDefinition, OperationType, TypeDefinition,
 };
+use codegen::case::{apply_rename_rule, RENAME_RULES};
+use codegen::types::type_matches;
+use syn::ext::IdentExt;
 
-use syn::export::Span;
-use syn::DeriveInput;
+use ast::suggest::suggestion;
+
+use crate::search::{describe, find_type_definition, load_schema, wrong_kind, Schema};
 
-const SCHEMA: &str = "schema";
+use syn::DeriveInput;
 
 /// Derives `Object` on the specified object. This function is probably going to take some
 /// refinement and anyone willing to act as a guinea pig for it would be appreciated.
 pub fn derive_object(input: syn::DeriveInput) -> Result<proc_macro2::TokenStream, syn::Error> {
-    let schema_location = input
-        .attrs
-        .iter()
-        .filter_map(|attribute| {
-            let original_attribute = attribute;
-            if let Ok(attribute) = attribute.parse_meta() {
-                if let syn::Meta::NameValue(name_value) = attribute {
-                    if name_value.path.is_ident(SCHEMA) {
-                        if let syn::Lit::Str(string) = name_value.lit {
-                            Some(Ok(string.value()))
-                        } else {
-                            Some(Err(syn::Error::new_spanned(
-                                original_attribute,
-                                "The type of `#[schema=<...>]` should be a string ",
-                            )))
-                        }
-                    } else {
-                        None
-                    }
-                } else {
-                    None
-                }
+    derive(input, true)
+}
+
+/// Derives `ValueObject` on the specified object.
+pub fn derive_value_object(
+    input: syn::DeriveInput,
+) -> Result<proc_macro2::TokenStream, syn::Error> {
+    derive(input, false)
+}
+
+/// Does the work for both `derive_object` and `derive_value_object` – the only difference between
+/// the two is that objects which are nodes also implement `Object`.
+fn derive(input: syn::DeriveInput, node: bool) -> Result<proc_macro2::TokenStream, syn::Error> {
+    let schema = load_schema(&input)?;
+    match find_type_definition(&schema, &input)? {
+        TypeDefinition::ObjectTypeDefinition(object) => {
+            let attributes = graphql_attributes(&input.attrs, &["rename_all"], &["serde"])?;
+            let fields = object_fields(&input, attributes.value("rename_all"))?;
+            check_type_def(&schema, object, &fields)?;
+            let object_impl = if node {
+                check_node_interface(&schema, object, &input)?;
+                output_struct(&schema, object, &fields, &input)?
             } else {
-                None
-            }
-        })
-        .next()
-        .unwrap()?;
-    let document = ast::parse_file(schema_location).map_err(|_| {
-        syn::Error::new_spanned(
-            input.ident.clone(),
-            "The provided schema could not be parsed. Please ensure that your schema is
-            valid, and if in doubt file a bug report at https://github.com/d3bate/myoxine.",
-        )
-    })?;
-    let relevant_type = document.get_type(&Name(input.ident.to_string()));
-    match relevant_type {
-        Some(def) => match def {
-            Definition::TypeSystemDefinition(def) => match def {
-                TypeSystemDefinition::TypeDefinition(def) => match def {
-                    ast::ast::TypeDefinition::ObjectTypeDefinition(object) => {
-                        Ok(output_struct(object, &input)?)
-                    }
-                    _ => panic!("Invalid type."),
-                },
-                _ => panic!(),
-            },
-            _ => Err(syn::Error::new_spanned(
-                input.ident.clone(),
-                "Encountered an internal error.",
-            )),
-        },
-        None => Err(syn::Error::new_spanned(
+                check_not_node(&schema, object, &input)?
+            };
+            let selection_impl = output_selection(&schema, object, &fields, &input)?;
+            let serde_impls = if attributes.flag("serde") {
+                codegen::object::output_impls(
+                    &input.ident,
+                    &object.name.0,
+                    &fields
+                        .iter()
+                        .map(|field| (field.ident.clone(), field.name.clone()))
+                        .collect::<Vec<_>>(),
+                )
+            } else {
+                check_serde_names(&input, &fields)?;
+                proc_macro2::TokenStream::new()
+            };
+            Ok(schema.tracked(quote::quote! {
+                #object_impl
+                #selection_impl
+                #serde_impls
+            }))
+        }
+        TypeDefinition::EnumTypeDefinition(def) => Err(syn::Error::new_spanned(
             input.ident.clone(),
-            "This type could not be found in the schema.",
+            format!(
+                "`{}` is an enum in the schema; use `#[derive(Enum)]` instead.{}",
+                input.ident,
+                schema.note(&def.name)
+            ),
         )),
+        other => Err(wrong_kind(&schema, &input, other, "an object")),
     }
 }
 
-/// Checks that the `Node` interface is defined on a type.
-#[allow(dead_code)]
-fn check_node_interface(document: &Document, _: &syn::Ident) -> Result<(), syn::Error> {
-    document
-        .0
+/// A field of the struct, along with the name it has in the schema.
+struct ObjectField<'a> {
+    field: &'a syn::Field,
+    ident: &'a syn::Ident,
+    name: String,
+}
+
+/// The contents of `#[graphql(...)]` attributes – `key = "value"` pairs and flags (such as `serde`).
+#[derive(Default)]
+struct GraphqlAttributes {
+    values: Vec<(String, syn::LitStr)>,
+    flags: Vec<String>,
+}
+
+impl GraphqlAttributes {
+    fn value(&self, key: &str) -> Option<&syn::LitStr> {
+        self.values
+            .iter()
+            .rev()
+            .find(|(other, _)| other == key)
+            .map(|(_, value)| value)
+    }
+
+    fn flag(&self, flag: &str) -> bool {
+        self.flags.iter().any(|other| other == flag)
+    }
+}
+
+/// Reads `#[graphql(...)]` attributes. Each of `keys` can be given a value (`key = "..."`) and each
+/// of `flags` can be given by itself; anything else is rejected.
+fn graphql_attributes(
+    attrs: &[syn::Attribute],
+    keys: &[&str],
+    flags: &[&str],
+) -> Result<GraphqlAttributes, syn::Error> {
+    let allowed = keys
         .iter()
-        .filter_map(|definition| match definition {
-            Definition::TypeSystemDefinition(def) => Some(def),
-            _ => None,
-        })
-        .filter_map(|type_def| match type_def {
-            TypeSystemDefinition::TypeDefinition(type_def) => Some(type_def),
+        .map(|key| format!("{} = \"...\"", key))
+        .chain(flags.iter().map(|flag| flag.to_string()))
+        .collect::<Vec<_>>();
+    let mut attributes = GraphqlAttributes::default();
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("graphql")) {
+        let list = match attr.parse_meta()? {
+            syn::Meta::List(list) => list,
+            other => {
+                return Err(syn::Error::new_spanned(
+                    other,
+                    format!("Expected something like `#[graphql({})]`.", allowed[0]),
+                ))
+            }
+        };
+        for nested in list.nested {
+            match nested {
+                syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                    path,
+                    lit: syn::Lit::Str(lit),
+                    ..
+                })) if keys.iter().any(|key| path.is_ident(key)) => {
+                    let key = path.get_ident().expect("keys are identifiers").to_string();
+                    attributes.values.push((key, lit));
+                }
+                syn::NestedMeta::Meta(syn::Meta::Path(path))
+                    if flags.iter().any(|flag| path.is_ident(flag)) =>
+                {
+                    let flag = path.get_ident().expect("flags are identifiers").to_string();
+                    attributes.flags.push(flag);
+                }
+                other => {
+                    let message = match allowed.as_slice() {
+                        [only] => format!("the only one which can be used here is `{}`", only),
+                        [rest @ .., last] => format!(
+                            "the ones which can be used here are `{}` and `{}`",
+                            rest.join("`, `"),
+                            last
+                        ),
+                        [] => "none can be used here".to_string(),
+                    };
+                    return Err(syn::Error::new_spanned(
+                        other,
+                        format!("Unknown attribute ({}).", message),
+                    ));
+                }
+            }
+        }
+    }
+    Ok(attributes)
+}
+
+/// Reads the value of `#[serde(key = "...")]`, if there is one. (Other forms of serde's attributes,
+/// such as `rename(serialize = "...")`, are ignored.)
+fn serde_attribute(attrs: &[syn::Attribute], key: &str) -> Option<String> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("serde"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(syn::Meta::List(list)) => Some(list.nested),
             _ => None,
         })
-        .filter_map(|type_def| match type_def {
-            TypeDefinition::InterfaceTypeDefinition(def) => Some(def),
+        .flatten()
+        .find_map(|nested| match nested {
+            syn::NestedMeta::Meta(syn::Meta::NameValue(syn::MetaNameValue {
+                path,
+                lit: syn::Lit::Str(lit),
+                ..
+            })) if path.is_ident(key) => Some(lit.value()),
             _ => None,
         })
-        .find(|interface| interface.name.0 == "Node".to_string())
-        .map(|item| {
-            if let Some(fields) = &item.fields_definition {
-                if fields.0.len() > 1 {
-                    Err(syn::Error::new(
-                        Span::call_site(),
-                        "Your `Node` interface has too many fields – it must have only \
-                            one and it must be called `ID`!",
-                    ))
-                } else if let Some(item) = fields.0.get(0) {
-                    if item.name.0 == "id".to_string()
-                        && (item.graphql_type.extract_name().0).0 == "ID".to_string()
-                        && (match item.graphql_type {
-                            GraphQLType::NonNullType(_) => true,
-                            _ => false,
-                        })
-                    {
-                        Ok(item)
-                    } else {
-                        Err(syn::Error::new(
-                            Span::call_site(),
-                            "Your `Node` interface's `id` field is in some way malformed.",
-                        ))
-                    }
-                } else {
-                    Err(syn::Error::new(
-                        Span::call_site(),
-                        "This error shouldn't actually have \
-                            happened; please do report it if it shows up :)",
-                    ))
+}
+
+/// Checks that serde's derives (which are used unless the struct has `#[graphql(serde)]`) will use
+/// the same names as the schema for every field.
+fn check_serde_names(input: &DeriveInput, fields: &[ObjectField]) -> Result<(), syn::Error> {
+    let rename_all = serde_attribute(&input.attrs, "rename_all");
+    for field in fields {
+        let unraw = field.ident.unraw().to_string();
+        let serde_name = match serde_attribute(&field.field.attrs, "rename") {
+            Some(name) => name,
+            None => rename_all
+                .as_ref()
+                .and_then(|rule| apply_rename_rule(rule, &unraw))
+                .unwrap_or(unraw),
+        };
+        if serde_name != field.name {
+            return Err(syn::Error::new_spanned(
+                field.field,
+                format!(
+                    "This field is called `{name}` in the schema, but serde calls it `{serde}`. Add \
+                    `#[serde(rename = \"{name}\")]` to it, or `#[graphql(serde)]` to the struct (so \
+                    that `Serialize` and `Deserialize` are implemented using the schema's names).",
+                    name = field.name,
+                    serde = serde_name
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Works out the GraphQL name of every field of the struct (taking `rename` and `rename_all` into
+/// account).
+fn object_fields<'a>(
+    input: &'a DeriveInput,
+    rename_all: Option<&syn::LitStr>,
+) -> Result<Vec<ObjectField<'a>>, syn::Error> {
+    let fields = match &input.data {
+        syn::Data::Struct(syn::DataStruct {
+            fields: syn::Fields::Named(fields),
+            ..
+        }) => fields,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                input.ident.clone(),
+                "`#[derive(Object)]` can only be used on structs with named fields.",
+            ))
+        }
+    };
+    if let Some(rule) = rename_all {
+        if !RENAME_RULES.contains(&rule.value().as_str()) {
+            return Err(syn::Error::new_spanned(
+                rule,
+                format!(
+                    "Unknown `rename_all` rule (the options are {}).",
+                    RENAME_RULES.join(", ")
+                ),
+            ));
+        }
+    }
+    fields
+        .named
+        .iter()
+        .map(|field| {
+            let ident = field.ident.as_ref().expect("named fields have names");
+            let unraw = ident.unraw().to_string();
+            let rename = graphql_attributes(&field.attrs, &["rename"], &[])?;
+            let name = match (rename.value("rename"), rename_all) {
+                (Some(name), _) => name.value(),
+                (None, Some(rule)) => {
+                    apply_rename_rule(&rule.value(), &unraw).expect("the rule was checked above")
                 }
-            } else {
-                Err(syn::Error::new(
-                    Span::call_site(),
-                    "Your `Node` interface doesn't have any \
-                        fields. It should have one field `id` of type `ID!`",
+                (None, None) => unraw,
+            };
+            Ok(ObjectField { field, ident, name })
+        })
+        .collect()
+}
+
+/// Finds the field in the schema which corresponds to a field of the struct.
+fn field_definition<'a>(
+    schema: &Schema,
+    type_def: &'a ObjectTypeDefinition,
+    field: &ObjectField,
+) -> Result<&'a FieldDefinition, syn::Error> {
+    let definitions = type_def
+        .fields_definition
+        .as_ref()
+        .map(|definitions| definitions.0.as_slice())
+        .unwrap_or_default();
+    definitions
+        .iter()
+        .find(|definition| definition.name.0 == field.name)
+        .ok_or_else(|| {
+            syn::Error::new_spanned(
+                field.ident,
+                format!(
+                    "The type `{}` has no field called `{}`.{}{}",
+                    type_def.name,
+                    field.name,
+                    suggestion(
+                        &field.name,
+                        definitions
+                            .iter()
+                            .map(|definition| definition.name.0.as_str())
+                    ),
+                    schema.note(&type_def.name)
+                ),
+            )
+        })
+}
+
+/// Checks that a field is `id: ID!`.
+fn is_id_field(field: &FieldDefinition) -> bool {
+    field.name.0 == "id"
+        && match &field.graphql_type {
+            GraphQLType::NonNullType(inner) => match &**inner {
+                GraphQLType::NamedType(named) => (named.0).0 == "ID",
+                _ => false,
+            },
+            _ => false,
+        }
+}
+
+/// Checks that the object really is a value object (i.e. that it doesn't implement `Node`), and
+/// implements `ValueObject` on it.
+fn check_not_node(
+    schema: &Schema,
+    object: &ObjectTypeDefinition,
+    input: &DeriveInput,
+) -> Result<proc_macro2::TokenStream, syn::Error> {
+    if object
+        .implements_interfaces
+        .iter()
+        .flat_map(|interfaces| interfaces.0.iter())
+        .any(|interface| (interface.0).0 == "Node")
+    {
+        return Err(syn::Error::new_spanned(
+            input.ident.clone(),
+            format!(
+                "`{}` implements `Node`, so it should derive `Object` (with an `#[id]` field) \
+                rather than `ValueObject`.{}",
+                input.ident,
+                schema.note(&object.name)
+            ),
+        ));
+    }
+    let ident = &input.ident;
+    Ok(quote::quote! {
+        impl ::myoxine::objects::ValueObject for #ident {}
+    })
+}
+
+/// Checks that the `Node` interface is defined in the schema (and is defined correctly), that the
+/// object implements it and that the query type has a `node(id: ID!): Node` field, through which
+/// the object can be refetched.
+fn check_node_interface(
+    schema: &Schema,
+    object: &ObjectTypeDefinition,
+    input: &DeriveInput,
+) -> Result<(), syn::Error> {
+    let error = |message: String| Err(syn::Error::new_spanned(input.ident.clone(), message));
+    let node =
+        match schema.get_type_definition(&Name("Node".to_string())) {
+            Some(TypeDefinition::InterfaceTypeDefinition(node)) => node,
+            Some(other) => {
+                return error(format!(
+                    "`Node` should be an interface, but it is {} in your schema.",
+                    describe(other)
                 ))
             }
-        });
-    todo!()
+            None => return error(
+                "Your schema doesn't have a `Node` interface. Myoxine uses it (in the same way as \
+                Relay does) to refetch objects, so it should be defined as \
+                `interface Node { id: ID! }`."
+                    .to_string(),
+            ),
+        };
+    match node
+        .fields_definition
+        .as_ref()
+        .map(|fields| fields.0.as_slice())
+        .unwrap_or_default()
+    {
+        [] => {
+            return error(
+                "Your `Node` interface doesn't have any fields. It should have one field `id` of \
+                type `ID!`."
+                    .to_string(),
+            )
+        }
+        [id] if !is_id_field(id) => {
+            return error(format!(
+                "Your `Node` interface's `id` field is in some way malformed (it is `{}`, but \
+                should be `id: ID!`).",
+                id
+            ))
+        }
+        [_] => {}
+        _ => {
+            return error(
+                "Your `Node` interface has too many fields – it must have only one and it must \
+                be called `id`!"
+                    .to_string(),
+            )
+        }
+    }
+    if !object
+        .implements_interfaces
+        .iter()
+        .flat_map(|interfaces| interfaces.0.iter())
+        .any(|interface| (interface.0).0 == "Node")
+    {
+        return error(format!(
+            "`{name}` must implement the `Node` interface (i.e. `type {name} implements Node`) so \
+            that it can be refetched. If `{name}` doesn't have an identity of its own, derive \
+            `ValueObject` instead.{note}",
+            name = object.name,
+            note = schema.note(&object.name)
+        ));
+    }
+    let query_type = schema.root_operation_type(OperationType::Query);
+    let node_field = query_type
+        .as_ref()
+        .and_then(|query| match schema.get_type_definition(query) {
+            Some(TypeDefinition::ObjectTypeDefinition(query)) => query.fields_definition.as_ref(),
+            _ => None,
+        })
+        .and_then(|fields| fields.0.iter().find(|field| field.name.0 == "node"));
+    let valid = match node_field {
+        Some(field) => {
+            let arguments = field
+                .arguments_definition
+                .as_ref()
+                .map(|arguments| arguments.0.as_slice())
+                .unwrap_or_default();
+            field.graphql_type.extract_name().0 .0 == "Node"
+                && arguments.iter().any(|argument| {
+                    argument.name.0 == "id" && argument.graphql_type.to_string() == "ID!"
+                })
+        }
+        None => false,
+    };
+    if !valid {
+        return error(format!(
+            "Your query type should have a field `node(id: ID!): Node`, which is used to refetch \
+            objects.{}",
+            query_type
+                .map(|query| schema.note(&query))
+                .unwrap_or_default()
+        ));
+    }
+    Ok(())
 }
 
+/// Implements `Object` on the struct. The refetch query asks for the object through the `node`
+/// field, with the selection set for whichever fields were chosen.
 fn output_struct(
+    schema: &Schema,
     type_def: &ObjectTypeDefinition,
+    fields: &[ObjectField],
     input: &DeriveInput,
 ) -> Result<proc_macro2::TokenStream, syn::Error> {
-    check_type_def(type_def, input)?;
-    let ident = input.ident.clone();
-    let id_field = match input.data.clone() {
-        syn::Data::Struct(s) => s
-            .fields
+    let mut id_fields = fields.iter().filter(|field| {
+        field
+            .field
+            .attrs
             .iter()
-            .filter(|field| {
-                field
-                    .attrs
-                    .iter()
-                    .filter(|attr| {
-                        let x = match attr.parse_meta() {
-                            Ok(m) => m,
-                            Err(_) => return false,
-                        };
-                        if let syn::Meta::Path(p) = x {
-                            p.is_ident("id")
-                        } else {
-                            return true;
-                        }
-                    })
-                    .next()
-                    .is_some()
-            })
-            .next()
-            .map(Clone::clone)
-            .expect("Missing ID field"),
-        _ => panic!("Not a struct."),
-    };
-    #[allow(unused_variables)]
-    let id_type = id_field.ty;
-    let id_path = id_field.ident;
-    let fields_type = quote::format_ident!("{}Fields", ident);
-    let input_fields = match &input.data {
-        syn::Data::Struct(data_struct) => match &data_struct.fields {
-            syn::Fields::Named(n) => n,
-            _ => panic!(),
-        },
-        _ => panic!(),
+            .any(|attr| attr.path.is_ident("id"))
+    });
+    let id_field = id_fields.next().ok_or_else(|| {
+        syn::Error::new_spanned(
+            input.ident.clone(),
+            "One of the fields of this struct should be marked with `#[id]` (it should be the \
+            `id` field from the `Node` interface). Types without an identity of their own should \
+            derive `ValueObject` instead.",
+        )
+    })?;
+    if let Some(other) = id_fields.next() {
+        return Err(syn::Error::new_spanned(
+            other.field,
+            "Only one field can be marked with `#[id]`.",
+        ));
+    }
+    if !is_id_field(field_definition(schema, type_def, id_field)?) {
+        return Err(syn::Error::new_spanned(
+            id_field.field,
+            "The field marked with `#[id]` should be the `id: ID!` field from the `Node` interface.",
+        ));
     }
-    .named
-    .iter()
-    .map(|item| item.ident.as_ref().unwrap())
-    .into_iter();
+    let ident = &input.ident;
+    let id_path = id_field.ident;
+    let query = format!(
+        "query($id: ID!) {{ node(id: $id) {{ ... on {} ",
+        type_def.name
+    );
+    let graphql_type = &type_def.name.0;
+    let field_names = fields
+        .iter()
+        .map(|field| field.name.as_str())
+        .collect::<Vec<_>>();
+    let cache_control = codegen::object::cache_control(type_def, &field_names)
+        .map_err(|error| {
+            syn::Error::new_spanned(
+                input.ident.clone(),
+                format!("{}{}", error, schema.note(&type_def.name)),
+            )
+        })?
+        .map(|cache_control| {
+            quote::quote! {
+                const CACHE_CONTROL: ::myoxine::cache::control::CacheControl = #cache_control;
+            }
+        });
     Ok(quote::quote! {
-        struct #fields_type {
-            #(#input_fields: bool),*
-        }
-        impl ::myoxine::Object for #ident {
-            type FieldsSelection = #fields_type;
-            fn id(&self) -> ::myoxine ::Id {
+        impl ::myoxine::objects::Object for #ident {
+            const GRAPHQL_TYPE: &'static str = #graphql_type;
+            #cache_control
+            fn id(&self) -> &::myoxine::Id {
                 &self.#id_path
             }
-            fn refetch_query(&self, fields: Self::FieldsSelection) -> ::myoxine::Query {
-                ::myoxine::Query::new(format!("node(id: ) {\
-                \
-                }", self.id()))
+            fn refetch_fields(
+                &self,
+                fields: &<Self as ::myoxine::selection::Selection>::Fields,
+            ) -> ::myoxine::query::Query<Self> {
+                let mut query = ::std::string::String::from(#query);
+                query.push_str(
+                    &<Self as ::myoxine::selection::Selection>::selection_set_for(fields)
+                );
+                query.push_str(" } }");
+                let document = ::myoxine::ast::parse_string(query)
+                    .expect("the generated refetch query should be valid (please report this)");
+                // the fields which aren't requested keep their current values
+                let base = ::myoxine::serde_json::to_value(self)
+                    .expect("objects can always be serialized");
+                ::myoxine::query::Query::new(document)
+                    .with_response_path(&["node"])
+                    .with_base(base)
+                    .with_variable("id", self.id())
+                    .expect("ids can always be serialized")
             }
         }
     })
 }
 
-/// Maps built-in GraphQL types into the corresponding Rust ones. Note that Myoxine is picky about
-/// which Rust types are valid for certain GraphQL types. These values have been chosen to match the
-/// specification and reduce the possibility of errors arising.
-///
-/// This is an extremely rudimentary function and will need to be fleshed out in the future.
-fn graphql2rust(input: &str) -> &str {
-    match input {
-        "Int" => "i32",
-        "ID" => "String",
-        _ => input,
+/// Implements `Selection` on the struct, along with the `<Type>Fields` struct which says which
+/// fields should be selected. Fields whose types are objects, interfaces or unions are given the
+/// selection set of the corresponding Rust type (so nested objects can be selected partially too).
+fn output_selection(
+    schema: &Schema,
+    type_def: &ObjectTypeDefinition,
+    fields: &[ObjectField],
+    input: &DeriveInput,
+) -> Result<proc_macro2::TokenStream, syn::Error> {
+    let mut field_idents = vec![];
+    let mut field_types = vec![];
+    let mut all = vec![];
+    let mut none = vec![];
+    let mut statements = vec![];
+    for field in fields {
+        let definition = field_definition(schema, type_def, field)?;
+        let ident = field.ident;
+        let name = format!(" {}", definition.name);
+        let ty = &field.field.ty;
+        field_idents.push(ident);
+        if schema
+            .possible_types(&definition.graphql_type.extract_name().0)
+            .is_some()
+        {
+            field_types.push(quote::quote! {
+                ::std::option::Option<<#ty as ::myoxine::selection::Selection>::Fields>
+            });
+            all.push(quote::quote! {
+                ::std::option::Option::Some(::myoxine::selection::FieldsSelection::all())
+            });
+            none.push(quote::quote! { ::std::option::Option::None });
+            statements.push(quote::quote! {
+                if let ::std::option::Option::Some(fields) = &fields.#ident {
+                    selection.push_str(#name);
+                    selection.push(' ');
+                    selection.push_str(
+                        &<#ty as ::myoxine::selection::Selection>::selection_set_for(fields)
+                    );
+                }
+            });
+        } else {
+            field_types.push(quote::quote! { bool });
+            all.push(quote::quote! { true });
+            none.push(quote::quote! { false });
+            statements.push(quote::quote! {
+                if fields.#ident {
+                    selection.push_str(#name);
+                }
+            });
+        }
     }
-}
-
-fn check_type_def(type_def: &ObjectTypeDefinition, input: &DeriveInput) -> Result<(), syn::Error> {
-    let graphql_type_fields = type_def.fields_definition.clone().unwrap().0;
-    match input.data.clone() {
-        syn::Data::Struct(data_struct) => {
-            for error in data_struct.fields.iter().map(|field| {
-                let identifier = field.ident.clone().unwrap().to_string();
-                if !match field.ty.clone() {
-                    syn::Type::Path(path) => path.path.is_ident(graphql2rust(
-                        &(graphql_type_fields
-                            .iter()
-                            .find(|field_definition| field_definition.name.0 == identifier)
-                            .unwrap()
-                            .graphql_type
-                            .extract_name()
-                            .0)
-                            .0
-                            .clone(),
-                    )),
-                    _ => panic!(),
-                } {
-                    Err(syn::Error::new_spanned(
-                        field.ty.clone(),
-                        "The type of this \
-                    field does not match that of the GraphQL schema you have provided.",
-                    ))
-                } else {
-                    Ok(())
+    let ident = &input.ident;
+    let vis = &input.vis;
+    let fields_ident = quote::format_ident!("{}Fields", ident);
+    let doc = format!(
+        " Says which fields of `{}` should be requested from the server (see \
+        `myoxine::selection::FieldsSelection`).",
+        ident
+    );
+    Ok(quote::quote! {
+        #[doc = #doc]
+        #[derive(Clone, Debug, PartialEq)]
+        #vis struct #fields_ident {
+            #(pub #field_idents: #field_types,)*
+        }
+        impl ::myoxine::selection::FieldsSelection for #fields_ident {
+            fn all() -> Self {
+                Self {
+                    #(#field_idents: #all,)*
                 }
-            }) {
-                if let Err(e) = error {
-                    return Err(e);
+            }
+            fn none() -> Self {
+                Self {
+                    #(#field_idents: #none,)*
                 }
             }
-            Ok(())
         }
-        _ => panic!("invalid type"),
+        impl ::std::default::Default for #fields_ident {
+            fn default() -> Self {
+                ::myoxine::selection::FieldsSelection::all()
+            }
+        }
+        impl ::myoxine::selection::Selection for #ident {
+            type Fields = #fields_ident;
+            fn selection_set_for(fields: &Self::Fields) -> ::std::string::String {
+                let mut selection = ::std::string::String::from("{ __typename");
+                #(#statements)*
+                selection.push_str(" }");
+                selection
+            }
+        }
+    })
+}
+
+/// Checks that every field of the struct exists on the GraphQL type, and that its Rust type can
+/// hold values of the field's GraphQL type.
+fn check_type_def(
+    schema: &Schema,
+    type_def: &ObjectTypeDefinition,
+    fields: &[ObjectField],
+) -> Result<(), syn::Error> {
+    for field in fields {
+        let definition = field_definition(schema, type_def, field)?;
+        if !type_matches(&definition.graphql_type, &field.field.ty) {
+            return Err(syn::Error::new_spanned(
+                field.field.ty.clone(),
+                format!(
+                    "The type of this field does not match that of the GraphQL schema you have \
+                    provided (where `{}` is `{}`).{}",
+                    definition.name,
+                    definition.graphql_type,
+                    schema.note(&type_def.name)
+                ),
+            ));
+        }
     }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -265,35 +676,446 @@ mod test_object_derive_macro {
     fn test_simple_object_derivation() {
         let input: syn::DeriveInput = syn::parse_str(
             r#"
-        #[derive(Query)]
+        #[derive(Object)]
         #[schema="schema.graphql"]
         struct User {
             #[id]
-            id: i32,
+            id: Id,
             username: String
         }
         "#,
         )
         .expect("failed to parse");
         let output = derive_object(input).expect("failed to derive");
-        assert!(
-            crate::tests::token_streams_are_equal
-                (output,
-                 "impl :: myoxine :: Object for User { type Id = i32 ; fn id ( & self ) -> & Self :: Id
-                { & self . id } fn refetch_query ( & self ) -> :: myoxine :: Query { todo ! ( ) } }"
-                     .parse::<proc_macro2::TokenStream>()
-                     .unwrap(),
-                )
+        assert!(crate::tests::token_streams_are_equal(
+            output,
+            r#"impl :: myoxine :: objects :: Object for User {
+                const GRAPHQL_TYPE : & 'static str = "User" ;
+                fn id ( & self ) -> & :: myoxine :: Id { & self . id }
+                fn refetch_fields (
+                    & self ,
+                    fields : & < Self as :: myoxine :: selection :: Selection > :: Fields ,
+                ) -> :: myoxine :: query :: Query < Self > {
+                    let mut query = :: std :: string :: String :: from (
+                        "query($id: ID!) { node(id: $id) { ... on User "
+                    ) ;
+                    query . push_str (
+                        & < Self as :: myoxine :: selection :: Selection > :: selection_set_for ( fields )
+                    ) ;
+                    query . push_str ( " } }" ) ;
+                    let document = :: myoxine :: ast :: parse_string ( query )
+                        . expect ( "the generated refetch query should be valid (please report this)" ) ;
+                    let base = :: myoxine :: serde_json :: to_value ( self )
+                        . expect ( "objects can always be serialized" ) ;
+                    :: myoxine :: query :: Query :: new ( document )
+                        . with_response_path ( & [ "node" ] )
+                        . with_base ( base )
+                        . with_variable ( "id" , self . id ( ) )
+                        . expect ( "ids can always be serialized" )
+                }
+            }
+            # [ doc = " Says which fields of `User` should be requested from the server (see `myoxine::selection::FieldsSelection`)." ]
+            # [ derive ( Clone , Debug , PartialEq ) ]
+            struct UserFields {
+                pub id : bool ,
+                pub username : bool ,
+            }
+            impl :: myoxine :: selection :: FieldsSelection for UserFields {
+                fn all ( ) -> Self { Self { id : true , username : true , } }
+                fn none ( ) -> Self { Self { id : false , username : false , } }
+            }
+            impl :: std :: default :: Default for UserFields {
+                fn default ( ) -> Self { :: myoxine :: selection :: FieldsSelection :: all ( ) }
+            }
+            impl :: myoxine :: selection :: Selection for User {
+                type Fields = UserFields ;
+                fn selection_set_for ( fields : & Self :: Fields ) -> :: std :: string :: String {
+                    let mut selection = :: std :: string :: String :: from ( "{ __typename" ) ;
+                    if fields . id { selection . push_str ( " id" ) ; }
+                    if fields . username { selection . push_str ( " username" ) ; }
+                    selection . push_str ( " }" ) ;
+                    selection
+                }
+            }"#
+            .parse::<proc_macro2::TokenStream>()
+            .unwrap(),
+        ));
+    }
+
+    #[test]
+    fn test_id_field_is_required() {
+        let derive = |input: &str| derive_object(syn::parse_str(input).expect("failed to parse"));
+        assert!(derive(
+            r#"
+        #[schema="schema.graphql"]
+        struct User { id: Id, username: String }
+        "#
+        )
+        .is_err());
+        assert!(derive(
+            r#"
+        #[schema="schema.graphql"]
+        struct User { id: Id, #[id] username: String }
+        "#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_node_interface_checks() {
+        let input: syn::DeriveInput =
+            syn::parse_str("struct User { #[id] id: Id }").expect("failed to parse");
+        let check = |schema: &str| {
+            let schema = Schema {
+                path: "schema.graphql".into(),
+                files: vec![ast::sources::SourceFile {
+                    path: "schema.graphql".into(),
+                    source: schema.to_string(),
+                }],
+                document: ast::parse_string(schema).expect("failed to parse schema"),
+            };
+            let object = match schema
+                .get_type_definition(&Name("User".to_string()))
+                .unwrap()
+            {
+                TypeDefinition::ObjectTypeDefinition(object) => object.clone(),
+                _ => unreachable!(),
+            };
+            check_node_interface(&schema, &object, &input).map_err(|e| e.to_string())
+        };
+        let query = "type Query { node(id: ID!): Node }";
+        assert_eq!(
+            check(&format!(
+                "{} interface Node {{ id: ID! }} type User implements Node {{ id: ID! }}",
+                query
+            )),
+            Ok(())
         );
+        // no `Node` interface at all
+        assert!(check(&format!("{} type User {{ id: ID! }}", query))
+            .unwrap_err()
+            .contains("doesn't have a `Node` interface"));
+        // a malformed one
+        assert!(check(&format!(
+            "{} interface Node {{ id: String }} type User implements Node {{ id: ID! }}",
+            query
+        ))
+        .unwrap_err()
+        .contains("malformed"));
+        // `User` doesn't implement it
+        assert!(check(&format!(
+            "{} interface Node {{ id: ID! }} type User {{ id: ID! }}",
+            query
+        ))
+        .unwrap_err()
+        .contains("must implement the `Node` interface"));
+        // there's no way to refetch things
+        assert!(check(
+            "type Query { user: User } interface Node { id: ID! } type User implements Node { id: ID! }"
+        )
+        .unwrap_err()
+        .contains("node(id: ID!): Node"));
     }
 
     #[test]
     fn test_more_complex_object_derivation() {
-        todo!()
+        let output = derive_object(
+            syn::parse_str(
+                r#"
+        #[schema="schema.graphql"]
+        pub struct Post {
+            #[id]
+            pub id: myoxine::Id,
+            pub title: String,
+        }
+        "#,
+            )
+            .expect("failed to parse"),
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+        .to_string();
+        // the fields struct has the same visibility as the object
+        assert!(output.contains("pub struct PostFields { pub id : bool , pub title : bool , }"));
+        assert!(output.contains("\"query($id: ID!) { node(id: $id) { ... on Post \""));
+        // fields which aren't in the schema, or have the wrong type, are rejected
+        assert!(derive_object(
+            syn::parse_str(
+                r#"
+        #[schema="schema.graphql"]
+        struct Post { #[id] id: Id, body: String }
+        "#,
+            )
+            .unwrap(),
+        )
+        .is_err());
+        assert!(derive_object(
+            syn::parse_str(
+                r#"
+        #[schema="schema.graphql"]
+        struct Post { #[id] id: Id, title: Option<String> }
+        "#,
+            )
+            .unwrap(),
+        )
+        .is_err());
     }
 
     #[test]
     fn test_derivation_with_other_objects() {
-        todo!()
+        let output = derive_object(
+            syn::parse_str(
+                r#"
+        #[schema="schema.graphql"]
+        struct Post {
+            #[id]
+            id: Id,
+            author: User,
+        }
+        "#,
+            )
+            .expect("failed to parse"),
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+        .to_string();
+        assert!(output.contains(
+            "pub author : :: std :: option :: Option << User as :: myoxine :: selection :: \
+            Selection > :: Fields > ,"
+        ));
+        assert!(output.contains(
+            "selection . push_str (& < User as :: myoxine :: selection :: Selection > :: \
+            selection_set_for (fields)) ;"
+        ));
+    }
+
+    #[test]
+    fn test_cache_control() {
+        let output = derive_object(
+            syn::parse_str(
+                r#"
+        #[schema="schema.graphql"]
+        struct Post {
+            #[id]
+            id: Id,
+            title: String,
+        }
+        "#,
+            )
+            .expect("failed to parse"),
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+        .to_string();
+        assert!(output.contains(
+            "const CACHE_CONTROL : :: myoxine :: cache :: control :: CacheControl = :: myoxine :: \
+            cache :: control :: CacheControl { hint : :: myoxine :: cache :: control :: CacheHint \
+            { max_age : :: std :: option :: Option :: Some (240u32) , scope : :: myoxine :: cache \
+            :: control :: CacheScope :: Public , } , fields : & [(\"title\" , :: myoxine :: cache \
+            :: control :: CacheHint { max_age : :: std :: option :: Option :: Some (30u32) , scope \
+            : :: myoxine :: cache :: control :: CacheScope :: Public , })] , } ;"
+        ));
+        // only the hints on fields which the struct has are included
+        let output = derive_object(
+            syn::parse_str(
+                r#"
+        #[schema="schema.graphql"]
+        struct User {
+            #[id]
+            id: Id,
+            username: String,
+        }
+        "#,
+            )
+            .expect("failed to parse"),
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+        .to_string();
+        assert!(!output.contains("CACHE_CONTROL"));
+    }
+
+    #[test]
+    fn test_renamed_fields() {
+        let output = derive_object(
+            syn::parse_str(
+                r#"
+        #[schema="schema.graphql"]
+        #[graphql(rename_all = "camelCase", serde)]
+        struct Post {
+            #[id]
+            id: Id,
+            #[graphql(rename = "title")]
+            heading: String,
+            published_at: Option<String>,
+        }
+        "#,
+            )
+            .expect("failed to parse"),
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+        .to_string();
+        // the GraphQL names are used in the selection set...
+        assert!(output.contains("if fields . heading { selection . push_str (\" title\") ; }"));
+        assert!(output
+            .contains("if fields . published_at { selection . push_str (\" publishedAt\") ; }"));
+        // ...and by serde
+        assert!(output.contains("impl :: myoxine :: serde :: Serialize for Post"));
+        assert!(output.contains("impl < 'de > :: myoxine :: serde :: Deserialize < 'de > for Post"));
+        assert!(output.contains("\"publishedAt\" , & self . published_at"));
+        assert!(output.contains("published_at : match map . remove (\"publishedAt\")"));
+    }
+
+    #[test]
+    fn test_serde_derives() {
+        let derive = |input: &str| {
+            derive_object(syn::parse_str(input).expect("failed to parse"))
+                .map_err(|e| e.to_string())
+        };
+        // without `#[graphql(serde)]` the user's own derives are used...
+        let output = derive(
+            r#"
+        #[schema="schema.graphql"]
+        #[derive(Serialize, Deserialize)]
+        #[graphql(rename_all = "camelCase")]
+        #[serde(rename_all = "camelCase")]
+        struct Post {
+            #[id]
+            id: Id,
+            #[graphql(rename = "title")]
+            #[serde(rename = "title")]
+            heading: String,
+            published_at: Option<String>,
+        }
+        "#,
+        )
+        .unwrap()
+        .to_string();
+        assert!(!output.contains("impl :: myoxine :: serde :: Serialize"));
+        assert!(!output.contains("impl < 'de > :: myoxine :: serde :: Deserialize"));
+        // ...so they have to agree with the schema on what the fields are called
+        let error = derive(
+            r#"
+        #[schema="schema.graphql"]
+        #[derive(Serialize, Deserialize)]
+        #[graphql(rename_all = "camelCase")]
+        struct Post { #[id] id: Id, published_at: Option<String> }
+        "#,
+        )
+        .unwrap_err();
+        assert!(error.starts_with(
+            "This field is called `publishedAt` in the schema, but serde calls it `published_at`."
+        ));
+        assert!(derive(
+            r#"
+        #[schema="schema.graphql"]
+        #[graphql(sered)]
+        struct Post { #[id] id: Id }
+        "#
+        )
+        .unwrap_err()
+        .starts_with(
+            "Unknown attribute (the ones which can be used here are `rename_all = \"...\"` and \
+            `serde`)."
+        ));
+    }
+
+    #[test]
+    fn test_invalid_renames() {
+        let derive = |input: &str| {
+            derive_object(syn::parse_str(input).expect("failed to parse"))
+                .map_err(|e| e.to_string())
+        };
+        // without `rename_all` the Rust name is used, which isn't in the schema
+        let error = derive(
+            r#"
+        #[schema="schema.graphql"]
+        struct Post { #[id] id: Id, published_at: Option<String> }
+        "#,
+        )
+        .unwrap_err();
+        assert!(error.starts_with(
+            "The type `Post` has no field called `published_at`. Did you mean `publishedAt`?"
+        ));
+        assert!(error.contains("`Post` is defined at schema.graphql:"));
+        assert!(derive(
+            r#"
+        #[schema="schema.graphql"]
+        #[graphql(rename_all = "kebab-case")]
+        struct Post { #[id] id: Id }
+        "#
+        )
+        .unwrap_err()
+        .starts_with("Unknown `rename_all` rule"));
+        assert!(derive(
+            r#"
+        #[schema="schema.graphql"]
+        struct Post { #[id] id: Id, #[graphql(name = "title")] heading: String }
+        "#
+        )
+        .unwrap_err()
+        .starts_with("Unknown attribute"));
+    }
+
+    #[test]
+    fn test_value_object_derivation() {
+        let output = derive_value_object(
+            syn::parse_str(
+                r#"
+        #[schema="schema.graphql"]
+        struct Address {
+            street: String,
+            city: String,
+            location: Option<GeoPoint>,
+        }
+        "#,
+            )
+            .expect("failed to parse"),
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+        .to_string();
+        assert!(output.starts_with("impl :: myoxine :: objects :: ValueObject for Address { }"));
+        assert!(!output.contains(":: myoxine :: objects :: Object for"));
+        // nested value objects are selected like any other object
+        assert!(output.contains(
+            "pub location : :: std :: option :: Option << Option < GeoPoint > as :: myoxine :: \
+            selection :: Selection > :: Fields > ,"
+        ));
+        // value objects are type-checked in the same way as objects
+        assert!(derive_value_object(
+            syn::parse_str(
+                r#"
+        #[schema="schema.graphql"]
+        struct Address { street: i32 }
+        "#,
+            )
+            .unwrap(),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_nodes_cannot_be_value_objects() {
+        assert!(derive_value_object(
+            syn::parse_str(
+                r#"
+        #[schema="schema.graphql"]
+        struct User { id: Id, username: String }
+        "#,
+            )
+            .unwrap(),
+        )
+        .unwrap_err()
+        .to_string()
+        .contains("should derive `Object`"));
+        // and value objects can't derive `Object`
+        assert!(derive_object(
+            syn::parse_str(
+                r#"
+        #[schema="schema.graphql"]
+        struct Address { street: String }
+        "#,
+            )
+            .unwrap(),
+        )
+        .unwrap_err()
+        .to_string()
+        .contains("derive `ValueObject` instead"));
     }
 }
@@ -0,0 +1,329 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+
+//! Contains code with which one can derive `Union` or `Interface` on a Rust enum corresponding to a
+//! GraphQL union or interface.
+//!
+//! Each variant is named after one of the object types which the union/interface could be, and
+//! contains the Rust type for that object (e.g. `User(User)`). There must also be an
+//! `Unknown(String)` variant, which is used for any types we don't have a variant for (including
+//! types added to the schema after the code was compiled) and stores the `__typename` of the value.
+//!
+//! `__typename` is always requested from the server (see the generated `Selection`
+//! implementation) and deserialization dispatches on it.
+
+use std::collections::HashSet;
+
+use ast::ast::TypeDefinition;
+use ast::suggest::suggestion;
+use quote::quote;
+
+use crate::search::{describe, find_type_definition, load_schema, Schema};
+
+/// The name of the variant used for types which don't have a variant of their own.
+const FALLBACK_VARIANT: &str = "Unknown";
+
+/// Which kind of type we are deriving on.
+#[derive(Copy, Clone)]
+pub enum Kind {
+    Union,
+    Interface,
+}
+
+impl Kind {
+    fn describe(self) -> &'static str {
+        match self {
+            Kind::Union => "a union",
+            Kind::Interface => "an interface",
+        }
+    }
+}
+
+/// Derives `Union` or `Interface` (depending on `kind`) on the specified enum.
+pub fn derive_polymorphic(
+    input: syn::DeriveInput,
+    kind: Kind,
+) -> Result<proc_macro2::TokenStream, syn::Error> {
+    let data = match &input.data {
+        syn::Data::Enum(data) => data,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                input.ident.clone(),
+                "GraphQL unions and interfaces can only be derived on enums.",
+            ))
+        }
+    };
+    let schema = load_schema(&input)?;
+    let type_def = find_type_definition(&schema, &input)?;
+    match (kind, type_def) {
+        (Kind::Union, TypeDefinition::UnionTypeDefinition(_))
+        | (Kind::Interface, TypeDefinition::InterfaceTypeDefinition(_)) => {
+            output_enum(&schema, type_def, data, &input).map(|output| schema.tracked(output))
+        }
+        (_, other) => Err(syn::Error::new_spanned(
+            input.ident.clone(),
+            format!(
+                "`{}` is {} in the schema, not {}.{}",
+                input.ident,
+                describe(other),
+                kind.describe(),
+                schema.note(other.name())
+            ),
+        )),
+    }
+}
+
+/// Returns the type contained in a variant of the form `Name(Type)`.
+fn variant_type(variant: &syn::Variant) -> Option<&syn::Type> {
+    match &variant.fields {
+        syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => Some(&fields.unnamed[0].ty),
+        _ => None,
+    }
+}
+
+/// Checks that a variant is the `Unknown(String)` fallback variant.
+fn is_fallback(variant: &syn::Variant) -> bool {
+    variant.ident == FALLBACK_VARIANT
+        && match variant_type(variant) {
+            Some(syn::Type::Path(path)) => path.path.is_ident("String"),
+            _ => false,
+        }
+}
+
+fn output_enum(
+    schema: &Schema,
+    type_def: &TypeDefinition,
+    data: &syn::DataEnum,
+    input: &syn::DeriveInput,
+) -> Result<proc_macro2::TokenStream, syn::Error> {
+    let possible_types = schema
+        .possible_types(type_def.name())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|name| name.0.clone())
+        .collect::<Vec<_>>();
+
+    let mut variants = vec![];
+    let mut seen = HashSet::new();
+    let mut has_fallback = false;
+    for variant in &data.variants {
+        if is_fallback(variant) {
+            has_fallback = true;
+            continue;
+        }
+        let ty = variant_type(variant).ok_or_else(|| {
+            syn::Error::new_spanned(
+                variant,
+                "Variants should contain exactly one item – the object they correspond to (e.g. \
+                `User(User)`).",
+            )
+        })?;
+        let name = variant.ident.to_string();
+        if !possible_types.contains(&name) {
+            return Err(syn::Error::new_spanned(
+                &variant.ident,
+                format!(
+                    "`{}` is not one of the possible types of `{}` (which are: {}).{}{}",
+                    name,
+                    type_def.name(),
+                    possible_types.join(", "),
+                    suggestion(&name, possible_types.iter().map(String::as_str)),
+                    schema.note(type_def.name())
+                ),
+            ));
+        }
+        if !seen.insert(name.clone()) {
+            return Err(syn::Error::new_spanned(
+                &variant.ident,
+                format!("More than one variant corresponds to `{}`.", name),
+            ));
+        }
+        variants.push((variant.ident.clone(), ty.clone(), name));
+    }
+    if !has_fallback {
+        return Err(syn::Error::new_spanned(
+            input.ident.clone(),
+            "GraphQL unions and interfaces must have an `Unknown(String)` variant. It is used for \
+            types which don't have a variant of their own (including ones added to the schema \
+            after your code was compiled).",
+        ));
+    }
+
+    let ident = &input.ident;
+    let fallback = quote::format_ident!("{}", FALLBACK_VARIANT);
+    let variant_idents = variants
+        .iter()
+        .map(|(ident, _, _)| ident)
+        .collect::<Vec<_>>();
+    let variant_types = variants.iter().map(|(_, ty, _)| ty).collect::<Vec<_>>();
+    let typenames = variants.iter().map(|(_, _, name)| name).collect::<Vec<_>>();
+    let fragments = typenames
+        .iter()
+        .map(|name| format!(" ... on {} ", name))
+        .collect::<Vec<_>>();
+    Ok(quote! {
+        impl #ident {
+            /// Returns the `__typename` of this value.
+            pub fn typename(&self) -> &str {
+                match self {
+                    #(Self::#variant_idents(_) => #typenames,)*
+                    Self::#fallback(typename) => typename.as_str(),
+                }
+            }
+        }
+        impl ::myoxine::selection::Selection for #ident {
+            // the members of a union (or interface) are always requested in full
+            type Fields = ();
+            fn selection_set_for(_: &()) -> ::std::string::String {
+                let mut selection = ::std::string::String::from("{ __typename");
+                #(
+                    selection.push_str(#fragments);
+                    selection.push_str(
+                        &<#variant_types as ::myoxine::selection::Selection>::selection_set()
+                    );
+                )*
+                selection.push_str(" }");
+                selection
+            }
+        }
+        impl ::myoxine::serde::Serialize for #ident {
+            fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+            where
+                S: ::myoxine::serde::Serializer,
+            {
+                let mut value = match self {
+                    #(Self::#variant_idents(inner) => ::myoxine::serde_json::to_value(inner)
+                        .map_err(<S::Error as ::myoxine::serde::ser::Error>::custom)?,)*
+                    Self::#fallback(_) => ::myoxine::serde_json::Value::Object(
+                        ::myoxine::serde_json::Map::new()
+                    ),
+                };
+                // the inner types don't know their own `__typename`, so we add it back here
+                if let ::myoxine::serde_json::Value::Object(object) = &mut value {
+                    object.insert(
+                        "__typename".to_string(),
+                        ::myoxine::serde_json::Value::String(self.typename().to_string()),
+                    );
+                }
+                ::myoxine::serde::Serialize::serialize(&value, serializer)
+            }
+        }
+        impl<'de> ::myoxine::serde::Deserialize<'de> for #ident {
+            fn deserialize<D>(deserializer: D) -> ::std::result::Result<Self, D::Error>
+            where
+                D: ::myoxine::serde::Deserializer<'de>,
+            {
+                let value =
+                    <::myoxine::serde_json::Value as ::myoxine::serde::Deserialize>::deserialize(
+                        deserializer,
+                    )?;
+                let typename = value
+                    .get("__typename")
+                    .and_then(|typename| typename.as_str())
+                    .ok_or_else(|| {
+                        <D::Error as ::myoxine::serde::de::Error>::missing_field("__typename")
+                    })?
+                    .to_string();
+                match typename.as_str() {
+                    #(#typenames => ::myoxine::serde_json::from_value(value)
+                        .map(Self::#variant_idents)
+                        .map_err(<D::Error as ::myoxine::serde::de::Error>::custom),)*
+                    _ => Ok(Self::#fallback(typename)),
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test_polymorphic_derive_macro {
+    use super::*;
+
+    fn derive(input: &str, kind: Kind) -> Result<proc_macro2::TokenStream, syn::Error> {
+        derive_polymorphic(syn::parse_str(input).expect("failed to parse"), kind)
+    }
+
+    #[test]
+    fn test_union_derivation() {
+        let output = derive(
+            r#"
+        #[derive(Union)]
+        #[schema="schema.graphql"]
+        enum SearchResult {
+            User(User),
+            Post(Post),
+            Unknown(String)
+        }
+        "#,
+            Kind::Union,
+        )
+        .unwrap_or_else(|e| panic!("{}", e))
+        .to_string();
+        assert!(output.contains("\" ... on User \""));
+        assert!(output.contains("\"Post\" => :: myoxine :: serde_json :: from_value (value)"));
+        assert!(output.contains("_ => Ok (Self :: Unknown (typename))"));
+    }
+
+    #[test]
+    fn test_interface_derivation() {
+        derive(
+            r#"
+        #[schema="schema.graphql"]
+        enum Content {
+            Post(Post),
+            Unknown(String)
+        }
+        "#,
+            Kind::Interface,
+        )
+        .unwrap_or_else(|e| panic!("{}", e));
+        // `User` doesn't implement `Content`
+        assert!(derive(
+            r#"
+        #[schema="schema.graphql"]
+        enum Content {
+            User(User),
+            Unknown(String)
+        }
+        "#,
+            Kind::Interface,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_polymorphic_derivation_requires_fallback() {
+        assert!(derive(
+            r#"
+        #[schema="schema.graphql"]
+        enum SearchResult { User(User), Post(Post) }
+        "#,
+            Kind::Union,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_polymorphic_derivation_on_wrong_kind() {
+        assert!(derive(
+            r#"
+        #[schema="schema.graphql"]
+        enum SearchResult { User(User), Unknown(String) }
+        "#,
+            Kind::Interface,
+        )
+        .is_err());
+        assert!(derive(
+            r#"
+        #[schema="schema.graphql"]
+        enum Role { Unknown(String) }
+        "#,
+            Kind::Union,
+        )
+        .is_err());
+    }
+}
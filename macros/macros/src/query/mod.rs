@@ -1,10 +1,413 @@
-mod query;
+//! The `query!` macro, which checks a query (written as GraphQL, directly inside the macro) and
+//! turns it into the text which is sent to the server.
+//!
+//! ```ignore
+//! const USER_PAGE: &str = query! {
+//!     #[schema = "schema.graphql"]
+//!     query UserPage($id: ID!) {
+//!         user(id: $id) { ...UserFields }
+//!     }
+//!     fragment UserFields on User { id username }
+//! };
+//! ```
+//!
+//! Because the query is made of Rust tokens (rather than being a string), every mistake in it can
+//! be reported at the exact place in the source code where it was made.
+//!
+//! The query can also be written as a string literal, which is handy for queries which have been
+//! copied from elsewhere (and is the only way of using GraphQL's `#` comments):
+//!
+//! ```ignore
+//! const USER_PAGE: &str = query!(
+//!     #[schema = "schema.graphql"]
+//!     "query UserPage($id: ID!) { user(id: $id) { id username } }"
+//! );
+//! ```
+//!
+//! Mistakes in a string literal are pointed at as precisely as the compiler allows (see `spans`).
+//!
+//! Fragments which are shared between several queries can be kept in `.graphql` files and
+//! imported with `#[fragments = "fragments/*.graphql"]` (or `#[fragments("a.graphql", ...)]`).
 
-use proc_macro2::TokenStream;
+mod spans;
+
+use std::ops::Range;
+
+use ast::ast::{Document, OperationType};
+use ast::sources::{self, SourceFile};
+use ast::tokens::{parse_tokens, TokenSource};
+use proc_macro2::{Span, TokenStream};
+use syn::parse::{Parse, ParseStream};
+
+use crate::search::{
+    attribute_paths, parse_schema, read_files, source_error, track, DEFAULT_SCHEMA, SCHEMA,
+};
+
+/// The name of the attribute used to import fragments from other files.
+const FRAGMENTS: &str = "fragments";
+
+/// The input to `query!` – optional `#[schema = "..."]` and `#[fragments = "..."]` attributes
+/// followed by some GraphQL. If there's no schema attribute the default schema (see
+/// `search::DEFAULT_SCHEMA`) is used, if there is one.
+pub struct QueryInput {
+    /// The paths to the schema, along with the attribute they came from.
+    schema: Option<(Vec<String>, syn::Attribute)>,
+    fragments: Option<(Vec<String>, syn::Attribute)>,
+    graphql: TokenStream,
+}
+
+impl Parse for QueryInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut schema = None;
+        let mut fragments = None;
+        for attribute in input.call(syn::Attribute::parse_outer)? {
+            if let Some(paths) = attribute_paths(&attribute, SCHEMA) {
+                schema = Some((paths?, attribute));
+            } else if let Some(paths) = attribute_paths(&attribute, FRAGMENTS) {
+                fragments = Some((paths?, attribute));
+            } else {
+                return Err(syn::Error::new_spanned(
+                    attribute,
+                    "The only attributes `query!` supports are `#[schema = \"...\"]` and \
+                    `#[fragments = \"...\"]`.",
+                ));
+            }
+        }
+        Ok(Self {
+            schema,
+            fragments,
+            graphql: input.parse()?,
+        })
+    }
+}
+
+/// Where the text of a query came from, which decides how mistakes in it are pointed at.
+enum QuerySource {
+    /// GraphQL written directly inside the macro.
+    Tokens(TokenSource),
+    /// A string literal containing the query.
+    Literal(syn::LitStr),
+}
+
+impl QuerySource {
+    /// An error about the part of the query given by `range` (in bytes).
+    fn error(&self, range: Option<Range<usize>>, message: &str) -> syn::Error {
+        match (self, range) {
+            (Self::Tokens(source), Some(range)) => syn::Error::new(source.span(range), message),
+            (Self::Tokens(_), None) => syn::Error::new(Span::call_site(), message),
+            (Self::Literal(literal), range) => spans::query_error(literal, range, message),
+        }
+    }
+}
 
 /// The query macros. Note that this is named `query_inner` because it takes types from the
 /// `proc_macro2` crate rather than the `proc_macro` crate. This is useful for testing the macros.
-#[allow(dead_code)]
-pub fn query_inner(_input: TokenStream) -> TokenStream {
-    todo!()
+pub fn query_inner(input: TokenStream) -> TokenStream {
+    match syn::parse2(input).and_then(query) {
+        Ok(output) => output,
+        Err(e) => {
+            // the macro is used as an expression, so the errors go in a block (which would
+            // evaluate to a string, if it weren't for the errors)
+            let errors = e.to_compile_error();
+            quote::quote! {{ #errors "" }}
+        }
+    }
+}
+
+fn query(input: QueryInput) -> Result<TokenStream, syn::Error> {
+    if input.graphql.is_empty() {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "`query!` needs a query (e.g. `query! { query UserPage { ... } }`).",
+        ));
+    }
+    let (document, source) = match syn::parse2::<syn::LitStr>(input.graphql.clone()) {
+        Ok(literal) => {
+            let document = ast::parse_string(literal.value())
+                .map_err(|error| spans::parse_error(&literal, &error))?;
+            (document, QuerySource::Literal(literal))
+        }
+        Err(_) => {
+            let (document, source) = parse_tokens(input.graphql)
+                .map_err(|error| syn::Error::new(error.span, error.message))?;
+            (document, QuerySource::Tokens(source))
+        }
+    };
+    let schema = match &input.schema {
+        Some((paths, attribute)) => Some(parse_schema(paths, attribute)?),
+        None => match std::env::var(DEFAULT_SCHEMA) {
+            Ok(path) => Some(parse_schema(&[path], TokenStream::new())?),
+            Err(_) => None,
+        },
+    };
+    let (fragment_files, imported) = match &input.fragments {
+        Some((paths, attribute)) => import_fragments(
+            paths,
+            attribute,
+            schema.as_deref().map(|schema| &schema.document),
+        )?,
+        None => (vec![], Document::default()),
+    };
+    let imported = imported.fragments().collect::<Vec<_>>();
+    if let Some(schema) = &schema {
+        check(
+            &source,
+            ast::validate::validate_with_fragments(schema, &document, &imported),
+        )?;
+    }
+
+    let operations = document.operations().collect::<Vec<_>>();
+    let operation = match operations.as_slice() {
+        [operation] => operation,
+        _ => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                format!(
+                    "`query!` should contain exactly one operation (along with any fragments it \
+                    uses), but this contains {}.",
+                    operations.len()
+                ),
+            ))
+        }
+    };
+    if operation.operation_type.token != OperationType::Query {
+        return Err(source.error(
+            Some(operation.operation_type.range()),
+            &format!(
+                "Only queries can be used with `query!` (this is a {}).",
+                operation.operation_type.token
+            ),
+        ));
+    }
+    let fragments = document
+        .fragments()
+        .chain(imported.iter().copied())
+        .collect::<Vec<_>>();
+    let text = codegen::query::operation_text(operation, &fragments);
+    let track_schema = schema.map(|schema| schema.track());
+    let track_fragments = track(&fragment_files);
+    Ok(quote::quote! {{
+        #track_schema
+        #track_fragments
+        #text
+    }})
+}
+
+/// Reads the fragments in the files matched by `paths`. Each file is checked against the schema
+/// (if there is one) on its own, and a fragment can only be defined in one of them.
+fn import_fragments(
+    paths: &[String],
+    attribute: &syn::Attribute,
+    schema: Option<&Document>,
+) -> Result<(Vec<SourceFile>, Document), syn::Error> {
+    let what = "the fragments";
+    let files = read_files(paths, attribute, what)?;
+    let documents = sources::parse_each(&files, |source| ast::parse_string(source))
+        .map_err(|error| source_error(error, attribute, what))?;
+    let mut imported = Document::default();
+    for (file, document) in files.iter().zip(documents) {
+        if document.operations().next().is_some() {
+            return Err(syn::Error::new_spanned(
+                attribute,
+                format!(
+                    "`{}` should only contain fragments, but it contains an operation.",
+                    file.path.display()
+                ),
+            ));
+        }
+        if let Some(schema) = schema {
+            let errors = ast::validate::validate(schema, &document);
+            if let Some(error) = errors.into_iter().next() {
+                let location = match error.span {
+                    Some(range) => {
+                        let (line, column) = ast::line_col(&file.source, range.start);
+                        format!("{}:{}:{}", file.path.display(), line, column)
+                    }
+                    None => file.path.display().to_string(),
+                };
+                return Err(syn::Error::new_spanned(
+                    attribute,
+                    format!("{} (in {})", error.message, location),
+                ));
+            }
+        }
+        imported.0.extend(document.0);
+    }
+    Ok((files, imported))
+}
+
+/// Turns validation errors into a single `syn::Error`, pointing at the parts of the query which
+/// they are about.
+fn check(
+    source: &QuerySource,
+    errors: Vec<ast::validate::ValidationError>,
+) -> Result<(), syn::Error> {
+    let mut combined: Option<syn::Error> = None;
+    for error in errors {
+        let error = source.error(error.span, &error.message);
+        match &mut combined {
+            Some(combined) => combined.combine(error),
+            None => combined = Some(error),
+        }
+    }
+    match combined {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test_query_macro {
+    use super::*;
+
+    fn expand(input: &str) -> Result<String, syn::Error> {
+        query(syn::parse_str(input).expect("failed to parse")).map(|output| output.to_string())
+    }
+
+    #[test]
+    fn test_query_macro() {
+        let output = expand(
+            r#"
+            #[schema = "schema.graphql"]
+            query UserPage($id: ID!) {
+                user(id: $id) { ...UserFields }
+            }
+            fragment UserFields on User { id username }
+            fragment Unused on User { id }
+            "#,
+        )
+        .unwrap_or_else(|e| panic!("{}", e));
+        assert!(output.starts_with("{ const _ : & [u8] = :: std :: include_bytes !"));
+        assert!(output.contains("\"query UserPage"));
+        assert!(output.contains("fragment UserFields on User"));
+        assert!(!output.contains("Unused"));
+    }
+
+    #[test]
+    fn test_errors_point_at_tokens() {
+        let error = expand(
+            r#"
+            #[schema = "schema.graphql"]
+            query UserPage($id: ID!) {
+                user(id: $id) { usrname }
+            }
+            "#,
+        )
+        .unwrap_err();
+        assert!(error.to_string().starts_with(
+            "The type `User` has no field called `usrname`. Did you mean `username`?"
+        ));
+        let start = error.span().start();
+        assert_eq!((start.line, start.column), (4, 32));
+        // mistakes in the syntax are pointed at too
+        let error = expand("query { user(id: 1) { id } } 42").unwrap_err();
+        assert_eq!(error.span().start().column, 29);
+    }
+
+    #[test]
+    fn test_imported_fragments() {
+        let output = expand(
+            r#"
+            #[schema = "schema.graphql"]
+            #[fragments = "fixtures/fragments/*.graphql"]
+            query UserPage($id: ID!) {
+                user(id: $id) { ...UserFields }
+            }
+            "#,
+        )
+        .unwrap_or_else(|e| panic!("{}", e));
+        assert!(output.contains("fragment UserFields on User"));
+        assert!(output.contains("fixtures/fragments/users.graphql"));
+        // fragments can't be defined both in the query and in an imported file
+        let error = expand(
+            r#"
+            #[schema = "schema.graphql"]
+            #[fragments = "fixtures/fragments/users.graphql"]
+            query UserPage($id: ID!) {
+                user(id: $id) { ...UserFields }
+            }
+            fragment UserFields on User { id }
+            "#,
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("is also imported"));
+        // and the imported files are checked against the schema too
+        let error = expand(
+            r#"
+            #[schema = "schema.graphql"]
+            #[fragments = "fixtures/invalid/*.graphql"]
+            query UserPage($id: ID!) {
+                user(id: $id) { ...UserFields }
+            }
+            "#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The type `User` has no field called `usrname`. Did you mean `username`? (in \
+            fixtures/invalid/users.graphql:3:5)"
+        );
+    }
+
+    #[test]
+    fn test_string_literals() {
+        let output = expand(
+            r#"
+            #[schema = "schema.graphql"]
+            "query UserPage($id: ID!) { user(id: $id) { id username } }"
+            "#,
+        )
+        .unwrap_or_else(|e| panic!("{}", e));
+        assert!(output.contains("\"query UserPage"));
+        // (`#` comments can only be used in strings)
+        expand("\"{ anything # a comment\n }\"").unwrap_or_else(|e| panic!("{}", e));
+    }
+
+    #[test]
+    fn test_errors_point_into_string_literals() {
+        let error = expand(
+            r#"
+            #[schema = "schema.graphql"]
+            "query($id: ID!) { user(id: $id) { usrname } }"
+            "#,
+        )
+        .unwrap_err();
+        assert!(error.to_string().starts_with(
+            "The type `User` has no field called `usrname`. Did you mean `username`?"
+        ));
+        // the literal starts at column 12 (counting from zero) and `usrname` is 34 bytes into the
+        // query, after the opening quote
+        let (start, end) = (error.span().start(), error.span().end());
+        assert_eq!((start.line, start.column), (3, 12 + 1 + 34));
+        assert_eq!(end.column, start.column + "usrname".len());
+    }
+
+    #[test]
+    fn test_every_mistake_in_a_string_literal_is_reported() {
+        let errors = expand(
+            r#"
+            #[schema = "schema.graphql"]
+            "{ user(id: \"1\") { usrname } search(trm: \"x\") { __typename } }"
+            "#,
+        )
+        .unwrap_err()
+        .into_iter()
+        .map(|error| error.to_string())
+        .collect::<Vec<_>>();
+        assert_eq!(errors.len(), 3);
+        // the literal has escapes in it, so this falls back to including the location
+        assert!(errors[0].ends_with("(at line 1, column 19 of the query)"));
+        let error = expand("\"query { user(id: 1) { id }\"").unwrap_err();
+        assert!(error.to_string().starts_with("Couldn't parse this query:"));
+        let error = expand("\"mutation { __typename }\"").unwrap_err();
+        assert_eq!(error.span().start().column, 1);
+    }
+
+    #[test]
+    fn test_only_one_query() {
+        assert!(expand("query A { __typename } query B { __typename }").is_err());
+        assert!(expand("mutation A { __typename }").is_err());
+        // queries don't have to be checked against a schema
+        expand("{ anything }").unwrap_or_else(|e| panic!("{}", e));
+    }
 }
@@ -0,0 +1,108 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+
+//! Turns the locations of mistakes in a query (which are byte offsets into the text of the query)
+//! into errors which point at the right part of the Rust source code.
+//!
+//! When the query is written in a string literal (`query!("...")`) we try to point at the exact
+//! characters inside the literal. This relies on `Literal::subspan`, which is only available on
+//! nightly compilers at the moment; everywhere else we point at the whole literal and add the line
+//! and column of the mistake to the message, so that it's still easy to find.
+
+use std::ops::Range;
+
+use pest::error::InputLocation;
+
+/// Returns the span of `range` (a range of bytes in the value of `literal`), if the compiler can
+/// give us one.
+pub fn literal_subspan(literal: &syn::LitStr, range: Range<usize>) -> Option<proc_macro2::Span> {
+    let token = literal.token();
+    let source = token.to_string();
+    let offset = match source.strip_prefix('r') {
+        // `r#"` – the `r`, any number of hashes and then a quote
+        Some(raw) => raw.find('"')? + 2,
+        None => {
+            // if the string contains escapes then offsets into its value don't line up with offsets
+            // into the source code
+            if source.get(1..source.len().saturating_sub(1))? != literal.value() {
+                return None;
+            }
+            1
+        }
+    };
+    token.subspan(range.start + offset..range.end + offset)
+}
+
+/// Creates an error about the part of the query in `literal` given by `range`.
+pub fn query_error(
+    literal: &syn::LitStr,
+    range: Option<Range<usize>>,
+    message: &str,
+) -> syn::Error {
+    let range = match range {
+        Some(range) => range,
+        None => return syn::Error::new_spanned(literal, message),
+    };
+    match literal_subspan(literal, range.clone()) {
+        Some(span) => syn::Error::new(span, message),
+        None => {
+            let (line, column) = ast::line_col(&literal.value(), range.start);
+            syn::Error::new_spanned(
+                literal,
+                format!(
+                    "{} (at line {}, column {} of the query)",
+                    message, line, column
+                ),
+            )
+        }
+    }
+}
+
+/// Converts an error from the parser into one which points at the right part of the query.
+pub fn parse_error(
+    literal: &syn::LitStr,
+    error: &pest::error::Error<ast::ast::Rule>,
+) -> syn::Error {
+    let range = match error.location {
+        InputLocation::Pos(position) => position..position + 1,
+        InputLocation::Span((start, end)) => start..end,
+    };
+    query_error(
+        literal,
+        Some(range),
+        &format!("Couldn't parse this query: {}", error.variant.message()),
+    )
+}
+
+#[cfg(test)]
+mod test_spans {
+    use super::*;
+
+    fn literal(source: &str) -> syn::LitStr {
+        syn::parse_str(source).expect("failed to parse")
+    }
+
+    #[test]
+    fn test_subspans() {
+        // the tests enable proc-macro2's `span-locations` feature, so subspans are available
+        let span = literal_subspan(&literal(r#""{ user }""#), 2..6).unwrap();
+        assert_eq!((span.start().column, span.end().column), (3, 7));
+        let span = literal_subspan(&literal(r###"r##"{ user }"##"###), 2..6).unwrap();
+        assert_eq!((span.start().column, span.end().column), (6, 10));
+        // escapes mean that we can't work out where things are
+        assert!(literal_subspan(&literal(r#""{ \"user\" }""#), 2..6).is_none());
+    }
+
+    #[test]
+    fn test_line_column_fallback() {
+        let error = query_error(&literal("\"{\\n  \\\"user\\\" }\""), Some(4..8), "Oops.");
+        assert_eq!(
+            error.to_string(),
+            "Oops. (at line 2, column 3 of the query)"
+        );
+    }
+}
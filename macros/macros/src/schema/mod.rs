@@ -0,0 +1,84 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+
+//! Contains the code for the `graphql_schema!` macro, which turns a whole schema into a Rust module.
+//! The actual code generation lives in `codegen::schema` (so that it can be shared with the build
+//! script API); this module just parses the input to the macro and loads the schema.
+
+use syn::parse::{Parse, ParseStream};
+
+use crate::search::parse_schema;
+
+/// The input to the macro: either `"path/to/schema.graphql"` (which generates a module called
+/// `schema`) or `name = "path/to/schema.graphql"`.
+pub struct SchemaInput {
+    name: syn::Ident,
+    path: syn::LitStr,
+}
+
+impl Parse for SchemaInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = if input.peek(syn::Ident) {
+            let name = input.parse()?;
+            input.parse::<syn::Token![=]>()?;
+            name
+        } else {
+            quote::format_ident!("schema")
+        };
+        Ok(Self {
+            name,
+            path: input.parse()?,
+        })
+    }
+}
+
+/// Generates the module for a schema.
+pub fn graphql_schema(input: SchemaInput) -> Result<proc_macro2::TokenStream, syn::Error> {
+    let schema = parse_schema(&[input.path.value()], &input.path)?;
+    Ok(schema.tracked(codegen::schema::schema_module(
+        &schema,
+        &input.name,
+        &input.path.value(),
+        proc_macro2::TokenStream::new(),
+    )))
+}
+
+#[cfg(test)]
+mod test_graphql_schema_macro {
+    use super::*;
+
+    fn generate(input: &str) -> Result<String, syn::Error> {
+        graphql_schema(syn::parse_str(input).expect("failed to parse")).map(|t| t.to_string())
+    }
+
+    #[test]
+    fn test_schema_module_generation() {
+        let output = generate(r#""schema.graphql""#).unwrap_or_else(|e| panic!("{}", e));
+        assert!(output.contains("pub mod schema"));
+        assert!(output.contains("pub const User : :: myoxine :: schema :: TypeMeta"));
+        assert!(output.contains("pub enum Role { Admin , Moderator , Member ,"));
+        assert!(output.contains("pub struct CreateUserInput"));
+        // `role` has a default value, so it's optional
+        assert!(output.contains("pub role : :: std :: option :: Option < Role >"));
+        assert!(output.contains("possible_types : & [\"User\" , \"Post\"]"));
+        // every field's `@cacheControl` hints are included, for `Cache::use_schema`
+        assert!(output.contains("fields : & [(\"address\" , :: myoxine :: cache :: control :: CacheHint { max_age : :: std :: option :: Option :: None , scope : :: myoxine :: cache :: control :: CacheScope :: Private , })]"));
+        assert!(output
+            .contains("cache_control : :: myoxine :: cache :: control :: CacheControl :: NONE"));
+    }
+
+    #[test]
+    fn test_named_schema_module() {
+        let output = generate(r#"github = "schema.graphql""#).unwrap_or_else(|e| panic!("{}", e));
+        assert!(output.contains("pub mod github"));
+    }
+
+    #[test]
+    fn test_missing_schema() {
+        assert!(generate(r#""does-not-exist.graphql""#).is_err());
+    }
+}
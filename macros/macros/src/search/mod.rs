@@ -2,3 +2,417 @@
 //! from schema files.
 //!
 //! Note: this is still a work in progress.
+
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use ast::ast::{Document, Name, TypeDefinition};
+use ast::cache::SchemaCache;
+use ast::sources::{self, SourceError, SourceFile};
+use ast::suggest::suggestion;
+use quote::ToTokens;
+
+/// The name of the attribute used to point a derive macro at a schema.
+pub const SCHEMA: &str = "schema";
+
+/// The environment variable which sets the schema used by items without a `#[schema = "..."]`
+/// attribute. The easiest way to set it for a whole workspace is in `.cargo/config.toml`:
+///
+/// ```toml
+/// [env]
+/// MYOXINE_SCHEMA = { value = "schema.graphql", relative = true }
+/// ```
+///
+/// (`relative = true` makes the path relative to the directory containing `.cargo`, rather than
+/// to each crate.)
+pub const DEFAULT_SCHEMA: &str = "MYOXINE_SCHEMA";
+
+/// Resolves a path to a schema. Relative paths are relative to the crate being compiled (i.e. the
+/// directory containing its `Cargo.toml`) rather than to wherever the compiler happens to be
+/// running.
+pub fn resolve(path: &str) -> PathBuf {
+    let path = PathBuf::from(path);
+    match std::env::var_os("CARGO_MANIFEST_DIR") {
+        Some(directory) if path.is_relative() => PathBuf::from(directory).join(path),
+        _ => path,
+    }
+}
+
+/// The opposite of `resolve` – turns a path back into one relative to the crate being compiled (if
+/// it's inside the crate), which is much nicer to read in error messages.
+pub fn unresolve(path: &Path) -> PathBuf {
+    std::env::var_os("CARGO_MANIFEST_DIR")
+        .and_then(|directory| path.strip_prefix(directory).ok())
+        .unwrap_or(path)
+        .to_path_buf()
+}
+
+/// Reads the paths out of an attribute which points at some GraphQL files. This can either be
+/// `#[name = "path"]` or `#[name("path", "another/path", ...)]`, and every path can be a glob
+/// (e.g. `schema/*.graphql`). Returns `None` if the attribute is called something else.
+pub fn attribute_paths(
+    attribute: &syn::Attribute,
+    name: &str,
+) -> Option<Result<Vec<String>, syn::Error>> {
+    if !attribute.path.is_ident(name) {
+        return None;
+    }
+    let error = || {
+        syn::Error::new_spanned(
+            attribute,
+            format!(
+                "This should be `#[{0} = \"<path>\"]` or `#[{0}(\"<path>\", \"<another path>\")]` \
+                (paths can contain wildcards, e.g. `schema/*.graphql`).",
+                name
+            ),
+        )
+    };
+    Some(match attribute.parse_meta() {
+        Ok(syn::Meta::NameValue(syn::MetaNameValue {
+            lit: syn::Lit::Str(string),
+            ..
+        })) => Ok(vec![string.value()]),
+        Ok(syn::Meta::List(list)) if !list.nested.is_empty() => list
+            .nested
+            .iter()
+            .map(|nested| match nested {
+                syn::NestedMeta::Lit(syn::Lit::Str(string)) => Ok(string.value()),
+                _ => Err(error()),
+            })
+            .collect(),
+        _ => Err(error()),
+    })
+}
+
+/// Finds the location of the schema from the `#[schema = "<...>"]` attribute on an item (or the
+/// default schema, if the item doesn't have one).
+pub fn schema_location(input: &syn::DeriveInput) -> Result<Vec<String>, syn::Error> {
+    input
+        .attrs
+        .iter()
+        .find_map(|attribute| attribute_paths(attribute, SCHEMA))
+        .unwrap_or_else(|| match std::env::var(DEFAULT_SCHEMA) {
+            Ok(path) => Ok(vec![path]),
+            Err(_) => Err(syn::Error::new_spanned(
+                &input.ident,
+                format!(
+                    "Missing `#[schema = \"<path to your schema>\"]` attribute (alternatively, \
+                    set a default schema for every item with the `{}` environment variable).",
+                    DEFAULT_SCHEMA
+                ),
+            )),
+        })
+}
+
+/// A parsed schema, along with where it came from. The source is kept around so that error messages
+/// can point at the relevant part of the schema.
+pub struct Schema {
+    /// The path (or paths) as they were written, which is what we show in error messages.
+    pub path: String,
+    /// The files which make up the schema. Their paths are relative to the crate being compiled
+    /// (see `unresolve`) where possible.
+    pub files: Vec<SourceFile>,
+    pub document: Document,
+}
+
+impl Deref for Schema {
+    type Target = Document;
+
+    fn deref(&self) -> &Self::Target {
+        &self.document
+    }
+}
+
+impl Schema {
+    /// Returns a note saying where a type is defined (e.g. "`User` is defined at
+    /// schema.graphql:12:1."), to be added to the end of an error message.
+    pub fn note(&self, name: &Name) -> String {
+        match sources::find_definition(&self.files, &name.0) {
+            Some((path, line, column)) => format!(
+                "\n\n`{}` is defined at {}:{}:{}.",
+                name,
+                path.display(),
+                line,
+                column
+            ),
+            None => String::new(),
+        }
+    }
+
+    /// Code which makes the compiler treat the schema as an input of the crate, so that changing
+    /// the schema causes the crate to be recompiled (see `track`).
+    pub fn track(&self) -> proc_macro2::TokenStream {
+        let default = match std::env::var_os(DEFAULT_SCHEMA) {
+            Some(_) => quote::quote! {
+                const _: ::std::option::Option<&str> = ::std::option_env!(#DEFAULT_SCHEMA);
+            },
+            None => proc_macro2::TokenStream::new(),
+        };
+        let files = track(&self.files);
+        quote::quote! {
+            #files
+            #default
+        }
+    }
+
+    /// Appends `track` to the output of a macro.
+    pub fn tracked(&self, output: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+        let track = self.track();
+        quote::quote! {
+            #output
+            #track
+        }
+    }
+}
+
+/// Code which makes the compiler treat some files as inputs of the crate being compiled.
+///
+/// Procedural macros can't (yet, on stable) tell the compiler which files they read, but the
+/// compiler does keep track of the files used by `include_bytes!` (and of the environment
+/// variables used by `option_env!`, which covers the default schema). Note that this means adding a
+/// new file which matches a glob doesn't cause a rebuild – only changes to the files which were
+/// there last time do.
+pub fn track(files: &[SourceFile]) -> proc_macro2::TokenStream {
+    let paths = files.iter().map(|file| {
+        resolve(&file.path.display().to_string())
+            .display()
+            .to_string()
+    });
+    quote::quote! {
+        #(const _: &[u8] = ::std::include_bytes!(#paths);)*
+    }
+}
+
+/// Reads every file matching `paths` (which are resolved using `resolve`). `what` describes the
+/// files, for use in error messages (e.g. "the schema").
+pub fn read_files<T>(paths: &[String], tokens: T, what: &str) -> Result<Vec<SourceFile>, syn::Error>
+where
+    T: ToTokens,
+{
+    let resolved = paths.iter().map(|path| resolve(path)).collect::<Vec<_>>();
+    let mut files = sources::read(&resolved).map_err(|error| source_error(error, &tokens, what))?;
+    for file in &mut files {
+        file.path = unresolve(&file.path);
+    }
+    Ok(files)
+}
+
+/// Turns a problem with reading or merging some files into an error at `tokens`.
+pub fn source_error<T>(error: SourceError, tokens: T, what: &str) -> syn::Error
+where
+    T: ToTokens,
+{
+    let message = match error {
+        SourceError::Io { path, error } => format!(
+            "Couldn't read {} at `{}` ({}). Relative paths are relative to the directory \
+            containing your `Cargo.toml`.",
+            what,
+            path.display(),
+            error
+        ),
+        SourceError::NoMatches(pattern) => format!(
+            "`{}` didn't match any files (while looking for {}). Relative paths are relative to \
+            the directory containing your `Cargo.toml`.",
+            pattern.display(),
+            what
+        ),
+        SourceError::Parse { path, message } => format!(
+            "The GraphQL at `{}` could not be parsed. Please ensure that {} is valid, and if in \
+            doubt file a bug report at https://github.com/d3bate/myoxine.\n\n{}",
+            unresolve(&path).display(),
+            what,
+            message
+        ),
+        SourceError::Duplicate(message) => message,
+    };
+    syn::Error::new_spanned(tokens, message)
+}
+
+/// The files making up a schema (and when each was last modified), as well as the parsed schema.
+type ParsedSchema = (Vec<(PathBuf, Option<SystemTime>)>, Arc<Schema>);
+
+/// Schemas which have already been parsed, along with the time at which each of their files was
+/// last modified (so that long-running processes, such as IDEs, notice when the schema changes).
+///
+/// The compiler loads procedural macros once per crate, so this means that a schema is only parsed
+/// once no matter how many items refer to it. Across crates (and builds) the on-disk cache in
+/// `ast::cache` is used instead.
+static SCHEMAS: Mutex<Option<HashMap<Vec<PathBuf>, ParsedSchema>>> = Mutex::new(None);
+
+/// Parses the schema made up of the files at the given paths (or retrieves it, if it has already
+/// been parsed). The files are merged into one schema. Errors are reported at the location of
+/// `tokens`.
+pub fn parse_schema<T>(paths: &[String], tokens: T) -> Result<Arc<Schema>, syn::Error>
+where
+    T: ToTokens,
+{
+    let what = "the schema";
+    let patterns = paths.iter().map(|path| resolve(path)).collect::<Vec<_>>();
+    let mut matched = vec![];
+    for pattern in &patterns {
+        for path in sources::glob(pattern).map_err(|error| source_error(error, &tokens, what))? {
+            let modified = std::fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .ok();
+            matched.push((path, modified));
+        }
+    }
+    let mut schemas = SCHEMAS.lock().unwrap_or_else(|error| error.into_inner());
+    let schemas = schemas.get_or_insert_with(HashMap::new);
+    if let Some((last_matched, schema)) = schemas.get(&patterns) {
+        if last_matched == &matched && matched.iter().all(|(_, modified)| modified.is_some()) {
+            return Ok(schema.clone());
+        }
+    }
+    let files = read_files(paths, &tokens, what)?;
+    // parsing big schemas is slow, so we check whether an earlier build has already done it
+    let cache = SchemaCache::from_environment();
+    let document = sources::parse(&files, |source| match &cache {
+        Some(cache) => cache.parse(source),
+        None => ast::parse_string(source),
+    })
+    .map_err(|error| source_error(error, &tokens, what))?;
+    let schema = Arc::new(Schema {
+        path: paths.join(", "),
+        files,
+        document,
+    });
+    schemas.insert(patterns, (matched, schema.clone()));
+    Ok(schema)
+}
+
+/// Parses the schema which the provided item refers to.
+pub fn load_schema(input: &syn::DeriveInput) -> Result<Arc<Schema>, syn::Error> {
+    parse_schema(&schema_location(input)?, &input.ident)
+}
+
+/// Finds the type in the schema with the same name as the item being derived on.
+pub fn find_type_definition<'a>(
+    schema: &'a Schema,
+    input: &syn::DeriveInput,
+) -> Result<&'a TypeDefinition, syn::Error> {
+    let name = input.ident.to_string();
+    schema
+        .get_type_definition(&Name(name.clone()))
+        .ok_or_else(|| {
+            syn::Error::new_spanned(
+                input.ident.clone(),
+                format!(
+                    "The type `{}` could not be found in the schema at `{}`.{}",
+                    name,
+                    schema.path,
+                    suggestion(
+                        &name,
+                        schema
+                            .type_definitions()
+                            .map(|definition| definition.name().0.as_str())
+                    )
+                ),
+            )
+        })
+}
+
+/// The error for when the type being derived on is the wrong kind of type in the schema (e.g. an
+/// enum rather than an object).
+pub fn wrong_kind(
+    schema: &Schema,
+    input: &syn::DeriveInput,
+    found: &TypeDefinition,
+    expected: &str,
+) -> syn::Error {
+    syn::Error::new_spanned(
+        input.ident.clone(),
+        format!(
+            "`{}` is {} in the schema, not {}.{}",
+            input.ident,
+            describe(found),
+            expected,
+            schema.note(found.name())
+        ),
+    )
+}
+
+/// Describes what sort of type a `TypeDefinition` is, for use in error messages.
+pub fn describe(type_definition: &TypeDefinition) -> &'static str {
+    match type_definition {
+        TypeDefinition::ScalarTypeDefinition(_) => "a scalar",
+        TypeDefinition::ObjectTypeDefinition(_) => "an object",
+        TypeDefinition::InterfaceTypeDefinition(_) => "an interface",
+        TypeDefinition::UnionTypeDefinition(_) => "a union",
+        TypeDefinition::EnumTypeDefinition(_) => "an enum",
+        TypeDefinition::InputObjectTypeDefinition(_) => "an input object",
+    }
+}
+
+#[cfg(test)]
+mod test_schema_paths {
+    use super::*;
+
+    #[test]
+    fn test_paths_are_relative_to_the_manifest() {
+        let schema = parse_schema(&["schema.graphql".to_string()], quote::quote!()).unwrap();
+        assert_eq!(schema.path, "schema.graphql");
+        assert_eq!(schema.files[0].path, PathBuf::from("schema.graphql"));
+        let resolved = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("schema.graphql");
+        assert!(schema
+            .track()
+            .to_string()
+            .contains(&format!("{:?}", resolved.display().to_string())));
+        // absolute paths are left alone
+        assert_eq!(resolve("/schema.graphql"), PathBuf::from("/schema.graphql"));
+    }
+
+    #[test]
+    fn test_schemas_split_across_files() {
+        let schema =
+            parse_schema(&["fixtures/split/*.graphql".to_string()], quote::quote!()).unwrap();
+        assert_eq!(schema.files.len(), 2);
+        assert!(schema
+            .get_type_definition(&Name("User".to_string()))
+            .is_some());
+        assert_eq!(
+            schema.note(&Name("User".to_string())),
+            "\n\n`User` is defined at fixtures/split/users.graphql:1:1."
+        );
+        assert_eq!(
+            schema.track().to_string().matches("include_bytes").count(),
+            2
+        );
+        // the same type can't be defined in two places
+        let error = parse_schema(
+            &[
+                "fixtures/split/*.graphql".to_string(),
+                "schema.graphql".to_string(),
+            ],
+            quote::quote!(),
+        )
+        .err()
+        .unwrap();
+        assert!(error.to_string().contains("is defined more than once"));
+        let error = parse_schema(&["fixtures/split/*.json".to_string()], quote::quote!())
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("didn't match any files"));
+    }
+
+    #[test]
+    fn test_attribute_paths() {
+        let paths = |attribute: &str| {
+            let item: syn::DeriveInput =
+                syn::parse_str(&format!("{} struct User;", attribute)).unwrap();
+            schema_location(&item).map_err(|error| error.to_string())
+        };
+        assert_eq!(
+            paths("#[schema = \"a.graphql\"]"),
+            Ok(vec!["a.graphql".to_string()])
+        );
+        assert_eq!(
+            paths("#[schema(\"a.graphql\", \"b/*.graphql\")]"),
+            Ok(vec!["a.graphql".to_string(), "b/*.graphql".to_string()])
+        );
+        assert!(paths("#[schema(a)]").is_err());
+    }
+}
@@ -3,10 +3,10 @@ pub fn token_streams_are_equal(
     stream1: proc_macro2::TokenStream,
     stream2: proc_macro2::TokenStream,
 ) -> bool {
-    stream1
+    !stream1
         .into_iter()
         .zip(stream2)
-        .filter(|(token1, token2)| match token1 {
+        .any(|(token1, token2)| match token1 {
             proc_macro2::TokenTree::Group(group1) => {
                 if let proc_macro2::TokenTree::Group(group2) = token2 {
                     let delimiter1 = group1.delimiter();
@@ -25,14 +25,12 @@ pub fn token_streams_are_equal(
                 }
             }
             proc_macro2::TokenTree::Punct(punct1) => {
-                if let proc_macro2::TokenTree::Punct(_) = token2 {
-                    punct1.to_string() != punct1.to_string()
+                if let proc_macro2::TokenTree::Punct(punct2) = token2 {
+                    punct1.as_char() != punct2.as_char()
                 } else {
                     true
                 }
             }
             proc_macro2::TokenTree::Literal(_) => false,
         })
-        .next()
-        .is_none()
 }
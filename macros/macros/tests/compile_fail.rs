@@ -0,0 +1,41 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+
+//! Checks the errors which the macros produce, as the compiler shows them (and that code which
+//! should compile does).
+//!
+//! Mistakes in string literals can only be pointed at exactly on nightly compilers (see
+//! `src/query/spans.rs`), so stable and nightly compilers each have their own expected output.
+
+use std::process::Command;
+
+/// Whether the tests are being compiled with a nightly (or otherwise unstable) compiler.
+fn nightly() -> bool {
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    Command::new(rustc)
+        .arg("--version")
+        .output()
+        .map(|output| {
+            let version = String::from_utf8_lossy(&output.stdout);
+            version.contains("nightly") || version.contains("-dev")
+        })
+        .unwrap_or(false)
+}
+
+#[test]
+fn test_compile_errors() {
+    // (the tests are compiled somewhere else, so they can't refer to the schema with a relative
+    // path)
+    std::env::set_var(
+        "MYOXINE_SCHEMA",
+        concat!(env!("CARGO_MANIFEST_DIR"), "/schema.graphql"),
+    );
+    let channel = if nightly() { "nightly" } else { "stable" };
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail(format!("tests/ui/{}/*.rs", channel));
+    cases.pass("tests/ui/pass/*.rs");
+}
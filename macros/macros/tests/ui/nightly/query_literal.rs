@@ -0,0 +1,6 @@
+// the schema comes from `MYOXINE_SCHEMA` (see `tests/compile_fail.rs`)
+const USER_PAGE: &str = macros::query!("query UserPage($id: ID!) { user(id: $id) { usrname } }");
+
+fn main() {
+    println!("{}", USER_PAGE);
+}
@@ -0,0 +1,58 @@
+// objects can use serde's own derives (as long as they agree with the schema about names)...
+extern crate runtime as myoxine;
+
+use myoxine::objects::Object;
+use serde::{Deserialize, Serialize};
+
+#[derive(macros::ValueObject, Serialize, Deserialize)]
+struct Address {
+    street: String,
+    city: String,
+}
+
+#[derive(macros::Object, Serialize, Deserialize)]
+#[serde(tag = "__typename")]
+struct User {
+    #[id]
+    id: myoxine::Id,
+    username: String,
+    address: Option<Address>,
+}
+
+#[derive(macros::Object, Serialize, Deserialize)]
+#[graphql(rename_all = "camelCase")]
+#[serde(rename_all = "camelCase")]
+struct Post {
+    #[id]
+    id: myoxine::Id,
+    #[graphql(rename = "title")]
+    #[serde(rename = "title")]
+    heading: String,
+    published_at: Option<String>,
+}
+
+// ...or have them generated
+mod generated {
+    #[derive(macros::Object)]
+    #[graphql(rename_all = "camelCase", serde)]
+    pub struct Post {
+        #[id]
+        pub id: myoxine::Id,
+        pub published_at: Option<String>,
+    }
+}
+
+fn main() {
+    let user = User {
+        id: "1".to_string(),
+        username: "jane".to_string(),
+        address: None,
+    };
+    let _ = user.refetch_object();
+    let _ = myoxine::serde_json::to_value(Post {
+        id: "1".to_string(),
+        heading: "Hello".to_string(),
+        published_at: None,
+    });
+    let _ = myoxine::serde_json::from_str::<generated::Post>(r#"{"__typename": "Post", "id": "1"}"#);
+}
@@ -0,0 +1,151 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+
+//! Benchmarks for `VanillaCache`, with 10k, 100k and 1M records in the cache. Every operation
+//! should take (roughly) the same time whatever the size of the cache. Run them with
+//! `cargo bench -p runtime`.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use runtime::cache::{Cache, VanillaCache};
+use runtime::objects::Object;
+use runtime::query::Query;
+use runtime::selection::Selection;
+use serde::{Deserialize, Serialize};
+
+const SIZES: &[usize] = &[10_000, 100_000, 1_000_000];
+
+/// The number of posts in a page of the feed (for `write_query`).
+const PAGE: usize = 100;
+
+// the derive macros can't be used here (they refer to the `myoxine` crate, which this is), so the
+// traits are implemented by hand
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "__typename")]
+struct User {
+    id: String,
+    name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "__typename")]
+struct Post {
+    id: String,
+    title: String,
+    author: User,
+}
+
+impl Selection for Post {
+    type Fields = ();
+    fn selection_set_for(_: &()) -> String {
+        "{ __typename id title author { __typename id name } }".to_string()
+    }
+}
+
+impl Object for Post {
+    const GRAPHQL_TYPE: &'static str = "Post";
+    fn id(&self) -> &runtime::Id {
+        &self.id
+    }
+    fn refetch_fields(&self, _: &()) -> Query<Self> {
+        Query::new(
+            runtime::ast::parse_string(
+                "query($id: ID!) { node(id: $id) { ... on Post \
+                { __typename id title author { __typename id name } } } }",
+            )
+            .unwrap(),
+        )
+        .with_response_path(&["node"])
+        .with_variable("id", self.id())
+        .unwrap()
+    }
+}
+
+fn post(id: usize) -> Post {
+    Post {
+        id: id.to_string(),
+        title: format!("Post {}", id),
+        // every user writes ten posts
+        author: User {
+            id: (id / 10).to_string(),
+            name: format!("User {}", id / 10),
+        },
+    }
+}
+
+/// A cache containing `size` records (posts, and a tenth as many users).
+fn populated(size: usize) -> VanillaCache {
+    let mut cache = VanillaCache::new();
+    for id in 0..size * 10 / 11 {
+        cache.cache(post(id));
+    }
+    cache
+}
+
+/// A page of the feed, as the server would send it.
+fn feed_page(start: usize) -> serde_json::Value {
+    let posts = (start..start + PAGE)
+        .map(|id| serde_json::to_value(post(id)).unwrap())
+        .collect::<Vec<_>>();
+    serde_json::json!({ "feed": posts })
+}
+
+fn bench_cache(c: &mut Criterion) {
+    let feed = Query::<serde_json::Value>::new(
+        ast::parse_string(
+            "query($after: Int) { feed(after: $after) { __typename id title \
+            author { __typename id name } } }",
+        )
+        .unwrap(),
+    )
+    .with_variable("after", &0)
+    .unwrap();
+
+    let mut group = c.benchmark_group("vanilla_cache");
+    group.sample_size(10);
+    for &size in SIZES {
+        let mut cache = populated(size);
+        let posts = size * 10 / 11;
+        let mut next = 0;
+        let mut id = move || {
+            // a large prime, so that the ids are spread across the whole cache
+            next = (next + 7919) % posts;
+            next
+        };
+
+        group.bench_with_input(BenchmarkId::new("retrieve", size), &size, |b, _| {
+            b.iter(|| black_box(cache.retrieve::<Post>(&id().to_string())))
+        });
+        group.bench_with_input(BenchmarkId::new("update", size), &size, |b, _| {
+            b.iter_batched(
+                || post(id()),
+                |post| cache.cache(post),
+                BatchSize::SmallInput,
+            )
+        });
+        group.bench_with_input(BenchmarkId::new("remove", size), &size, |b, _| {
+            b.iter_batched(
+                || id().to_string(),
+                |id| cache.remove::<Post>(&id),
+                BatchSize::SmallInput,
+            )
+        });
+        group.bench_with_input(BenchmarkId::new("write_feed_page", size), &size, |b, _| {
+            b.iter_batched(
+                || feed_page(id()),
+                |page| cache.write_query(&feed, &page),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_cache);
+criterion_main!(benches);
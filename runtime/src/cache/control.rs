@@ -0,0 +1,173 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+
+//! Caching hints from the schema's `@cacheControl` directives, e.g.
+//!
+//! ```graphql
+//! type Post @cacheControl(maxAge: 240) {
+//!   id: ID!
+//!   votes: Int @cacheControl(maxAge: 30)
+//!   readByCurrentUser: Boolean! @cacheControl(scope: PRIVATE)
+//! }
+//! ```
+//!
+//! `#[derive(Object)]` turns these into `Object::CACHE_CONTROL`. Once a record is older than its
+//! `maxAge` it is *stale* – it is still returned by the cache, but it is also refetched (see
+//! `Cache::set_revalidator`). Anything `PRIVATE` (i.e. data which belongs to whoever is logged in)
+//! is tagged when it is written, so that it can be thrown away with `Cache::purge_private`.
+
+use std::collections::{HashMap, HashSet};
+
+/// Who the data is for (the `scope` argument of `@cacheControl`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheScope {
+    /// The same for everybody.
+    Public,
+    /// Specific to whoever is logged in.
+    Private,
+}
+
+/// A single `@cacheControl` directive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheHint {
+    /// How long (in seconds) the data stays fresh. `None` means forever (or, for a field, however
+    /// long the type it is on stays fresh).
+    pub max_age: Option<u32>,
+    pub scope: CacheScope,
+}
+
+impl CacheHint {
+    pub const NONE: Self = Self {
+        max_age: None,
+        scope: CacheScope::Public,
+    };
+}
+
+/// The hints on an object type, and on its fields (keyed by their GraphQL names). Only fields
+/// which the Rust type actually has are included (except in `TypeMeta::cache_control`, which has
+/// all of them).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheControl {
+    pub hint: CacheHint,
+    pub fields: &'static [(&'static str, CacheHint)],
+}
+
+impl CacheControl {
+    /// No hints at all, so objects never go stale.
+    pub const NONE: Self = Self {
+        hint: CacheHint::NONE,
+        fields: &[],
+    };
+
+    /// How long an object stays fresh – the shortest `maxAge` out of the type and its fields.
+    pub fn max_age(&self) -> Option<u32> {
+        std::iter::once(&self.hint)
+            .chain(self.fields.iter().map(|(_, hint)| hint))
+            .filter_map(|hint| hint.max_age)
+            .min()
+    }
+
+    /// Whether the type, or any of its fields, is `PRIVATE`.
+    pub fn is_private(&self) -> bool {
+        std::iter::once(&self.hint)
+            .chain(self.fields.iter().map(|(_, hint)| hint))
+            .any(|hint| hint.scope == CacheScope::Private)
+    }
+}
+
+/// Which types and fields are `PRIVATE`, out of the hints of every type the cache knows about.
+/// Several Rust types can share a GraphQL type (each with the hints of different fields), so
+/// this adds them all up.
+#[derive(Debug, Clone, Default)]
+pub struct Privacy {
+    types: HashSet<&'static str>,
+    fields: HashMap<&'static str, HashSet<&'static str>>,
+}
+
+impl Privacy {
+    /// Adds the hints of a type (by its `__typename`).
+    pub fn add(&mut self, typename: &'static str, control: &CacheControl) {
+        if control.hint.scope == CacheScope::Private {
+            self.types.insert(typename);
+        }
+        for (field, hint) in control.fields {
+            if hint.scope == CacheScope::Private {
+                self.fields.entry(typename).or_default().insert(field);
+            }
+        }
+    }
+
+    /// Whether every object of a type is private.
+    pub fn is_private_type(&self, typename: &str) -> bool {
+        self.types.contains(typename)
+    }
+
+    /// Whether a field of a type is private (not counting the type itself being private).
+    pub fn is_private_field(&self, typename: &str, field: &str) -> bool {
+        self.fields
+            .get(typename)
+            .is_some_and(|fields| fields.contains(field))
+    }
+}
+
+/// The current time, in milliseconds since the Unix epoch. (`std::time` panics in the browser, so
+/// JavaScript's clock is used there instead.)
+pub fn now() -> f64 {
+    #[cfg(target_arch = "wasm32")]
+    {
+        js_sys::Date::now()
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_secs_f64() * 1000.0)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test_cache_control {
+    use super::*;
+
+    #[test]
+    fn test_fields_are_taken_into_account() {
+        let control = CacheControl {
+            hint: CacheHint {
+                max_age: Some(240),
+                scope: CacheScope::Public,
+            },
+            fields: &[
+                (
+                    "votes",
+                    CacheHint {
+                        max_age: Some(30),
+                        scope: CacheScope::Public,
+                    },
+                ),
+                (
+                    "readByCurrentUser",
+                    CacheHint {
+                        max_age: None,
+                        scope: CacheScope::Private,
+                    },
+                ),
+            ],
+        };
+        assert_eq!(control.max_age(), Some(30));
+        assert!(control.is_private());
+        assert_eq!(CacheControl::NONE.max_age(), None);
+        assert!(!CacheControl::NONE.is_private());
+
+        let mut privacy = Privacy::default();
+        privacy.add("Post", &control);
+        assert!(!privacy.is_private_type("Post"));
+        assert!(privacy.is_private_field("Post", "readByCurrentUser"));
+        assert!(!privacy.is_private_field("Post", "votes"));
+        assert!(!privacy.is_private_field("User", "readByCurrentUser"));
+    }
+}
@@ -0,0 +1,213 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+
+//! Eviction, which stops the cache from growing forever.
+//!
+//! A cache can be given a `Limit` (a number of records, or a rough number of bytes) and an
+//! `EvictionPolicy`, which decides which records go first when the limit is exceeded. `Lru` and
+//! `Lfu` are provided, but anything which implements `EvictionPolicy` can be used.
+//!
+//! ```ignore
+//! VANILLA_CACHE.with(|cache| {
+//!     cache
+//!         .borrow_mut()
+//!         .set_eviction(Lru::new(), Limit::Records(10_000))
+//! });
+//! ```
+//!
+//! Records which are pinned (see `Cache::pin` – `QueryProvider` pins whatever it is showing), or
+//! which can be reached from a pinned record, are never evicted.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde_json::Value;
+
+use super::store::{Fields, RecordKey, StoreValue};
+
+/// How big the cache is allowed to get.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    /// The maximum number of records.
+    Records(usize),
+    /// The (approximate – see `record_size`) maximum number of bytes the records take up.
+    Bytes(usize),
+}
+
+/// Decides which records are evicted first. The cache tells the policy whenever a record is used
+/// and whenever one is removed, and asks it for records to evict when it gets too big.
+pub trait EvictionPolicy {
+    /// Called whenever a record is written or read.
+    fn touch(&mut self, record: &RecordKey);
+    /// Called when a record is removed from the cache (whether or not it was evicted).
+    fn forget(&mut self, record: &RecordKey);
+    /// Every record the policy knows about, starting with the one which should be evicted first.
+    fn order(&self) -> Box<dyn Iterator<Item = &RecordKey> + '_>;
+}
+
+/// Evicts the least recently used records first.
+#[derive(Debug, Default)]
+pub struct Lru {
+    tick: u64,
+    last_used: HashMap<RecordKey, u64>,
+    order: BTreeMap<u64, RecordKey>,
+}
+
+impl Lru {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl EvictionPolicy for Lru {
+    fn touch(&mut self, record: &RecordKey) {
+        self.tick += 1;
+        if let Some(previous) = self.last_used.insert(record.clone(), self.tick) {
+            self.order.remove(&previous);
+        }
+        self.order.insert(self.tick, record.clone());
+    }
+
+    fn forget(&mut self, record: &RecordKey) {
+        if let Some(previous) = self.last_used.remove(record) {
+            self.order.remove(&previous);
+        }
+    }
+
+    fn order(&self) -> Box<dyn Iterator<Item = &RecordKey> + '_> {
+        Box::new(self.order.values())
+    }
+}
+
+/// Evicts the least frequently used records first (and, out of records which have been used
+/// equally often, the one which was used longest ago).
+#[derive(Debug, Default)]
+pub struct Lfu {
+    tick: u64,
+    /// The number of times each record has been used, and when it was last used.
+    uses: HashMap<RecordKey, (u64, u64)>,
+    order: BTreeMap<(u64, u64), RecordKey>,
+}
+
+impl Lfu {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl EvictionPolicy for Lfu {
+    fn touch(&mut self, record: &RecordKey) {
+        self.tick += 1;
+        let count = match self.uses.get(record) {
+            Some(previous) => {
+                self.order.remove(previous);
+                previous.0 + 1
+            }
+            None => 1,
+        };
+        self.uses.insert(record.clone(), (count, self.tick));
+        self.order.insert((count, self.tick), record.clone());
+    }
+
+    fn forget(&mut self, record: &RecordKey) {
+        if let Some(previous) = self.uses.remove(record) {
+            self.order.remove(&previous);
+        }
+    }
+
+    fn order(&self) -> Box<dyn Iterator<Item = &RecordKey> + '_> {
+        Box::new(self.order.values())
+    }
+}
+
+/// Roughly how many bytes a record takes up. This doesn't try to be exact (it ignores the overhead
+/// of the hash maps, for example) – it only needs to grow and shrink along with the real size.
+pub fn record_size(record: &RecordKey, fields: &Fields) -> usize {
+    record.typename.len() + record.id.len() + fields_size(fields)
+}
+
+fn fields_size(fields: &Fields) -> usize {
+    fields
+        .iter()
+        .map(|(key, value)| key.len() + value_size(value))
+        .sum()
+}
+
+fn value_size(value: &StoreValue) -> usize {
+    // (every value takes up at least the space of the enum it is stored in)
+    let base = std::mem::size_of::<StoreValue>();
+    base + match value {
+        StoreValue::Scalar(value) => json_size(value),
+        StoreValue::Reference(key) => key.typename.len() + key.id.len(),
+        StoreValue::Object(fields) => fields_size(fields),
+        StoreValue::List(items) => items.iter().map(value_size).sum(),
+    }
+}
+
+fn json_size(value: &Value) -> usize {
+    match value {
+        Value::Null | Value::Bool(_) | Value::Number(_) => 0,
+        Value::String(string) => string.len(),
+        Value::Array(items) => items.iter().map(json_size).sum(),
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, value)| key.len() + json_size(value))
+            .sum(),
+    }
+}
+
+#[cfg(test)]
+mod test_eviction {
+    use super::*;
+
+    fn key(id: &str) -> RecordKey {
+        RecordKey::new("User", id)
+    }
+
+    fn order(policy: &dyn EvictionPolicy) -> Vec<String> {
+        policy.order().map(|key| key.id.clone()).collect()
+    }
+
+    #[test]
+    fn test_lru() {
+        let mut lru = Lru::new();
+        for id in &["1", "2", "3"] {
+            lru.touch(&key(id));
+        }
+        lru.touch(&key("1"));
+        assert_eq!(order(&lru), vec!["2", "3", "1"]);
+        lru.forget(&key("3"));
+        assert_eq!(order(&lru), vec!["2", "1"]);
+    }
+
+    #[test]
+    fn test_lfu() {
+        let mut lfu = Lfu::new();
+        for id in &["1", "2", "3", "1", "1", "3"] {
+            lfu.touch(&key(id));
+        }
+        assert_eq!(order(&lfu), vec!["2", "3", "1"]);
+        lfu.forget(&key("2"));
+        lfu.touch(&key("4"));
+        assert_eq!(order(&lfu), vec!["4", "3", "1"]);
+    }
+
+    #[test]
+    fn test_sizes() {
+        let small = vec![(
+            "name".to_string(),
+            StoreValue::Scalar(Value::String("Jane".to_string())),
+        )]
+        .into_iter()
+        .collect::<Fields>();
+        let mut large = small.clone();
+        large.insert(
+            "bio".to_string(),
+            StoreValue::Scalar(Value::String("x".repeat(1000))),
+        );
+        assert!(record_size(&key("1"), &large) > record_size(&key("1"), &small) + 1000);
+    }
+}
@@ -0,0 +1,345 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+
+//! Fetch policies, which decide when data is read from the cache and when it is fetched from the
+//! server.
+//!
+//! The default (`FetchPolicy::CacheFirst`) only goes to the network for things which aren't in the
+//! cache, which is what makes a cache restored from the server (see `Cache::restore`) useful – the
+//! page renders straight away, without sending a single request.
+//!
+//! ```ignore
+//! fetch_query::<_, VanillaNetwork, VanillaCache>(
+//!     query,
+//!     FetchPolicy::CacheAndNetwork,
+//!     link.callback(Msg::Loaded),
+//! );
+//! ```
+
+use serde::Deserialize;
+use yew::Callback;
+
+use super::Cache;
+use crate::network::{Network, NetworkError};
+use crate::query::Query;
+
+/// When to use the cache, and when to use the network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FetchPolicy {
+    /// Use whatever is in the cache, and only fetch things which aren't there.
+    #[default]
+    CacheFirst,
+    /// Use whatever is in the cache straight away, but fetch everything anyway (so that it is
+    /// replaced with the latest version shortly afterwards).
+    CacheAndNetwork,
+    /// Always fetch, and ignore whatever is in the cache until the response arrives. The response
+    /// is still written to the cache.
+    NetworkOnly,
+    /// Never fetch anything – things which aren't in the cache aren't loaded at all.
+    CacheOnly,
+}
+
+impl FetchPolicy {
+    /// Whether whatever is in the cache should be used (rather than waiting for the network).
+    pub fn reads_cache(self) -> bool {
+        self != Self::NetworkOnly
+    }
+
+    /// Whether the network should be used, given whether the cache had what was asked for.
+    pub fn fetches(self, cached: bool) -> bool {
+        match self {
+            Self::CacheFirst => !cached,
+            Self::CacheAndNetwork | Self::NetworkOnly => true,
+            Self::CacheOnly => false,
+        }
+    }
+}
+
+/// Runs a query according to `policy`. `callback` is called with the result from the cache (if
+/// the policy reads it and the result is there), and then with the result from the network (if the
+/// policy fetches it), which is also written into `CACHE`.
+pub fn fetch_query<OUT, NETWORK, CACHE>(
+    query: Query<OUT>,
+    policy: FetchPolicy,
+    callback: Callback<OUT>,
+) where
+    OUT: for<'de> Deserialize<'de> + 'static,
+    NETWORK: Network + 'static,
+    CACHE: Cache,
+{
+    let cached = if policy.reads_cache() {
+        CACHE::local_key().with(|cache| cache.borrow().read_query(&query))
+    } else {
+        None
+    };
+    let fetch = policy.fetches(cached.is_some());
+    // (the cache isn't borrowed while the callback runs, in case it wants to use the cache too)
+    if let Some(cached) = cached {
+        callback.emit(cached);
+    }
+    if !fetch {
+        return;
+    }
+    let raw = query.raw();
+    NETWORK::local_key().with(|network| {
+        network.borrow_mut().dispatch(
+            raw,
+            Callback::from(move |data: Result<serde_json::Value, NetworkError>| {
+                // (there's nothing to do if it failed – whatever was in the cache is still there)
+                if let Ok(data) = data {
+                    CACHE::local_key().with(|cache| cache.borrow_mut().write_query(&query, &data));
+                    if let Ok(result) = query.deserialize_data(data) {
+                        callback.emit(result);
+                    }
+                }
+            }),
+        )
+    });
+}
+
+/// Sends a mutation, showing `optimistic` (what the server is expected to respond with) straight
+/// away – see `Cache::write_optimistic`. The optimistic layer is replaced with the server's
+/// response when it arrives, or rolled back if the mutation fails (and then `callback` is called
+/// with the result), e.g.
+///
+/// ```ignore
+/// let layer = mutate_optimistically::<_, VanillaNetwork, VanillaCache>(
+///     like_post,
+///     &json!({"likePost": {"__typename": "Post", "id": id, "likes": likes + 1}}),
+///     link.callback(Msg::Liked),
+/// );
+/// ```
+///
+/// The id of the optimistic layer is returned, in case it needs to be rolled back early (e.g. if
+/// the user cancels whatever they were doing).
+pub fn mutate_optimistically<OUT, NETWORK, CACHE>(
+    mutation: Query<OUT>,
+    optimistic: &serde_json::Value,
+    callback: Callback<Result<OUT, NetworkError>>,
+) -> u64
+where
+    OUT: for<'de> Deserialize<'de> + 'static,
+    NETWORK: Network + 'static,
+    CACHE: Cache,
+{
+    let layer =
+        CACHE::local_key().with(|cache| cache.borrow_mut().write_optimistic(&mutation, optimistic));
+    let raw = mutation.raw();
+    NETWORK::local_key().with(|network| {
+        network.borrow_mut().dispatch(
+            raw,
+            Callback::from(move |data: Result<serde_json::Value, NetworkError>| {
+                let data = match data {
+                    Ok(data) => data,
+                    Err(error) => {
+                        CACHE::local_key()
+                            .with(|cache| cache.borrow_mut().rollback_optimistic(layer));
+                        return callback.emit(Err(error));
+                    }
+                };
+                CACHE::local_key().with(|cache| {
+                    cache
+                        .borrow_mut()
+                        .commit_optimistic(layer, &mutation, &data)
+                });
+                callback.emit(
+                    mutation
+                        .deserialize_data(data)
+                        .map_err(|error| NetworkError::Response(error.to_string())),
+                );
+            }),
+        )
+    });
+    layer
+}
+
+#[cfg(test)]
+mod test_fetch {
+    use super::*;
+    use crate::cache::store::{RecordKey, StoreValue};
+    use crate::cache::{Event, VanillaCache, VANILLA_CACHE};
+    use crate::network::test_network::{respond, take_pending, TestNetwork};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn query() -> Query<String> {
+        Query::new(ast::parse_string("query { viewer { name } }").unwrap())
+            .with_response_path(&["viewer", "name"])
+    }
+
+    fn fetch(policy: FetchPolicy) -> Rc<RefCell<Vec<String>>> {
+        let results = Rc::new(RefCell::new(vec![]));
+        fetch_query::<_, TestNetwork, VanillaCache>(
+            query(),
+            policy,
+            Callback::from({
+                let results = results.clone();
+                move |name| results.borrow_mut().push(name)
+            }),
+        );
+        results
+    }
+
+    #[test]
+    fn test_fetch_policies() {
+        // nothing is in the cache yet, so even `CacheFirst` has to fetch
+        let results = fetch(FetchPolicy::CacheFirst);
+        assert_eq!(respond(serde_json::json!({"viewer": {"name": "Jane"}})), 1);
+        assert_eq!(*results.borrow(), vec!["Jane"]);
+        // ...but not any more
+        assert_eq!(*fetch(FetchPolicy::CacheFirst).borrow(), vec!["Jane"]);
+        assert_eq!(*fetch(FetchPolicy::CacheOnly).borrow(), vec!["Jane"]);
+        assert_eq!(respond(serde_json::json!(null)), 0);
+
+        let results = fetch(FetchPolicy::CacheAndNetwork);
+        assert_eq!(respond(serde_json::json!({"viewer": {"name": "Janet"}})), 1);
+        assert_eq!(*results.borrow(), vec!["Jane", "Janet"]);
+        // (the response was written to the cache)
+        assert_eq!(
+            VANILLA_CACHE.with(|cache| cache.borrow().read_query(&query())),
+            Some("Janet".to_string())
+        );
+
+        let results = fetch(FetchPolicy::NetworkOnly);
+        assert!(results.borrow().is_empty());
+        assert_eq!(respond(serde_json::json!({"viewer": {"name": "J"}})), 1);
+        assert_eq!(*results.borrow(), vec!["J"]);
+    }
+
+    #[test]
+    fn test_mutate_optimistically() {
+        let mutation = Query::<String>::new(
+            ast::parse_string("mutation { rename { __typename id name } }").unwrap(),
+        )
+        .with_response_path(&["rename", "name"]);
+        let user = |name: &str| serde_json::json!({"__typename": "User", "id": "1", "name": name});
+        let name = || {
+            VANILLA_CACHE.with(|cache| {
+                cache
+                    .borrow()
+                    .store()
+                    .get(&RecordKey::new("User", "1"))
+                    .map(|fields| fields["name"].clone())
+            })
+        };
+        let results = Rc::new(RefCell::new(vec![]));
+        mutate_optimistically::<_, TestNetwork, VanillaCache>(
+            mutation,
+            &serde_json::json!({ "rename": user("Jane") }),
+            Callback::from({
+                let results = results.clone();
+                move |name| results.borrow_mut().push(name)
+            }),
+        );
+        // the optimistic response shows up straight away...
+        assert_eq!(name(), Some(StoreValue::Scalar(serde_json::json!("Jane"))));
+        assert!(results.borrow().is_empty());
+        // ...and is replaced by what the server says
+        assert_eq!(respond(serde_json::json!({ "rename": user("Janet") })), 1);
+        assert_eq!(name(), Some(StoreValue::Scalar(serde_json::json!("Janet"))));
+        assert_eq!(*results.borrow(), vec![Ok("Janet".to_string())]);
+    }
+
+    #[derive(serde::Serialize, Deserialize, Debug, Clone, PartialEq)]
+    #[serde(tag = "__typename")]
+    struct User {
+        id: String,
+        name: String,
+    }
+
+    impl crate::selection::Selection for User {
+        type Fields = ();
+        fn selection_set_for(_: &()) -> String {
+            "{ __typename id name }".to_string()
+        }
+    }
+
+    impl crate::objects::Object for User {
+        const GRAPHQL_TYPE: &'static str = "User";
+        fn id(&self) -> &crate::Id {
+            &self.id
+        }
+        fn refetch_fields(&self, _: &()) -> Query<Self> {
+            Self::fetch(&self.id)
+        }
+    }
+
+    #[test]
+    fn test_failed_mutations_are_rolled_back() {
+        VANILLA_CACHE.with(|cache| {
+            cache.borrow_mut().cache(User {
+                id: "1".to_string(),
+                name: "Jane".to_string(),
+            })
+        });
+        let events = Rc::new(RefCell::new(vec![]));
+        VANILLA_CACHE.with(|cache| {
+            cache.borrow_mut().subscribe::<User>(
+                Box::new(|_| true),
+                Callback::from({
+                    let events = events.clone();
+                    move |(event, user): (Event, Rc<User>)| {
+                        events.borrow_mut().push((event, user.name.clone()))
+                    }
+                }),
+                &[Event::Create, Event::Update, Event::Delete],
+            )
+        });
+        let renamed = |id: &str, name: &str| serde_json::json!({"rename": {"__typename": "User", "id": id, "name": name}});
+        let results = Rc::new(RefCell::new(vec![]));
+        for (id, name) in &[("1", "Janet"), ("2", "John")] {
+            mutate_optimistically::<_, TestNetwork, VanillaCache>(
+                Query::<serde_json::Value>::new(
+                    ast::parse_string("mutation { rename { __typename id name } }").unwrap(),
+                ),
+                &renamed(id, name),
+                Callback::from({
+                    let results = results.clone();
+                    move |result: Result<_, NetworkError>| results.borrow_mut().push(result.is_ok())
+                }),
+            );
+        }
+        let retrieve = |id: &str| {
+            VANILLA_CACHE.with(|cache| {
+                cache
+                    .borrow()
+                    .retrieve::<User>(&id.to_string())
+                    .map(|user| user.name.clone())
+            })
+        };
+        let layers = || VANILLA_CACHE.with(|cache| cache.borrow().layers.len());
+        assert_eq!(retrieve("1").as_deref(), Some("Janet"));
+        assert_eq!(layers(), 2);
+
+        let mut pending = take_pending().into_iter();
+        // the first mutation fails, so its layer is rolled back (and subscribers are told)...
+        (pending.next().unwrap())(Err(NetworkError::Request("timed out".to_string())));
+        assert_eq!(*results.borrow(), vec![false]);
+        assert_eq!(retrieve("1").as_deref(), Some("Jane"));
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                (Event::Update, "Janet".to_string()),
+                (Event::Create, "John".to_string()),
+                (Event::Update, "Jane".to_string()),
+            ]
+        );
+        // ...while the other one is still there until it is confirmed
+        assert_eq!(layers(), 1);
+        assert_eq!(retrieve("2").as_deref(), Some("John"));
+        (pending.next().unwrap())(Ok(renamed("2", "John")));
+        assert_eq!(*results.borrow(), vec![false, true]);
+        assert_eq!(layers(), 0);
+        assert_eq!(retrieve("2").as_deref(), Some("John"));
+    }
+
+    #[test]
+    fn test_cache_only_never_fetches() {
+        assert!(fetch(FetchPolicy::CacheOnly).borrow().is_empty());
+        assert_eq!(respond(serde_json::json!(null)), 0);
+    }
+}
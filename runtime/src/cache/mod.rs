@@ -0,0 +1,2212 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+
+//! The cache, which stores the objects (and query results) fetched from the server so that
+//! components can share them. `VanillaCache` keeps everything in a normalised `store::Store`.
+
+pub mod control;
+pub mod eviction;
+pub mod fetch;
+pub mod storage;
+pub mod store;
+
+use crate::input::Variables;
+use crate::network::Network;
+use crate::objects::Object;
+use crate::query::Query;
+use crate::schema::SchemaMeta;
+use crate::selection::Selection;
+use ast::ast::SelectionSet;
+use ast::prelude::Document;
+use control::{CacheControl, Privacy};
+use eviction::{EvictionPolicy, Limit};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::thread::LocalKey;
+use std::{any::Any, any::TypeId, rc::Rc};
+use storage::{CacheStorage, Snapshot, SnapshotQuery, SnapshotRecord, StorageError};
+use store::{Changes, Private, RecordKey, Store};
+use thiserror::Error as ThisError;
+use yew::Callback;
+
+#[derive(ThisError, Debug)]
+pub enum CacheError {
+    #[error("the provided item was not found in the cache")]
+    NotFound,
+}
+
+/// Something which happened to an object in the cache.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Event {
+    /// The object was added to the cache.
+    Create,
+    /// Some of the object's fields changed (including fields of objects it contains).
+    Update,
+    /// The object was removed from the cache.
+    Delete,
+}
+
+/// Decides which objects a subscriber is interested in.
+pub type Selector<O> = Box<dyn Fn(&O) -> bool>;
+
+pub trait Cache: 'static + Sized {
+    /// Caches an item. Any value objects (see `ValueObject`) which it contains are stored as part
+    /// of it, rather than separately, while any objects it contains are cached in their own right.
+    fn cache<O>(&mut self, item: O)
+    where
+        O: Object + 'static;
+    /// Retrieves an object, if every one of its fields is in the cache.
+    ///
+    /// If the object is stale (i.e. older than the `maxAge` of its `@cacheControl` hints) it is
+    /// still returned, but it is also refetched (see `set_revalidator`) so that the fresh version
+    /// shows up shortly afterwards.
+    fn retrieve<O>(&self, id: &crate::Id) -> Option<Rc<O>>
+    where
+        O: Object + 'static;
+    /// Caches the `data` from a response to `query`.
+    fn write_query<OUT>(&mut self, query: &Query<OUT>, data: &serde_json::Value)
+    where
+        OUT: for<'de> Deserialize<'de>;
+    /// Reads the result of `query` from the cache, if everything it asks for is there.
+    fn read_query<OUT>(&self, query: &Query<OUT>) -> Option<OUT>
+    where
+        OUT: for<'de> Deserialize<'de>;
+    /// Calls `callback` whenever one of `events` happens to an object of type `O` for which
+    /// `selector` returns `true`, e.g.
+    ///
+    /// ```ignore
+    /// let id = user.id().clone();
+    /// cache.subscribe::<User>(
+    ///     Box::new(move |user| user.id() == &id),
+    ///     link.callback(|(event, user)| Msg::UserChanged(event, user)),
+    ///     &[Event::Update, Event::Delete],
+    /// );
+    /// ```
+    ///
+    /// Returns an id which can be passed to `unsubscribe`.
+    fn subscribe<O>(
+        &mut self,
+        selector: Selector<O>,
+        callback: Callback<(Event, Rc<O>)>,
+        events: &[Event],
+    ) -> u64
+    where
+        O: Object + 'static;
+    /// Removes a subscription, so that its callback is never called again. Returns `false` (and
+    /// does nothing else) if there is no subscription with that id, e.g. because it has already
+    /// been removed.
+    fn unsubscribe(&mut self, id: u64) -> bool;
+    /// Evicts an item from the cache.
+    fn remove<O>(&mut self, object: &crate::Id)
+    where
+        O: Object;
+    /// Stops the cache from growing past `limit`, by evicting records in the order `policy` picks
+    /// (see the `eviction` module). Evicted objects are sent to `Event::Delete` subscribers.
+    fn set_eviction<P>(&mut self, policy: P, limit: Limit)
+    where
+        P: EvictionPolicy + 'static;
+    /// Stops an object (and everything it contains) from being evicted, until it is unpinned. An
+    /// object can be pinned several times, in which case it has to be unpinned as many times.
+    /// Removing an object (with `remove`) unpins it.
+    fn pin<O>(&mut self, object: &crate::Id)
+    where
+        O: Object;
+    fn unpin<O>(&mut self, object: &crate::Id)
+    where
+        O: Object;
+    /// Keeps an object (and everything it contains) around when the cache is garbage collected
+    /// (see `gc`), until it is released. Like pins, objects can be retained several times.
+    fn retain<O>(&mut self, object: &crate::Id)
+    where
+        O: Object;
+    fn release<O>(&mut self, object: &crate::Id)
+    where
+        O: Object;
+    /// Removes every record which can't be reached from a pinned object (i.e. one which a
+    /// `QueryProvider` is showing) or a retained one, telling `Event::Delete` subscribers about
+    /// them. Query results are only kept if they are reachable too, so they have to be fetched
+    /// again afterwards.
+    ///
+    /// Unlike eviction this doesn't care how big the cache is – it's meant to be called after
+    /// navigating somewhere else, to throw away exactly the data which is no longer used.
+    fn gc(&mut self) -> Collected;
+    /// Sets the function which refetches stale objects. It is given the result of
+    /// `Object::refetch_object`, and should write the response back into the cache (with
+    /// `write_query`). `revalidate_with` creates one which uses a `Network`.
+    fn set_revalidator(&mut self, revalidate: Box<dyn Fn(Query<serde_json::Value>)>);
+    /// Whether an object is older than the `maxAge` of its (or any of its fields')
+    /// `@cacheControl` hints.
+    fn is_stale<O>(&self, object: &crate::Id) -> bool
+    where
+        O: Object + 'static;
+    /// Tells the cache about the `@cacheControl` hints of every type in a schema (the `SCHEMA`
+    /// generated by `graphql_schema!`). Otherwise the cache only knows about the hints of types
+    /// it has seen (through `cache`, `retrieve` or `subscribe`), so private data of any other type
+    /// isn't tagged when it is written – and isn't removed by `purge_private`.
+    fn use_schema(&mut self, schema: &'static SchemaMeta);
+    /// Removes all of the `@cacheControl(scope: PRIVATE)` data, e.g. when somebody logs out.
+    /// Private data is tagged when it is written: records of private types (and records fetched
+    /// through private fields) are removed completely, while for other records only their private
+    /// fields are removed. Subscribers are told that an object was deleted if it can't be read
+    /// without the data which was removed.
+    fn purge_private(&mut self);
+    /// Removes everything from the cache (e.g. when somebody logs out), telling subscribers that
+    /// every object they could see has been deleted. Subscriptions, pins and settings (such as the
+    /// eviction policy) are kept. Only the partition which is being used is cleared (along with
+    /// the snapshot in the cache's storage, if it has one).
+    fn reset(&mut self);
+    /// Switches to another partition (creating it if it doesn't exist yet). Each partition has its
+    /// own records, so that e.g. switching between accounts never shows one account's objects to
+    /// another. Subscribers are told that everything in the old partition was deleted, and that
+    /// everything in the new one was created.
+    fn switch_partition(&mut self, name: &str);
+    /// The name of the partition which is being used (`DEFAULT_PARTITION` to begin with).
+    fn partition(&self) -> &str;
+    /// Throws away the records of a partition. Removing the partition which is being used resets
+    /// it.
+    fn remove_partition(&mut self, name: &str);
+    /// Sets where the cache is persisted (see the `storage` module), and restores whatever was
+    /// saved there – unless it was saved with a different `schema_version`, in which case it is
+    /// thrown away. Restored records don't replace any which are already in the cache.
+    fn set_storage<S>(&mut self, storage: S, schema_version: &str) -> Result<(), StorageError>
+    where
+        S: CacheStorage + 'static;
+    /// Saves a snapshot of the partition which is being used to the cache's storage.
+    fn persist(&mut self) -> Result<(), StorageError>;
+    /// Takes a snapshot of the partition which is being used: every record, along with the result
+    /// of every query which was written to it (keyed by the query's operation and variables).
+    ///
+    /// This is how data fetched while rendering a page on the server gets to the browser – the
+    /// snapshot is serialized into the page, and then passed to `restore`, so that
+    /// `QueryProvider`s can render straight away rather than fetching everything again (see
+    /// `fetch::FetchPolicy`).
+    fn extract(&self) -> Snapshot;
+    /// Adds the records and query results in a snapshot (usually one from `extract`) to the cache.
+    /// Records which are already in the cache aren't replaced, and (unlike with `set_storage`) the
+    /// snapshot's version isn't checked.
+    fn restore(&mut self, snapshot: Snapshot);
+    /// Writes `data` (what the server is expected to respond to `query` with, which is usually a
+    /// mutation) into a new optimistic layer on top of the cache, so that the UI can show the
+    /// result straight away. Subscribers are told about the changes immediately.
+    ///
+    /// Returns the id of the layer, which should be passed to `commit_optimistic` once the server
+    /// responds, or to `rollback_optimistic` if the request fails (`fetch::mutate_optimistically`
+    /// does both). Layers don't affect each other – removing one leaves any others in place. They
+    /// are thrown away when the cache is reset (or switches partition), and aren't included in
+    /// snapshots.
+    fn write_optimistic<OUT>(&mut self, query: &Query<OUT>, data: &serde_json::Value) -> u64
+    where
+        OUT: for<'de> Deserialize<'de>;
+    /// Replaces an optimistic layer with the server's response (which is written as with
+    /// `write_query`). Subscribers are only told about whatever the response changed. Responses
+    /// for layers which have already been thrown away are ignored, because they might belong to a
+    /// partition which is no longer being used.
+    fn commit_optimistic<OUT>(&mut self, layer: u64, query: &Query<OUT>, data: &serde_json::Value)
+    where
+        OUT: for<'de> Deserialize<'de>;
+    /// Throws away an optimistic layer (e.g. because the request failed), telling subscribers
+    /// about everything which changes back.
+    fn rollback_optimistic(&mut self, layer: u64);
+    fn local_key() -> &'static LocalKey<RefCell<Self>>;
+}
+
+/// A revalidator (see `Cache::set_revalidator`) which sends queries using `NETWORK` and writes the
+/// responses into `CACHE`, e.g.
+///
+/// ```ignore
+/// VANILLA_CACHE.with(|cache| {
+///     cache
+///         .borrow_mut()
+///         .set_revalidator(revalidate_with::<VanillaNetwork, VanillaCache>())
+/// });
+/// ```
+pub fn revalidate_with<NETWORK, CACHE>() -> Box<dyn Fn(Query<serde_json::Value>)>
+where
+    NETWORK: Network + 'static,
+    CACHE: Cache,
+{
+    Box::new(|query| {
+        let written = query.clone();
+        NETWORK::local_key().with(|network| {
+            network.borrow_mut().dispatch(
+                query,
+                Callback::from(move |data: Result<serde_json::Value, _>| {
+                    // (if it failed there's nothing to write, so the object is left as it is)
+                    if let Ok(data) = data {
+                        CACHE::local_key()
+                            .with(|cache| cache.borrow_mut().write_query(&written, &data))
+                    }
+                }),
+            )
+        })
+    })
+}
+
+thread_local! {
+    pub static VANILLA_CACHE: RefCell<VanillaCache> = RefCell::new(VanillaCache::restored());
+    /// The storage which `VANILLA_CACHE` should be restored from when it is created.
+    static VANILLA_STORAGE: RefCell<Option<(Box<dyn CacheStorage>, String)>> =
+        const { RefCell::new(None) };
+    static VANILLA_CACHE_CREATED: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
+
+/// Makes `VANILLA_CACHE` persistent (see `Cache::set_storage`). If this is called before the cache
+/// is first used (e.g. at the start of `main`), the cache is restored lazily, when it is first
+/// used. Problems with the storage are ignored (the cache starts off empty instead).
+pub fn persist_vanilla_cache<S>(storage: S, schema_version: &str)
+where
+    S: CacheStorage + 'static,
+{
+    if VANILLA_CACHE_CREATED.with(|created| created.get()) {
+        VANILLA_CACHE.with(|cache| {
+            let _ = cache.borrow_mut().set_storage(storage, schema_version);
+        });
+    } else {
+        VANILLA_STORAGE.with(|vanilla_storage| {
+            *vanilla_storage.borrow_mut() = Some((Box::new(storage), schema_version.to_string()))
+        });
+    }
+}
+
+/// What was removed by `Cache::gc`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Collected {
+    /// The records which were removed.
+    pub records: Vec<RecordKey>,
+    /// The number of records which are still in the cache.
+    pub kept: usize,
+}
+
+/// A subscription to the cache. `notify` is only called for the right type of object (and the
+/// right events); it checks the selector before calling the subscriber's callback.
+struct Subscription {
+    id: u64,
+    events: Vec<Event>,
+    notify: Box<dyn Fn(Event, Rc<dyn Any>)>,
+}
+
+/// The subscriptions to one Rust type of object.
+struct TypeSubscriptions {
+    /// The `__typename` of the type's records.
+    typename: &'static str,
+    /// Reads an object of this type out of the cache.
+    retrieve: fn(&VanillaCache, &crate::Id) -> Option<Rc<dyn Any>>,
+    subscriptions: Vec<Subscription>,
+}
+
+/// Identifies an object which has been read out of the store – its Rust type and its id (the same
+/// record can be read as several different Rust types, which select different fields).
+type ObjectKey = (TypeId, crate::Id);
+
+/// An object which has been read out of the store, along with the records it was read from.
+struct Memo {
+    object: Rc<dyn Any>,
+    records: Vec<RecordKey>,
+}
+
+/// The records of a partition (see `Cache::switch_partition`).
+#[derive(Default)]
+struct Partition {
+    store: Store,
+    written_at: HashMap<RecordKey, f64>,
+    queries: BTreeMap<String, (Document, Variables)>,
+}
+
+/// A write which hasn't been confirmed by the server yet (see `Cache::write_optimistic`).
+struct Layer {
+    id: u64,
+    document: Document,
+    variables: Variables,
+    data: serde_json::Value,
+}
+
+/// The partition which the cache starts off using.
+pub const DEFAULT_PARTITION: &str = "default";
+
+/// Everything needed to keep the cache below its limit.
+struct Eviction {
+    policy: RefCell<Box<dyn EvictionPolicy>>,
+    limit: Limit,
+    /// The size of each record (see `eviction::record_size`), and the total.
+    sizes: HashMap<RecordKey, usize>,
+    bytes: usize,
+}
+
+impl Eviction {
+    /// Starts keeping track of every record in `store`.
+    fn track_all(&mut self, store: &Store) {
+        // (there's no way of telling which of the records were used most recently, so the policy
+        // sees them in whatever order they come out of the store)
+        let policy = self.policy.get_mut();
+        for key in store.keys() {
+            policy.touch(key);
+            let size = eviction::record_size(key, store.get(key).unwrap());
+            self.sizes.insert(key.clone(), size);
+            self.bytes += size;
+        }
+    }
+
+    /// Stops keeping track of any records.
+    fn forget_all(&mut self) {
+        let policy = self.policy.get_mut();
+        for (key, _) in self.sizes.drain() {
+            policy.forget(&key);
+        }
+        self.bytes = 0;
+    }
+
+    fn touch<'a, I>(&self, records: I)
+    where
+        I: IntoIterator<Item = &'a RecordKey>,
+    {
+        let mut policy = self.policy.borrow_mut();
+        for record in records {
+            policy.touch(record);
+        }
+    }
+
+    fn over_limit(&self, records: usize) -> bool {
+        match self.limit {
+            Limit::Records(limit) => records > limit,
+            Limit::Bytes(limit) => self.bytes > limit,
+        }
+    }
+}
+
+pub struct VanillaCache {
+    store: Store,
+    /// The selection set of each type of object, so that it is only parsed once.
+    selection_sets: RefCell<HashMap<TypeId, Rc<SelectionSet>>>,
+    /// Objects which have already been read out of the store, so that retrieving them again is a
+    /// single lookup rather than a walk through the records (and deserializing the result).
+    objects: RefCell<HashMap<ObjectKey, Memo>>,
+    /// The objects in `objects` which were read from each record, and so have to be thrown away
+    /// when it changes.
+    dependents: RefCell<HashMap<RecordKey, HashSet<ObjectKey>>>,
+    /// Subscriptions, indexed by the type of object they are interested in.
+    subscriptions: HashMap<TypeId, TypeSubscriptions>,
+    subscription_counter: u64,
+    /// This is `None` unless `set_eviction` has been called, in which case the cache can grow as
+    /// big as it likes.
+    eviction: Option<Eviction>,
+    /// The number of times each record has been pinned.
+    pins: HashMap<RecordKey, usize>,
+    /// The number of times each record has been retained.
+    retained: HashMap<RecordKey, usize>,
+    /// The `@cacheControl` hints of each type (by `__typename`) the cache has seen.
+    hints: RefCell<HashMap<&'static str, CacheControl>>,
+    /// Which types and fields are private, so that private data can be tagged when it is written.
+    privacy: RefCell<Privacy>,
+    /// When each record was last written (see `control::now`).
+    written_at: HashMap<RecordKey, f64>,
+    clock: Rc<dyn Fn() -> f64>,
+    revalidator: Option<Box<dyn Fn(Query<serde_json::Value>)>>,
+    /// Objects which are being refetched, so that they aren't refetched again every time they are
+    /// read before the response arrives.
+    revalidating: RefCell<HashSet<RecordKey>>,
+    /// Where the cache is persisted, and the schema version to tag snapshots with.
+    storage: Option<(Box<dyn CacheStorage>, String)>,
+    /// The name of the partition which is being used, and the records of the others.
+    partition: String,
+    partitions: HashMap<String, Partition>,
+    /// The queries which have been written to the cache (keyed by `query_key`), so that their
+    /// results can be extracted.
+    queries: BTreeMap<String, (Document, Variables)>,
+    /// The optimistic layers, in the order they were written. While there are any, `store` holds
+    /// the records with the layers applied, and `base` holds them without.
+    layers: Vec<Layer>,
+    base: Option<Store>,
+    layer_counter: u64,
+}
+
+impl Default for VanillaCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VanillaCache {
+    /// Creates an empty cache. Most applications use the one in `VANILLA_CACHE` rather than
+    /// creating their own.
+    pub fn new() -> Self {
+        Self {
+            store: Store::new(),
+            selection_sets: RefCell::new(HashMap::new()),
+            objects: RefCell::new(HashMap::new()),
+            dependents: RefCell::new(HashMap::new()),
+            subscriptions: HashMap::new(),
+            subscription_counter: 0,
+            eviction: None,
+            pins: HashMap::new(),
+            retained: HashMap::new(),
+            hints: RefCell::new(HashMap::new()),
+            privacy: RefCell::new(Privacy::default()),
+            written_at: HashMap::new(),
+            clock: Rc::new(control::now),
+            revalidator: None,
+            revalidating: RefCell::new(HashSet::new()),
+            storage: None,
+            partition: DEFAULT_PARTITION.to_string(),
+            partitions: HashMap::new(),
+            queries: BTreeMap::new(),
+            layers: vec![],
+            base: None,
+            layer_counter: 0,
+        }
+    }
+
+    /// Creates `VANILLA_CACHE`, restoring it from the storage passed to `persist_vanilla_cache`.
+    fn restored() -> Self {
+        VANILLA_CACHE_CREATED.with(|created| created.set(true));
+        let mut cache = Self::new();
+        if let Some((storage, version)) =
+            VANILLA_STORAGE.with(|storage| storage.borrow_mut().take())
+        {
+            let _ = cache.set_boxed_storage(storage, version);
+        }
+        cache
+    }
+
+    /// Does the work for `Cache::set_storage`.
+    fn set_boxed_storage(
+        &mut self,
+        mut storage: Box<dyn CacheStorage>,
+        version: String,
+    ) -> Result<(), StorageError> {
+        let snapshot = match storage.load() {
+            Ok(Some(snapshot)) => Snapshot::parse(&snapshot, &version),
+            Ok(None) => Ok(None),
+            Err(error) => Err(error),
+        };
+        let stale = matches!(snapshot, Ok(None));
+        self.storage = Some((storage, version));
+        match snapshot? {
+            Some(snapshot) => self.restore_snapshot(snapshot),
+            // snapshots from other versions are no use to anybody
+            None if stale => {
+                if let Some((storage, _)) = &mut self.storage {
+                    storage.clear()?;
+                }
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Adds the records in a snapshot to the cache (or to the partition they came from).
+    fn restore_snapshot(&mut self, snapshot: Snapshot) {
+        if snapshot.partition != self.partition {
+            let partition = self.partitions.entry(snapshot.partition).or_default();
+            for record in snapshot.records {
+                if !partition.store.contains(&record.key) {
+                    if let Some(written_at) = record.written_at {
+                        partition.written_at.insert(record.key.clone(), written_at);
+                    }
+                    if let Some(private) = record.private {
+                        partition.store.tag_private(record.key.clone(), private);
+                    }
+                    partition.store.insert(record.key, record.fields);
+                }
+            }
+            for (document, variables, data) in parse_queries(snapshot.queries) {
+                if partition.store.read_query(&document, &variables).is_none() {
+                    partition.store.write_query_tagged(
+                        &document,
+                        &variables,
+                        &data,
+                        &self.privacy.borrow(),
+                    );
+                }
+                partition
+                    .queries
+                    .insert(query_key(&document, &variables), (document, variables));
+            }
+            return;
+        }
+        let mut restored = vec![];
+        for record in snapshot.records {
+            if self.confirmed().contains(&record.key) {
+                continue;
+            }
+            if let Some(written_at) = record.written_at {
+                self.written_at.insert(record.key.clone(), written_at);
+            }
+            if let Some(eviction) = &mut self.eviction {
+                eviction.policy.get_mut().touch(&record.key);
+                let size = eviction::record_size(&record.key, &record.fields);
+                eviction.sizes.insert(record.key.clone(), size);
+                eviction.bytes += size;
+            }
+            if let Some(base) = &mut self.base {
+                base.insert(record.key.clone(), record.fields.clone());
+                if let Some(private) = &record.private {
+                    base.tag_private(record.key.clone(), private.clone());
+                }
+            }
+            if let Some(private) = record.private {
+                self.store.tag_private(record.key.clone(), private);
+            }
+            self.store.insert(record.key.clone(), record.fields);
+            restored.push(record.key);
+        }
+        self.invalidate(&restored);
+        let created = self.subscribed_objects(&restored);
+        self.emit_objects(Event::Create, created);
+        self.evict(&Changes::default());
+        for (document, variables, data) in parse_queries(snapshot.queries) {
+            // (the query's result is usually in the records which were just restored, but it is
+            // written again if it isn't – e.g. because some of them were evicted)
+            if self.confirmed().read_query(&document, &variables).is_none() {
+                self.write_result(&document, &variables, &data);
+            } else {
+                self.queries
+                    .insert(query_key(&document, &variables), (document, variables));
+            }
+        }
+    }
+
+    /// Writes the result of a query into the store, and remembers the query (so that its result
+    /// can be extracted).
+    fn write_result(
+        &mut self,
+        document: &Document,
+        variables: &Variables,
+        data: &serde_json::Value,
+    ) {
+        self.queries.insert(
+            query_key(document, variables),
+            (document.clone(), variables.clone()),
+        );
+        self.write_with(|store, privacy| {
+            store.write_query_tagged(document, variables, data, privacy)
+        });
+    }
+
+    /// Makes a write to the store – or, if there are optimistic layers, to the records underneath
+    /// them (after which the layers are put back on top).
+    fn write_with<F>(&mut self, write: F)
+    where
+        F: FnOnce(&mut Store, &Privacy) -> Changes,
+    {
+        let mut changes = {
+            let privacy = self.privacy.borrow();
+            write(self.base.as_mut().unwrap_or(&mut self.store), &privacy)
+        };
+        if self.base.is_some() {
+            // (only the records which look different with the layers applied have changed)
+            let relayered = self.relayer();
+            changes.created = relayered.created;
+            changes.updated = relayered.updated;
+        }
+        self.written(&changes);
+        self.changed(&changes);
+        self.evict(&changes);
+    }
+
+    /// Applies the optimistic layers to the records underneath them again (e.g. after a layer has
+    /// been removed). Subscribers are told about any records which have gone, and the changes to
+    /// the rest are returned.
+    fn relayer(&mut self) -> Changes {
+        let store = if self.layers.is_empty() {
+            self.base.take().unwrap_or_default()
+        } else {
+            let mut store = self.base.clone().unwrap_or_default();
+            for layer in &self.layers {
+                store.write_query_tagged(
+                    &layer.document,
+                    &layer.variables,
+                    &layer.data,
+                    &self.privacy.borrow(),
+                );
+            }
+            store
+        };
+        // e.g. objects which only a rolled back layer had created
+        let gone = self
+            .store
+            .keys()
+            .filter(|key| !store.contains(key))
+            .cloned()
+            .collect::<Vec<_>>();
+        let deleted = self.subscribed_objects(&gone);
+        let previous = std::mem::replace(&mut self.store, store);
+        for key in &gone {
+            self.written_at.remove(key);
+            if let Some(eviction) = &mut self.eviction {
+                eviction.policy.get_mut().forget(key);
+                eviction.bytes -= eviction.sizes.remove(key).unwrap_or_default();
+            }
+        }
+        self.invalidate(&gone);
+        self.emit_objects(Event::Delete, deleted);
+        previous.changes_to(&self.store)
+    }
+
+    /// The records which the cache's data is stored in.
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    /// The records without any optimistic layers.
+    fn confirmed(&self) -> &Store {
+        self.base.as_ref().unwrap_or(&self.store)
+    }
+
+    /// The selection set containing every field of `O`, which is used to read and write objects
+    /// of that type.
+    fn selection_set<O>(&self) -> Rc<SelectionSet>
+    where
+        O: Selection + 'static,
+    {
+        self.selection_sets
+            .borrow_mut()
+            .entry(TypeId::of::<O>())
+            .or_insert_with(|| {
+                let document = ast::parse_string(O::selection_set())
+                    .expect("generated selection sets should be valid (please report this)");
+                let operation = document
+                    .operations()
+                    .next()
+                    .expect("a selection set is a valid query");
+                Rc::new(operation.selection_set.clone())
+            })
+            .clone()
+    }
+
+    /// Remembers the `@cacheControl` hints of `O`.
+    fn learn<O>(&self)
+    where
+        O: Object,
+    {
+        if O::CACHE_CONTROL != CacheControl::NONE {
+            self.hints
+                .borrow_mut()
+                .insert(O::GRAPHQL_TYPE, O::CACHE_CONTROL);
+            self.privacy
+                .borrow_mut()
+                .add(O::GRAPHQL_TYPE, &O::CACHE_CONTROL);
+        }
+    }
+
+    /// Removes the private fields of a record (see `Store::remove_private`), telling
+    /// `Event::Delete` subscribers about the objects which can't be read without them.
+    fn remove_private_fields(&mut self, key: &RecordKey) {
+        let before = self.subscribed_objects(std::iter::once(key));
+        self.store.remove_private(key);
+        if let Some(base) = &mut self.base {
+            base.remove_private(key);
+        }
+        self.invalidate(std::iter::once(key));
+        if let (Some(eviction), Some(fields)) = (&mut self.eviction, self.store.get(key)) {
+            let size = eviction::record_size(key, fields);
+            let previous = eviction.sizes.insert(key.clone(), size);
+            eviction.bytes = eviction.bytes + size - previous.unwrap_or_default();
+        }
+        let after = self
+            .subscribed_objects(std::iter::once(key))
+            .into_iter()
+            .map(|(type_id, _)| type_id)
+            .collect::<HashSet<_>>();
+        let deleted = before
+            .into_iter()
+            .filter(|(type_id, _)| !after.contains(type_id))
+            .collect();
+        self.emit_objects(Event::Delete, deleted);
+    }
+
+    /// Whether a record is older than the `maxAge` of its type.
+    fn record_is_stale(&self, record: &RecordKey, now: f64) -> bool {
+        let max_age = match self
+            .hints
+            .borrow()
+            .get(record.typename.as_str())
+            .and_then(CacheControl::max_age)
+        {
+            Some(max_age) => max_age,
+            None => return false,
+        };
+        match self.written_at.get(record) {
+            Some(written_at) => now - written_at > f64::from(max_age) * 1000.0,
+            None => false,
+        }
+    }
+
+    /// Refetches an object which has just been read, if any of the records it was read from are
+    /// stale (stale-while-revalidate).
+    fn revalidate<O>(&self, object: &O, records: &[RecordKey])
+    where
+        O: Object,
+    {
+        let revalidate = match &self.revalidator {
+            Some(revalidate) if !self.hints.borrow().is_empty() => revalidate,
+            _ => return,
+        };
+        let now = (self.clock)();
+        if !records
+            .iter()
+            .any(|record| self.record_is_stale(record, now))
+        {
+            return;
+        }
+        let key = RecordKey::new(O::GRAPHQL_TYPE, object.id().clone());
+        if self.revalidating.borrow_mut().insert(key) {
+            revalidate(object.refetch_object().raw());
+        }
+    }
+
+    /// Notes down when records were written (which makes them fresh again).
+    fn written(&mut self, changes: &Changes) {
+        let now = (self.clock)();
+        let revalidating = self.revalidating.get_mut();
+        for record in &changes.written {
+            revalidating.remove(record);
+            self.written_at.insert(record.clone(), now);
+        }
+    }
+
+    /// Throws away the objects which were read from any of `records`.
+    fn invalidate<'a, I>(&self, records: I)
+    where
+        I: IntoIterator<Item = &'a RecordKey>,
+    {
+        let mut dependents = self.dependents.borrow_mut();
+        let mut objects = self.objects.borrow_mut();
+        for record in records {
+            for object in dependents.remove(record).into_iter().flatten() {
+                objects.remove(&object);
+            }
+        }
+    }
+
+    /// Tells subscribers about the records which a write created or changed (and throws away any
+    /// objects which are out of date).
+    ///
+    /// An object is updated when its own record changes, but also when the record of anything it
+    /// contains does – e.g. a `Post` is updated when the name of its author changes.
+    fn changed(&self, changes: &Changes) {
+        let mut events: Vec<(TypeId, crate::Id, Event)> = vec![];
+        for (type_id, subscriptions) in &self.subscriptions {
+            let records = changes
+                .created
+                .iter()
+                .map(|record| (record, Event::Create))
+                .chain(changes.updated.iter().map(|record| (record, Event::Update)));
+            for (record, event) in records {
+                if record.typename == subscriptions.typename {
+                    events.push((*type_id, record.id.clone(), event));
+                }
+            }
+        }
+        {
+            let dependents = self.dependents.borrow();
+            for record in &changes.updated {
+                for (type_id, id) in dependents.get(record).into_iter().flatten() {
+                    let already = events
+                        .iter()
+                        .any(|(other_type, other_id, _)| other_type == type_id && other_id == id);
+                    if !already && self.subscriptions.contains_key(type_id) {
+                        events.push((*type_id, id.clone(), Event::Update));
+                    }
+                }
+            }
+        }
+        self.invalidate(changes.created.iter().chain(&changes.updated));
+        for (type_id, id, event) in events {
+            self.emit(&type_id, &id, event);
+        }
+    }
+
+    /// Removes a record, telling `Event::Delete` subscribers about it.
+    fn remove_record(&mut self, key: &RecordKey) {
+        if !self.store.contains(key) {
+            return;
+        }
+        // the object is read (as every type which is subscribed to) before it is removed, so that
+        // subscribers can be told what was deleted
+        let deleted = self.subscribed_objects(std::iter::once(key));
+        self.store.remove(key);
+        if let Some(base) = &mut self.base {
+            base.remove(key);
+        }
+        self.pins.remove(key);
+        self.retained.remove(key);
+        self.written_at.remove(key);
+        self.invalidate(std::iter::once(key));
+        if let Some(eviction) = &mut self.eviction {
+            eviction.policy.get_mut().forget(key);
+            eviction.bytes -= eviction.sizes.remove(key).unwrap_or_default();
+        }
+        self.emit_objects(Event::Delete, deleted);
+    }
+
+    /// Reads each of `records` as every type which has been subscribed to (for records of the
+    /// right `__typename`).
+    fn subscribed_objects<'a, I>(&self, records: I) -> Vec<(TypeId, Rc<dyn Any>)>
+    where
+        I: IntoIterator<Item = &'a RecordKey>,
+    {
+        let mut types: HashMap<&str, Vec<(&TypeId, &TypeSubscriptions)>> = HashMap::new();
+        for (type_id, subscriptions) in &self.subscriptions {
+            types
+                .entry(subscriptions.typename)
+                .or_default()
+                .push((type_id, subscriptions));
+        }
+        let mut objects = vec![];
+        for record in records {
+            for (type_id, subscriptions) in
+                types.get(record.typename.as_str()).into_iter().flatten()
+            {
+                if let Some(object) = (subscriptions.retrieve)(self, &record.id) {
+                    objects.push((**type_id, object));
+                }
+            }
+        }
+        objects
+    }
+
+    /// Sends objects (read with `subscribed_objects`) to the subscriptions for `event`.
+    fn emit_objects(&self, event: Event, objects: Vec<(TypeId, Rc<dyn Any>)>) {
+        for (type_id, object) in objects {
+            let subscriptions = match self.subscriptions.get(&type_id) {
+                Some(subscriptions) => subscriptions,
+                None => continue,
+            };
+            for subscription in subscriptions
+                .subscriptions
+                .iter()
+                .filter(|subscription| subscription.events.contains(&event))
+            {
+                (subscription.notify)(event, object.clone());
+            }
+        }
+    }
+
+    /// Replaces every record in the cache with the records of `partition` (which might be empty),
+    /// telling subscribers that everything they could see was deleted and that everything in the
+    /// partition was created. Returns the records which were replaced.
+    fn replace(&mut self, partition: Partition) -> Partition {
+        let deleted = self.subscribed_objects(self.store.keys());
+        if let Some(eviction) = &mut self.eviction {
+            eviction.forget_all();
+            eviction.track_all(&partition.store);
+        }
+        let previous = Partition {
+            store: std::mem::replace(&mut self.store, partition.store),
+            written_at: std::mem::replace(&mut self.written_at, partition.written_at),
+            queries: std::mem::replace(&mut self.queries, partition.queries),
+        };
+        self.objects.get_mut().clear();
+        self.dependents.get_mut().clear();
+        self.revalidating.get_mut().clear();
+        // (the layers were written on top of the records which have just been replaced)
+        self.layers.clear();
+        self.base = None;
+        self.emit_objects(Event::Delete, deleted);
+        let created = self.subscribed_objects(self.store.keys());
+        self.emit_objects(Event::Create, created);
+        previous
+    }
+
+    /// Records the changes made by a write, and then evicts records until the cache is back
+    /// within its limit (if it has one).
+    fn evict(&mut self, changes: &Changes) {
+        let eviction = match &mut self.eviction {
+            Some(eviction) => eviction,
+            None => return,
+        };
+        for key in changes.created.iter().chain(&changes.updated) {
+            eviction.policy.get_mut().touch(key);
+            if let Some(fields) = self.store.get(key) {
+                let size = eviction::record_size(key, fields);
+                let previous = eviction.sizes.insert(key.clone(), size);
+                eviction.bytes = eviction.bytes + size - previous.unwrap_or_default();
+            }
+        }
+        if !eviction.over_limit(self.store.len()) {
+            return;
+        }
+        let pinned = self.store.reachable(self.pins.keys());
+        // the victims are picked before anything is removed, because telling subscribers about
+        // them reads from the cache (which the policy sees)
+        let mut victims = vec![];
+        let (mut records, mut bytes) = (self.store.len(), eviction.bytes);
+        for key in eviction.policy.get_mut().order() {
+            let within = match eviction.limit {
+                Limit::Records(limit) => records <= limit,
+                Limit::Bytes(limit) => bytes <= limit,
+            };
+            if within {
+                break;
+            }
+            if pinned.contains(key) {
+                continue;
+            }
+            records -= 1;
+            bytes -= eviction.sizes.get(key).copied().unwrap_or_default();
+            victims.push(key.clone());
+        }
+        for key in victims {
+            self.remove_record(&key);
+        }
+    }
+
+    /// Reads an object and passes it to the subscriptions for `event`.
+    fn emit(&self, type_id: &TypeId, id: &crate::Id, event: Event) {
+        let subscriptions = match self.subscriptions.get(type_id) {
+            Some(subscriptions) => subscriptions,
+            None => return,
+        };
+        let mut interested = subscriptions
+            .subscriptions
+            .iter()
+            .filter(|subscription| subscription.events.contains(&event))
+            .peekable();
+        if interested.peek().is_none() {
+            return;
+        }
+        // (an object which can't be read, because some of its fields are missing, isn't sent)
+        if let Some(object) = (subscriptions.retrieve)(self, id) {
+            for subscription in interested {
+                (subscription.notify)(event, object.clone());
+            }
+        }
+    }
+}
+
+/// Adds one to the number of times a record has been pinned (or retained).
+fn count_up(counts: &mut HashMap<RecordKey, usize>, record: RecordKey) {
+    *counts.entry(record).or_default() += 1;
+}
+
+/// Takes one away from the number of times a record has been pinned (or retained).
+fn count_down(counts: &mut HashMap<RecordKey, usize>, record: &RecordKey) {
+    if let Some(count) = counts.get_mut(record) {
+        *count -= 1;
+        if *count == 0 {
+            counts.remove(record);
+        }
+    }
+}
+
+/// Identifies the result of a query – the text of its operation, and its variables.
+fn query_key(document: &Document, variables: &Variables) -> String {
+    format!(
+        "{} {}",
+        document,
+        serde_json::to_string(variables).unwrap_or_default()
+    )
+}
+
+/// Parses the operations of the queries in a snapshot (skipping any which can't be parsed).
+fn parse_queries(
+    queries: Vec<SnapshotQuery>,
+) -> impl Iterator<Item = (Document, Variables, serde_json::Value)> {
+    queries.into_iter().filter_map(|query| {
+        let document = ast::parse_string(&query.operation).ok()?;
+        Some((document, query.variables, query.data))
+    })
+}
+
+/// Retrieves an object without its type, so that it can be stored in `TypeSubscriptions`.
+fn retrieve_any<O>(cache: &VanillaCache, id: &crate::Id) -> Option<Rc<dyn Any>>
+where
+    O: Object + 'static,
+{
+    cache.retrieve::<O>(id).map(|object| object as Rc<dyn Any>)
+}
+
+impl Cache for VanillaCache {
+    fn cache<O>(&mut self, item: O)
+    where
+        O: Object + 'static,
+    {
+        self.learn::<O>();
+        let mut value = serde_json::to_value(&item).expect("objects can always be serialized");
+        // objects which use serde's own derives (rather than `#[graphql(serde)]`) don't include
+        // their `__typename` unless they're told to, but it's needed to work out their record
+        if let serde_json::Value::Object(map) = &mut value {
+            map.entry("__typename")
+                .or_insert_with(|| O::GRAPHQL_TYPE.into());
+        }
+        let selection_set = self.selection_set::<O>();
+        self.write_with(|store, privacy| {
+            store.write_object_tagged(&value, &selection_set, privacy).1
+        });
+    }
+
+    fn retrieve<O>(&self, id: &crate::Id) -> Option<Rc<O>>
+    where
+        O: Object + 'static,
+    {
+        self.learn::<O>();
+        let index = (TypeId::of::<O>(), id.clone());
+        if let Some(memo) = self.objects.borrow().get(&index) {
+            if let Some(eviction) = &self.eviction {
+                eviction.touch(&memo.records);
+            }
+            let object = memo.object.clone().downcast::<O>().ok()?;
+            self.revalidate(&*object, &memo.records);
+            return Some(object);
+        }
+        let key = RecordKey::new(O::GRAPHQL_TYPE, id.clone());
+        let (value, records) = self
+            .store
+            .read_object_tracked(&key, &self.selection_set::<O>())?;
+        let object = Rc::new(serde_json::from_value::<O>(value).ok()?);
+        if let Some(eviction) = &self.eviction {
+            eviction.touch(&records);
+        }
+        self.revalidate(&*object, &records);
+        let mut dependents = self.dependents.borrow_mut();
+        for record in &records {
+            dependents
+                .entry(record.clone())
+                .or_default()
+                .insert(index.clone());
+        }
+        self.objects.borrow_mut().insert(
+            index,
+            Memo {
+                object: object.clone(),
+                records,
+            },
+        );
+        Some(object)
+    }
+
+    fn write_query<OUT>(&mut self, query: &Query<OUT>, data: &serde_json::Value)
+    where
+        OUT: for<'de> Deserialize<'de>,
+    {
+        self.write_result(query.document(), query.variables(), data);
+    }
+
+    fn read_query<OUT>(&self, query: &Query<OUT>) -> Option<OUT>
+    where
+        OUT: for<'de> Deserialize<'de>,
+    {
+        let data = match &self.eviction {
+            Some(eviction) => {
+                let (data, records) = self
+                    .store
+                    .read_query_tracked(query.document(), query.variables())?;
+                eviction.touch(&records);
+                data
+            }
+            None => self.store.read_query(query.document(), query.variables())?,
+        };
+        query.deserialize_data(data).ok()
+    }
+
+    fn subscribe<O>(
+        &mut self,
+        selector: Selector<O>,
+        callback: Callback<(Event, Rc<O>)>,
+        events: &[Event],
+    ) -> u64
+    where
+        O: Object + 'static,
+    {
+        self.learn::<O>();
+        let id = self.subscription_counter;
+        self.subscription_counter += 1;
+        self.subscriptions
+            .entry(TypeId::of::<O>())
+            .or_insert_with(|| TypeSubscriptions {
+                typename: O::GRAPHQL_TYPE,
+                retrieve: retrieve_any::<O>,
+                subscriptions: vec![],
+            })
+            .subscriptions
+            .push(Subscription {
+                id,
+                events: events.to_vec(),
+                notify: Box::new(move |event, object| {
+                    if let Ok(object) = object.downcast::<O>() {
+                        if selector(&object) {
+                            callback.emit((event, object));
+                        }
+                    }
+                }),
+            });
+        id
+    }
+
+    fn unsubscribe(&mut self, id: u64) -> bool {
+        let type_id = match self
+            .subscriptions
+            .iter()
+            .find(|(_, subscriptions)| {
+                subscriptions
+                    .subscriptions
+                    .iter()
+                    .any(|subscription| subscription.id == id)
+            })
+            .map(|(type_id, _)| *type_id)
+        {
+            Some(type_id) => type_id,
+            None => return false,
+        };
+        let subscriptions = self.subscriptions.get_mut(&type_id).unwrap();
+        subscriptions
+            .subscriptions
+            .retain(|subscription| subscription.id != id);
+        if subscriptions.subscriptions.is_empty() {
+            self.subscriptions.remove(&type_id);
+        }
+        true
+    }
+
+    fn remove<O>(&mut self, object: &crate::Id)
+    where
+        O: Object,
+    {
+        self.remove_record(&RecordKey::new(O::GRAPHQL_TYPE, object.clone()));
+    }
+
+    fn set_eviction<P>(&mut self, policy: P, limit: Limit)
+    where
+        P: EvictionPolicy + 'static,
+    {
+        let mut eviction = Eviction {
+            policy: RefCell::new(Box::new(policy)),
+            limit,
+            sizes: HashMap::new(),
+            bytes: 0,
+        };
+        eviction.track_all(&self.store);
+        self.eviction = Some(eviction);
+        self.evict(&Changes::default());
+    }
+
+    fn pin<O>(&mut self, object: &crate::Id)
+    where
+        O: Object,
+    {
+        count_up(
+            &mut self.pins,
+            RecordKey::new(O::GRAPHQL_TYPE, object.clone()),
+        );
+    }
+
+    fn unpin<O>(&mut self, object: &crate::Id)
+    where
+        O: Object,
+    {
+        count_down(
+            &mut self.pins,
+            &RecordKey::new(O::GRAPHQL_TYPE, object.clone()),
+        );
+    }
+
+    fn retain<O>(&mut self, object: &crate::Id)
+    where
+        O: Object,
+    {
+        count_up(
+            &mut self.retained,
+            RecordKey::new(O::GRAPHQL_TYPE, object.clone()),
+        );
+    }
+
+    fn release<O>(&mut self, object: &crate::Id)
+    where
+        O: Object,
+    {
+        count_down(
+            &mut self.retained,
+            &RecordKey::new(O::GRAPHQL_TYPE, object.clone()),
+        );
+    }
+
+    fn gc(&mut self) -> Collected {
+        let reachable = self
+            .store
+            .reachable(self.pins.keys().chain(self.retained.keys()));
+        let mut records = self
+            .store
+            .keys()
+            .filter(|record| !reachable.contains(record))
+            .cloned()
+            .collect::<Vec<_>>();
+        records.sort();
+        for record in &records {
+            self.remove_record(record);
+        }
+        Collected {
+            records,
+            kept: self.store.len(),
+        }
+    }
+
+    fn set_revalidator(&mut self, revalidate: Box<dyn Fn(Query<serde_json::Value>)>) {
+        self.revalidator = Some(revalidate);
+    }
+
+    fn is_stale<O>(&self, object: &crate::Id) -> bool
+    where
+        O: Object + 'static,
+    {
+        self.learn::<O>();
+        let now = (self.clock)();
+        match self
+            .objects
+            .borrow()
+            .get(&(TypeId::of::<O>(), object.clone()))
+        {
+            Some(memo) => memo
+                .records
+                .iter()
+                .any(|record| self.record_is_stale(record, now)),
+            None => self.record_is_stale(&RecordKey::new(O::GRAPHQL_TYPE, object.clone()), now),
+        }
+    }
+
+    fn use_schema(&mut self, schema: &'static SchemaMeta) {
+        for type_meta in schema.types {
+            if type_meta.cache_control != CacheControl::NONE {
+                self.hints
+                    .get_mut()
+                    .insert(type_meta.name, type_meta.cache_control);
+                self.privacy
+                    .get_mut()
+                    .add(type_meta.name, &type_meta.cache_control);
+            }
+        }
+    }
+
+    fn purge_private(&mut self) {
+        let mut private = self
+            .store
+            .private_records()
+            .map(|(key, private)| (key.clone(), private.clone()))
+            .collect::<Vec<_>>();
+        private.sort_by(|(a, _), (b, _)| a.cmp(b));
+        for (key, private) in private {
+            match private {
+                Private::Record => self.remove_record(&key),
+                Private::Fields(_) => self.remove_private_fields(&key),
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.replace(Partition::default());
+        if let Some((storage, _)) = &mut self.storage {
+            // (if the storage can't be cleared there's nothing else which can be done about it)
+            let _ = storage.clear();
+        }
+    }
+
+    fn switch_partition(&mut self, name: &str) {
+        if name == self.partition {
+            return;
+        }
+        let partition = self.partitions.remove(name).unwrap_or_default();
+        let previous = self.replace(partition);
+        let previous_name = std::mem::replace(&mut self.partition, name.to_string());
+        self.partitions.insert(previous_name, previous);
+    }
+
+    fn partition(&self) -> &str {
+        &self.partition
+    }
+
+    fn remove_partition(&mut self, name: &str) {
+        if name == self.partition {
+            self.reset();
+        } else {
+            self.partitions.remove(name);
+        }
+    }
+
+    fn set_storage<S>(&mut self, storage: S, schema_version: &str) -> Result<(), StorageError>
+    where
+        S: CacheStorage + 'static,
+    {
+        self.set_boxed_storage(Box::new(storage), schema_version.to_string())
+    }
+
+    fn persist(&mut self) -> Result<(), StorageError> {
+        let snapshot = serde_json::to_string(&self.extract())?;
+        let (storage, _) = self.storage.as_mut().ok_or(StorageError::NoStorage)?;
+        storage.save(&snapshot)
+    }
+
+    fn extract(&self) -> Snapshot {
+        // (optimistic data might never be confirmed, so it isn't included)
+        let store = self.confirmed();
+        Snapshot {
+            // (snapshots which aren't going to the cache's storage don't need a version)
+            version: self
+                .storage
+                .as_ref()
+                .map(|(_, version)| version.clone())
+                .unwrap_or_default(),
+            partition: self.partition.clone(),
+            records: store
+                .keys()
+                .map(|key| SnapshotRecord {
+                    key: key.clone(),
+                    fields: store.get(key).cloned().unwrap_or_default(),
+                    written_at: self.written_at.get(key).copied(),
+                    private: store.private(key).cloned(),
+                })
+                .collect(),
+            // results which can no longer be read (e.g. because some of their records were
+            // evicted) are left out
+            queries: self
+                .queries
+                .values()
+                .filter_map(|(document, variables)| {
+                    Some(SnapshotQuery {
+                        operation: document.to_string(),
+                        variables: variables.clone(),
+                        data: store.read_query(document, variables)?,
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.restore_snapshot(snapshot);
+    }
+
+    fn write_optimistic<OUT>(&mut self, query: &Query<OUT>, data: &serde_json::Value) -> u64
+    where
+        OUT: for<'de> Deserialize<'de>,
+    {
+        if self.base.is_none() {
+            self.base = Some(self.store.clone());
+        }
+        let id = self.layer_counter;
+        self.layer_counter += 1;
+        self.layers.push(Layer {
+            id,
+            document: query.document().clone(),
+            variables: query.variables().clone(),
+            data: data.clone(),
+        });
+        // (the records aren't marked as written, because none of this has come from the server)
+        let changes = self.store.write_query_tagged(
+            query.document(),
+            query.variables(),
+            data,
+            &self.privacy.borrow(),
+        );
+        self.changed(&changes);
+        self.evict(&changes);
+        id
+    }
+
+    fn commit_optimistic<OUT>(&mut self, layer: u64, query: &Query<OUT>, data: &serde_json::Value)
+    where
+        OUT: for<'de> Deserialize<'de>,
+    {
+        if !self.layers.iter().any(|other| other.id == layer) {
+            return;
+        }
+        self.layers.retain(|other| other.id != layer);
+        self.write_result(query.document(), query.variables(), data);
+    }
+
+    fn rollback_optimistic(&mut self, layer: u64) {
+        if !self.layers.iter().any(|other| other.id == layer) {
+            return;
+        }
+        self.layers.retain(|other| other.id != layer);
+        self.write_with(|_, _| Changes::default());
+    }
+
+    fn local_key() -> &'static LocalKey<RefCell<Self>> {
+        &VANILLA_CACHE
+    }
+}
+
+#[cfg(test)]
+mod test_vanilla_cache {
+    use super::*;
+    use serde::Serialize;
+
+    /// Implements `Object` (by hand, since the derive macros can't be used inside this crate).
+    macro_rules! object {
+        ($ident:ident, $typename:literal, $selection_set:literal) => {
+            object!($ident, $typename, $selection_set, CacheControl::NONE);
+        };
+        ($ident:ident, $typename:literal, $selection_set:literal, $cache_control:expr) => {
+            impl Selection for $ident {
+                type Fields = ();
+                fn selection_set_for(_: &()) -> String {
+                    $selection_set.to_string()
+                }
+            }
+            impl Object for $ident {
+                const GRAPHQL_TYPE: &'static str = $typename;
+                const CACHE_CONTROL: CacheControl = $cache_control;
+                fn id(&self) -> &crate::Id {
+                    &self.id
+                }
+                fn refetch_fields(&self, _: &()) -> Query<Self> {
+                    Query::new(
+                        ast::parse_string(concat!(
+                            "query($id: ID!) { node(id: $id) { ... on ",
+                            $typename,
+                            " ",
+                            $selection_set,
+                            " } }"
+                        ))
+                        .unwrap(),
+                    )
+                    .with_response_path(&["node"])
+                    .with_variable("id", self.id())
+                    .unwrap()
+                }
+            }
+        };
+    }
+
+    // `#[graphql(serde)]` includes `__typename` when serializing objects, which this imitates
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    #[serde(tag = "__typename", rename = "User")]
+    struct UserName {
+        id: String,
+        name: String,
+    }
+    object!(UserName, "User", "{ __typename id name }");
+
+    // ...but plain serde derives don't, so the cache has to fill it in
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    struct UserEmail {
+        id: String,
+        email: String,
+    }
+    object!(UserEmail, "User", "{ __typename id email }");
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    #[serde(tag = "__typename")]
+    struct Post {
+        id: String,
+        author: UserName,
+    }
+    object!(
+        Post,
+        "Post",
+        "{ __typename id author { __typename id name } }"
+    );
+
+    fn user(id: &str, name: &str) -> UserName {
+        UserName {
+            id: id.to_string(),
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_different_fields_of_one_object() {
+        let mut cache = VanillaCache::new();
+        cache.cache(user("1", "Jane"));
+        cache.cache(UserEmail {
+            id: "1".to_string(),
+            email: "jane@example.com".to_string(),
+        });
+        assert_eq!(cache.store().len(), 1);
+        assert_eq!(
+            cache.retrieve::<UserName>(&"1".to_string()).unwrap().name,
+            "Jane"
+        );
+        assert_eq!(
+            cache.retrieve::<UserEmail>(&"1".to_string()).unwrap().email,
+            "jane@example.com"
+        );
+        assert!(cache.retrieve::<UserName>(&"2".to_string()).is_none());
+    }
+
+    #[test]
+    fn test_updates_show_up_everywhere() {
+        let mut cache = VanillaCache::new();
+        cache.cache(Post {
+            id: "1".to_string(),
+            author: user("2", "Jane"),
+        });
+        // (this makes sure that objects which have already been retrieved are updated too)
+        let post = cache.retrieve::<Post>(&"1".to_string()).unwrap();
+        assert!(Rc::ptr_eq(
+            &post,
+            &cache.retrieve::<Post>(&"1".to_string()).unwrap()
+        ));
+        cache.cache(user("2", "Janet"));
+        assert_eq!(
+            cache.retrieve::<Post>(&"1".to_string()).unwrap().author,
+            user("2", "Janet")
+        );
+        // the post can't be read once its author has gone
+        cache.remove::<UserName>(&"2".to_string());
+        assert!(cache.retrieve::<Post>(&"1".to_string()).is_none());
+    }
+
+    #[test]
+    fn test_queries() {
+        let mut cache = VanillaCache::new();
+        let query = Query::<UserName>::new(
+            ast::parse_string("query($id: ID!) { node(id: $id) { __typename id name } }").unwrap(),
+        )
+        .with_response_path(&["node"])
+        .with_variable("id", "1")
+        .unwrap();
+        assert!(cache.read_query(&query).is_none());
+        cache.write_query(
+            &query,
+            &serde_json::json!({"node": {"__typename": "User", "id": "1", "name": "Jane"}}),
+        );
+        assert_eq!(cache.read_query(&query), Some(user("1", "Jane")));
+        cache.cache(user("1", "Janet"));
+        assert_eq!(cache.read_query(&query), Some(user("1", "Janet")));
+    }
+
+    type Log<O> = Rc<RefCell<Vec<(Event, O)>>>;
+
+    /// Subscribes to `events`, returning a log of everything the subscription is sent.
+    fn log<O>(cache: &mut VanillaCache, selector: Selector<O>, events: &[Event]) -> (u64, Log<O>)
+    where
+        O: Object + Clone + 'static,
+    {
+        let log = Rc::new(RefCell::new(vec![]));
+        let id = cache.subscribe::<O>(
+            selector,
+            Callback::from({
+                let log = log.clone();
+                move |(event, object): (Event, Rc<O>)| {
+                    log.borrow_mut().push((event, (*object).clone()))
+                }
+            }),
+            events,
+        );
+        (id, log)
+    }
+
+    const ALL: &[Event] = &[Event::Create, Event::Update, Event::Delete];
+
+    #[test]
+    fn test_subscription_events() {
+        let mut cache = VanillaCache::new();
+        let (_, log) = log::<UserName>(&mut cache, Box::new(|_| true), ALL);
+        cache.cache(user("1", "Jane"));
+        cache.cache(user("1", "Janet"));
+        // writing exactly the same thing again doesn't change anything
+        cache.cache(user("1", "Janet"));
+        cache.remove::<UserName>(&"1".to_string());
+        assert_eq!(
+            *log.borrow(),
+            vec![
+                (Event::Create, user("1", "Jane")),
+                (Event::Update, user("1", "Janet")),
+                (Event::Delete, user("1", "Janet")),
+            ]
+        );
+        // removing something which isn't there doesn't tell anybody anything
+        cache.remove::<UserName>(&"1".to_string());
+        assert_eq!(log.borrow().len(), 3);
+    }
+
+    #[test]
+    fn test_subscription_filters() {
+        let mut cache = VanillaCache::new();
+        // selectors can capture things (like the id of the object a component is showing)
+        let id = "1".to_string();
+        let (_, selected) = log::<UserName>(&mut cache, Box::new(move |user| user.id == id), ALL);
+        let (_, updates) = log::<UserName>(&mut cache, Box::new(|_| true), &[Event::Update]);
+        let (_, deletes) = log::<UserName>(&mut cache, Box::new(|_| true), &[Event::Delete]);
+        cache.cache(user("1", "Jane"));
+        cache.cache(user("2", "John"));
+        cache.cache(user("2", "Johnny"));
+        cache.cache(Post {
+            id: "3".to_string(),
+            author: user("2", "Johnny"),
+        });
+        // removing a post isn't the same as removing a user
+        cache.remove::<Post>(&"3".to_string());
+        assert_eq!(*selected.borrow(), vec![(Event::Create, user("1", "Jane"))]);
+        assert_eq!(
+            *updates.borrow(),
+            vec![(Event::Update, user("2", "Johnny"))]
+        );
+        assert!(deletes.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_subscriptions_see_nested_updates() {
+        let mut cache = VanillaCache::new();
+        let (_, log) = log::<Post>(&mut cache, Box::new(|_| true), &[Event::Update]);
+        cache.cache(Post {
+            id: "1".to_string(),
+            author: user("2", "Jane"),
+        });
+        cache.retrieve::<Post>(&"1".to_string()).unwrap();
+        cache.cache(user("2", "Janet"));
+        assert_eq!(
+            *log.borrow(),
+            vec![(
+                Event::Update,
+                Post {
+                    id: "1".to_string(),
+                    author: user("2", "Janet")
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn test_unsubscribe() {
+        let mut cache = VanillaCache::new();
+        let (first, first_log) = log::<UserName>(&mut cache, Box::new(|_| true), ALL);
+        let (_, second_log) = log::<UserName>(&mut cache, Box::new(|_| true), ALL);
+        assert!(cache.unsubscribe(first));
+        cache.cache(user("1", "Jane"));
+        assert!(first_log.borrow().is_empty());
+        assert_eq!(second_log.borrow().len(), 1);
+        // (e.g. when a component is destroyed twice) unsubscribing again doesn't do anything
+        assert!(!cache.unsubscribe(first));
+        assert!(!cache.unsubscribe(42));
+        cache.cache(user("1", "Janet"));
+        assert_eq!(second_log.borrow().len(), 2);
+    }
+
+    #[test]
+    fn test_eviction() {
+        let mut cache = VanillaCache::new();
+        for id in &["1", "2", "3"] {
+            cache.cache(user(id, "Jane"));
+        }
+        let (_, deletes) = log::<UserName>(&mut cache, Box::new(|_| true), &[Event::Delete]);
+        // records which are already in the cache are evicted as soon as there is a limit
+        cache.set_eviction(eviction::Lru::new(), Limit::Records(2));
+        assert_eq!(cache.store().len(), 2);
+        assert_eq!(deletes.borrow().len(), 1);
+        let survivor = cache.store().keys().next().unwrap().id.clone();
+        cache.retrieve::<UserName>(&survivor).unwrap();
+        // the least recently used record goes first
+        cache.cache(user("4", "John"));
+        assert_eq!(cache.store().len(), 2);
+        assert!(cache.retrieve::<UserName>(&survivor).is_some());
+        assert!(cache.retrieve::<UserName>(&"4".to_string()).is_some());
+        assert_eq!(deletes.borrow().len(), 2);
+    }
+
+    #[test]
+    fn test_pinned_records_are_not_evicted() {
+        let mut cache = VanillaCache::new();
+        cache.set_eviction(eviction::Lfu::new(), Limit::Records(2));
+        cache.cache(Post {
+            id: "1".to_string(),
+            author: user("2", "Jane"),
+        });
+        cache.pin::<Post>(&"1".to_string());
+        cache.cache(user("3", "John"));
+        cache.cache(user("4", "Jo"));
+        // the post and its author are both pinned, so everything else is evicted instead
+        assert!(cache.retrieve::<Post>(&"1".to_string()).is_some());
+        assert!(cache.retrieve::<UserName>(&"3".to_string()).is_none());
+        assert!(cache.retrieve::<UserName>(&"4".to_string()).is_none());
+        assert_eq!(cache.store().len(), 2);
+        cache.unpin::<Post>(&"1".to_string());
+        cache.set_eviction(eviction::Lfu::new(), Limit::Records(1));
+        assert_eq!(cache.store().len(), 1);
+    }
+
+    #[test]
+    fn test_byte_limit() {
+        let mut cache = VanillaCache::new();
+        cache.set_eviction(eviction::Lru::new(), Limit::Bytes(1000));
+        for id in 0..100 {
+            cache.cache(user(&id.to_string(), "Jane"));
+        }
+        let size = cache.store().len();
+        assert!(size > 1 && size < 100);
+        // a big record pushes out lots of small ones
+        cache.cache(user("big", &"x".repeat(500)));
+        assert!(cache.store().len() < size);
+        assert!(cache.retrieve::<UserName>(&"big".to_string()).is_some());
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    #[serde(tag = "__typename", rename = "Post")]
+    struct Votes {
+        id: String,
+        votes: i32,
+    }
+    object!(
+        Votes,
+        "Post",
+        "{ __typename id votes }",
+        CacheControl {
+            hint: control::CacheHint {
+                max_age: Some(240),
+                scope: control::CacheScope::Public,
+            },
+            fields: &[(
+                "votes",
+                control::CacheHint {
+                    max_age: Some(30),
+                    scope: control::CacheScope::Public,
+                },
+            )],
+        }
+    );
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    #[serde(tag = "__typename", rename = "Viewer")]
+    struct Viewer {
+        id: String,
+        name: String,
+    }
+    object!(
+        Viewer,
+        "Viewer",
+        "{ __typename id name }",
+        CacheControl {
+            hint: control::CacheHint {
+                max_age: None,
+                scope: control::CacheScope::Private,
+            },
+            fields: &[],
+        }
+    );
+
+    /// A cache whose clock can be moved forwards (by setting the returned number of seconds).
+    fn with_clock() -> (VanillaCache, Rc<std::cell::Cell<f64>>) {
+        let seconds = Rc::new(std::cell::Cell::new(0.0));
+        let mut cache = VanillaCache::new();
+        cache.clock = Rc::new({
+            let seconds = seconds.clone();
+            move || seconds.get() * 1000.0
+        });
+        (cache, seconds)
+    }
+
+    #[test]
+    fn test_stale_while_revalidate() {
+        let (mut cache, seconds) = with_clock();
+        let refetched = Rc::new(RefCell::new(vec![]));
+        cache.set_revalidator(Box::new({
+            let refetched = refetched.clone();
+            move |query: Query<serde_json::Value>| refetched.borrow_mut().push(query)
+        }));
+        let id = "1".to_string();
+        cache.cache(Votes {
+            id: id.clone(),
+            votes: 1,
+        });
+        seconds.set(29.0);
+        assert!(!cache.is_stale::<Votes>(&id));
+        assert_eq!(cache.retrieve::<Votes>(&id).unwrap().votes, 1);
+        assert!(refetched.borrow().is_empty());
+        // `votes` only lasts for 30 seconds, even though posts last for 240
+        seconds.set(31.0);
+        assert!(cache.is_stale::<Votes>(&id));
+        // the stale object is still returned, but it is refetched (only once, however many times
+        // it is read)
+        assert_eq!(cache.retrieve::<Votes>(&id).unwrap().votes, 1);
+        cache.retrieve::<Votes>(&id).unwrap();
+        assert_eq!(refetched.borrow().len(), 1);
+        let query = refetched.borrow_mut().pop().unwrap();
+        assert!(query.to_string().contains("node"));
+        // the response makes it fresh again
+        cache.write_query(
+            &query,
+            &serde_json::json!({"node": {"__typename": "Post", "id": "1", "votes": 2}}),
+        );
+        assert!(!cache.is_stale::<Votes>(&id));
+        assert_eq!(cache.retrieve::<Votes>(&id).unwrap().votes, 2);
+        seconds.set(62.0);
+        cache.retrieve::<Votes>(&id).unwrap();
+        assert_eq!(refetched.borrow().len(), 1);
+    }
+
+    #[test]
+    fn test_purge_private() {
+        let mut cache = VanillaCache::new();
+        cache.cache(Viewer {
+            id: "me".to_string(),
+            name: "Jane".to_string(),
+        });
+        cache.cache(user("1", "John"));
+        let (_, deletes) = log::<Viewer>(&mut cache, Box::new(|_| true), &[Event::Delete]);
+        cache.purge_private();
+        assert!(cache.retrieve::<Viewer>(&"me".to_string()).is_none());
+        assert!(cache.retrieve::<UserName>(&"1".to_string()).is_some());
+        assert_eq!(deletes.borrow().len(), 1);
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    #[serde(tag = "__typename", rename = "User")]
+    struct UserAddress {
+        id: String,
+        name: String,
+        address: String,
+    }
+    object!(
+        UserAddress,
+        "User",
+        "{ __typename id name address }",
+        CacheControl {
+            hint: control::CacheHint::NONE,
+            fields: &[("address", PRIVATE)],
+        }
+    );
+
+    const PRIVATE: control::CacheHint = control::CacheHint {
+        max_age: None,
+        scope: control::CacheScope::Private,
+    };
+
+    #[test]
+    fn test_purge_private_fields() {
+        let mut cache = VanillaCache::new();
+        cache.cache(UserAddress {
+            id: "1".to_string(),
+            name: "Jane".to_string(),
+            address: "1 Main Street".to_string(),
+        });
+        let (_, names) = log::<UserName>(&mut cache, Box::new(|_| true), &[Event::Delete]);
+        let (_, addresses) = log::<UserAddress>(&mut cache, Box::new(|_| true), &[Event::Delete]);
+        cache.purge_private();
+        // only the address is gone – the rest of the user is still there
+        let record = cache.store().get(&RecordKey::new("User", "1")).unwrap();
+        assert!(!record.contains_key("address"));
+        assert_eq!(
+            cache.retrieve::<UserName>(&"1".to_string()).unwrap().name,
+            "Jane"
+        );
+        assert!(cache.retrieve::<UserAddress>(&"1".to_string()).is_none());
+        assert!(names.borrow().is_empty());
+        assert_eq!(addresses.borrow().len(), 1);
+        // (and it isn't purged again)
+        cache.purge_private();
+        assert_eq!(addresses.borrow().len(), 1);
+        assert!(cache.store().private_records().next().is_none());
+    }
+
+    static SCHEMA: SchemaMeta = SchemaMeta {
+        path: "schema.graphql",
+        types: &[
+            crate::schema::TypeMeta {
+                name: "Query",
+                kind: crate::schema::TypeKind::Object,
+                fields: &[],
+                possible_types: &[],
+                enum_values: &[],
+                cache_control: CacheControl {
+                    hint: control::CacheHint::NONE,
+                    fields: &[("viewer", PRIVATE)],
+                },
+            },
+            crate::schema::TypeMeta {
+                name: "Account",
+                kind: crate::schema::TypeKind::Object,
+                fields: &[],
+                possible_types: &[],
+                enum_values: &[],
+                cache_control: CacheControl {
+                    hint: control::CacheHint::NONE,
+                    fields: &[("email", PRIVATE)],
+                },
+            },
+        ],
+        query_type: Some("Query"),
+        mutation_type: None,
+        subscription_type: None,
+    };
+
+    #[test]
+    fn test_purge_private_with_schema() {
+        let mut cache = VanillaCache::new();
+        cache.use_schema(&SCHEMA);
+        // no Rust types for `Account` (or for `viewer`) have been seen, but the schema is enough
+        cache.write_query(
+            &Query::<serde_json::Value>::new(
+                ast::parse_string(
+                    r#"query {
+                        viewer { __typename id name }
+                        account(id: "2") { __typename id name email }
+                    }"#,
+                )
+                .unwrap(),
+            ),
+            &serde_json::json!({
+                "viewer": {"__typename": "User", "id": "1", "name": "Jane"},
+                "account": {"__typename": "Account", "id": "2", "name": "Shop", "email": "a@b.c"}
+            }),
+        );
+        cache.purge_private();
+        // records fetched through a private field are private too
+        assert!(!cache.store().contains(&RecordKey::new("User", "1")));
+        let account = cache.store().get(&RecordKey::new("Account", "2")).unwrap();
+        assert!(account.contains_key("name"));
+        assert!(!account.contains_key("email"));
+        let root = cache
+            .store()
+            .get(&RecordKey::root(ast::ast::OperationType::Query))
+            .unwrap();
+        assert!(!root.contains_key("viewer"));
+        assert!(root.contains_key(r#"account({"id":"2"})"#));
+    }
+
+    #[test]
+    fn test_reset() {
+        let mut cache = VanillaCache::new();
+        cache.set_eviction(eviction::Lru::new(), Limit::Records(10));
+        cache.cache(user("1", "Jane"));
+        cache.cache(Post {
+            id: "2".to_string(),
+            author: user("1", "Jane"),
+        });
+        let (_, users) = log::<UserName>(&mut cache, Box::new(|_| true), ALL);
+        let (_, posts) = log::<Post>(&mut cache, Box::new(|_| true), ALL);
+        cache.reset();
+        assert!(cache.store().is_empty());
+        assert!(cache.retrieve::<UserName>(&"1".to_string()).is_none());
+        assert_eq!(*users.borrow(), vec![(Event::Delete, user("1", "Jane"))]);
+        assert_eq!(posts.borrow().len(), 1);
+        // subscriptions (and the eviction policy) still work afterwards
+        for id in 0..20 {
+            cache.cache(user(&id.to_string(), "John"));
+        }
+        assert_eq!(cache.store().len(), 10);
+        assert_eq!(users.borrow().len(), 1 + 20 + 10);
+    }
+
+    #[test]
+    fn test_partitions() {
+        let mut cache = VanillaCache::new();
+        assert_eq!(cache.partition(), DEFAULT_PARTITION);
+        cache.switch_partition("jane");
+        cache.cache(user("1", "Jane's view"));
+        let (_, log) = log::<UserName>(&mut cache, Box::new(|_| true), ALL);
+        cache.switch_partition("john");
+        // nothing from the other partition is visible
+        assert!(cache.retrieve::<UserName>(&"1".to_string()).is_none());
+        cache.cache(user("1", "John's view"));
+        cache.switch_partition("jane");
+        assert_eq!(
+            cache.retrieve::<UserName>(&"1".to_string()).unwrap().name,
+            "Jane's view"
+        );
+        assert_eq!(
+            *log.borrow(),
+            vec![
+                (Event::Delete, user("1", "Jane's view")),
+                (Event::Create, user("1", "John's view")),
+                (Event::Delete, user("1", "John's view")),
+                (Event::Create, user("1", "Jane's view")),
+            ]
+        );
+        cache.remove_partition("john");
+        cache.switch_partition("john");
+        assert!(cache.store().is_empty());
+        // removing the partition which is being used clears it
+        cache.cache(user("1", "John's view"));
+        cache.remove_partition("john");
+        assert!(cache.store().is_empty());
+        assert_eq!(cache.partition(), "john");
+    }
+
+    #[test]
+    fn test_gc() {
+        let mut cache = VanillaCache::new();
+        let post = |id: &str, author: &str| Post {
+            id: id.to_string(),
+            author: user(author, "Jane"),
+        };
+        cache.cache(post("1", "10"));
+        cache.cache(post("2", "20"));
+        cache.cache(post("3", "30"));
+        cache.pin::<Post>(&"1".to_string());
+        cache.retain::<Post>(&"2".to_string());
+        let (_, deletes) = log::<Post>(&mut cache, Box::new(|_| true), &[Event::Delete]);
+        // the pinned and retained posts are kept (along with their authors), but nothing else
+        assert_eq!(
+            cache.gc(),
+            Collected {
+                records: vec![RecordKey::new("Post", "3"), RecordKey::new("User", "30")],
+                kept: 4,
+            }
+        );
+        assert_eq!(*deletes.borrow(), vec![(Event::Delete, post("3", "30"))]);
+        assert!(cache.retrieve::<Post>(&"2".to_string()).is_some());
+        assert_eq!(
+            cache.gc(),
+            Collected {
+                records: vec![],
+                kept: 4
+            }
+        );
+        cache.release::<Post>(&"2".to_string());
+        cache.unpin::<Post>(&"1".to_string());
+        assert_eq!(cache.gc().records.len(), 4);
+        assert!(cache.store().is_empty());
+    }
+
+    #[test]
+    fn test_storage() {
+        let storage = storage::MemoryStorage::new();
+        let mut cache = VanillaCache::new();
+        assert!(matches!(cache.persist(), Err(StorageError::NoStorage)));
+        cache.set_storage(storage.clone(), "1").unwrap();
+        cache.cache(Post {
+            id: "1".to_string(),
+            author: user("2", "Jane"),
+        });
+        cache.persist().unwrap();
+
+        // e.g. after the page is reloaded
+        let mut restored = VanillaCache::new();
+        restored.cache(user("2", "Janet"));
+        let (_, log) = log::<Post>(&mut restored, Box::new(|_| true), ALL);
+        restored.set_storage(storage.clone(), "1").unwrap();
+        // records which are already in the cache are newer than the ones in the snapshot
+        assert_eq!(
+            *log.borrow(),
+            vec![(
+                Event::Create,
+                Post {
+                    id: "1".to_string(),
+                    author: user("2", "Janet")
+                }
+            )]
+        );
+
+        // snapshots from other versions of the schema are thrown away
+        let mut upgraded = VanillaCache::new();
+        upgraded.set_storage(storage.clone(), "2").unwrap();
+        assert!(upgraded.store().is_empty());
+        assert!(storage.clone().load().unwrap().is_none());
+
+        // and resetting the cache clears its storage too
+        restored.persist().unwrap();
+        restored.reset();
+        assert!(storage.clone().load().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_extract_and_restore() {
+        let query = |id: &str| {
+            Query::<UserName>::new(
+                ast::parse_string("query($id: ID!) { node(id: $id) { __typename id name } }")
+                    .unwrap(),
+            )
+            .with_response_path(&["node"])
+            .with_variable("id", id)
+            .unwrap()
+        };
+        // e.g. while rendering a page on the server
+        let mut server = VanillaCache::new();
+        server.write_query(
+            &query("1"),
+            &serde_json::json!({"node": {"__typename": "User", "id": "1", "name": "Jane"}}),
+        );
+        server.cache(Post {
+            id: "2".to_string(),
+            author: user("3", "John"),
+        });
+        let snapshot = server.extract();
+        assert_eq!(snapshot.records.len(), 4);
+        assert_eq!(snapshot.queries.len(), 1);
+        assert_eq!(snapshot.queries[0].variables["id"], "1");
+        let page = serde_json::to_string(&snapshot).unwrap();
+
+        // ...and then in the browser
+        let mut browser = VanillaCache::new();
+        browser.restore(serde_json::from_str(&page).unwrap());
+        assert_eq!(browser.read_query(&query("1")), Some(user("1", "Jane")));
+        assert_eq!(
+            browser.retrieve::<Post>(&"2".to_string()).unwrap().author,
+            user("3", "John")
+        );
+        assert_eq!(browser.extract().queries, snapshot.queries);
+
+        // query results are enough on their own
+        let mut queries_only = VanillaCache::new();
+        queries_only.restore(Snapshot {
+            records: vec![],
+            ..snapshot
+        });
+        assert_eq!(
+            queries_only.read_query(&query("1")),
+            Some(user("1", "Jane"))
+        );
+        assert!(queries_only.read_query(&query("2")).is_none());
+    }
+
+    #[test]
+    fn test_optimistic_layers() {
+        let mut cache = VanillaCache::new();
+        cache.cache(user("1", "Jane"));
+        let (_, log) = log::<UserName>(&mut cache, Box::new(|_| true), ALL);
+        let rename = Query::<serde_json::Value>::new(
+            ast::parse_string("mutation { rename { __typename id name } }").unwrap(),
+        );
+        let renamed = |id: &str, name: &str| serde_json::json!({"rename": {"__typename": "User", "id": id, "name": name}});
+        let liked = cache.write_optimistic(&rename, &renamed("1", "Jane (liked)"));
+        let created = cache.write_optimistic(&rename, &renamed("2", "John"));
+        // optimistic data isn't included in snapshots
+        assert_eq!(cache.extract().records.len(), 1);
+        // rolling back one layer leaves the other one alone
+        cache.rollback_optimistic(liked);
+        assert!(cache.retrieve::<UserName>(&"2".to_string()).is_some());
+        // writes underneath the layers still show up
+        cache.cache(user("1", "Janet"));
+        cache.commit_optimistic(created, &rename, &renamed("2", "Johnny"));
+        // a layer which created an object deletes it again when it is rolled back
+        let temporary = cache.write_optimistic(&rename, &renamed("3", "Jim"));
+        cache.rollback_optimistic(temporary);
+        assert_eq!(
+            *log.borrow(),
+            vec![
+                (Event::Update, user("1", "Jane (liked)")),
+                (Event::Create, user("2", "John")),
+                (Event::Update, user("1", "Jane")),
+                (Event::Update, user("1", "Janet")),
+                (Event::Update, user("2", "Johnny")),
+                (Event::Create, user("3", "Jim")),
+                (Event::Delete, user("3", "Jim")),
+            ]
+        );
+        assert_eq!(cache.store().len(), 3);
+        // layers can only be removed once
+        cache.rollback_optimistic(liked);
+        cache.commit_optimistic(created, &rename, &renamed("2", "Jo"));
+        assert_eq!(log.borrow().len(), 7);
+        assert_eq!(
+            cache.retrieve::<UserName>(&"2".to_string()).unwrap().name,
+            "Johnny"
+        );
+    }
+
+    #[test]
+    fn test_vanilla_cache_is_restored_lazily() {
+        let storage = storage::MemoryStorage::new();
+        let mut cache = VanillaCache::new();
+        cache.set_storage(storage.clone(), "1").unwrap();
+        cache.cache(user("1", "Jane"));
+        cache.persist().unwrap();
+        // (each test runs on its own thread, so this is a brand new `VANILLA_CACHE`)
+        persist_vanilla_cache(storage, "1");
+        VANILLA_CACHE.with(|cache| {
+            assert_eq!(
+                cache
+                    .borrow()
+                    .retrieve::<UserName>(&"1".to_string())
+                    .unwrap()
+                    .name,
+                "Jane"
+            )
+        });
+    }
+}
@@ -0,0 +1,299 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+
+//! Somewhere to keep the contents of the cache between page loads (or runs of a program).
+//!
+//! A `CacheStorage` only has to store one string – the cache turns its records into a `Snapshot`
+//! (as JSON) and back again. Every snapshot is tagged with a schema version; snapshots from another
+//! version are thrown away rather than restored, because the records in them might not match what
+//! the new version of the application asks for.
+//!
+//! Snapshots are also how a cache which was filled on the server gets to the browser – see
+//! `Cache::extract` and `Cache::restore`.
+//!
+//! `VANILLA_CACHE` can be made persistent with `persist_vanilla_cache`, which restores it lazily
+//! (the first time it is used, rather than when the application starts).
+//!
+//! ```ignore
+//! persist_vanilla_cache(LocalStorage::new("myoxine"), env!("CARGO_PKG_VERSION"));
+//! // ...and then, whenever is convenient (e.g. when the page is hidden)
+//! VANILLA_CACHE.with(|cache| cache.borrow_mut().persist())?;
+//! ```
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use super::store::{Fields, Private, RecordKey};
+use crate::input::Variables;
+
+#[derive(ThisError, Debug)]
+pub enum StorageError {
+    #[error("couldn't access the cache's storage: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("the cache's storage contains something which isn't a snapshot: {0}")]
+    Invalid(#[from] serde_json::Error),
+    #[error("the cache's storage isn't available: {0}")]
+    Unavailable(String),
+    #[error("the cache doesn't have any storage (see `Cache::set_storage`)")]
+    NoStorage,
+}
+
+/// Somewhere a snapshot of the cache can be kept.
+pub trait CacheStorage {
+    /// Reads the snapshot which was saved last, if there is one.
+    fn load(&mut self) -> Result<Option<String>, StorageError>;
+    /// Replaces the saved snapshot.
+    fn save(&mut self, snapshot: &str) -> Result<(), StorageError>;
+    /// Throws away the saved snapshot.
+    fn clear(&mut self) -> Result<(), StorageError>;
+}
+
+/// The contents of the cache (well, of one partition of it), as they are stored.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// The schema version which the records were fetched with.
+    pub version: String,
+    /// The name of the partition the records came from.
+    pub partition: String,
+    pub records: Vec<SnapshotRecord>,
+    /// The results of the queries which were written to the cache. (Snapshots which were saved
+    /// before these were included don't have any.)
+    #[serde(default)]
+    pub queries: Vec<SnapshotQuery>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotRecord {
+    pub key: RecordKey,
+    pub fields: Fields,
+    /// When the record was written (in milliseconds since the Unix epoch), so that it goes stale
+    /// at the right time after it is restored.
+    pub written_at: Option<f64>,
+    /// Which of the record's data is private, so that `Cache::purge_private` still works after
+    /// it is restored.
+    #[serde(default)]
+    pub private: Option<Private>,
+}
+
+/// The result of a query, keyed by the query's operation and variables.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotQuery {
+    /// The text of the query's document.
+    pub operation: String,
+    pub variables: Variables,
+    /// The `data` the server responded with (as it is now, in the cache).
+    pub data: serde_json::Value,
+}
+
+impl Snapshot {
+    /// Parses a snapshot, returning `None` if it was made with a different version of the schema.
+    pub fn parse(snapshot: &str, version: &str) -> Result<Option<Self>, StorageError> {
+        // (the version is checked before the records are parsed, so that the records are allowed
+        // to be in a format this version doesn't understand)
+        #[derive(Deserialize)]
+        struct Version {
+            version: String,
+        }
+        if serde_json::from_str::<Version>(snapshot)?.version != version {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(snapshot)?))
+    }
+}
+
+/// Keeps the snapshot in memory, which is mostly useful for tests. Clones share the same snapshot.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStorage {
+    snapshot: Rc<RefCell<Option<String>>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CacheStorage for MemoryStorage {
+    fn load(&mut self) -> Result<Option<String>, StorageError> {
+        Ok(self.snapshot.borrow().clone())
+    }
+
+    fn save(&mut self, snapshot: &str) -> Result<(), StorageError> {
+        *self.snapshot.borrow_mut() = Some(snapshot.to_string());
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<(), StorageError> {
+        *self.snapshot.borrow_mut() = None;
+        Ok(())
+    }
+}
+
+/// Keeps the snapshot in a file (for applications which don't run in the browser).
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone)]
+pub struct FileStorage {
+    path: std::path::PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FileStorage {
+    pub fn new<P>(path: P) -> Self
+    where
+        P: Into<std::path::PathBuf>,
+    {
+        Self { path: path.into() }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl CacheStorage for FileStorage {
+    fn load(&mut self) -> Result<Option<String>, StorageError> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(snapshot) => Ok(Some(snapshot)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn save(&mut self, snapshot: &str) -> Result<(), StorageError> {
+        if let Some(directory) = self.path.parent() {
+            std::fs::create_dir_all(directory)?;
+        }
+        // the snapshot is written to a temporary file and then moved into place, so that a crash
+        // halfway through doesn't leave half of a snapshot behind
+        let temporary = self.path.with_extension("tmp");
+        std::fs::write(&temporary, snapshot)?;
+        std::fs::rename(&temporary, &self.path)?;
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<(), StorageError> {
+        match std::fs::remove_file(&self.path) {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Keeps the snapshot in the browser's `localStorage`, under `key`. (This needs the `wasm`
+/// feature.)
+///
+/// Note that `localStorage` can usually only hold a few megabytes, so big caches should be given an
+/// eviction policy (see `Cache::set_eviction`). `IndexedDB` can hold more, but it only has an
+/// asynchronous API, which doesn't fit `CacheStorage`.
+#[cfg(feature = "wasm")]
+#[derive(Debug, Clone)]
+pub struct LocalStorage {
+    key: String,
+}
+
+#[cfg(feature = "wasm")]
+impl LocalStorage {
+    pub fn new<K>(key: K) -> Self
+    where
+        K: Into<String>,
+    {
+        Self { key: key.into() }
+    }
+
+    fn storage(&self) -> Result<yew::web_sys::Storage, StorageError> {
+        yew::utils::window()
+            .local_storage()
+            .ok()
+            .flatten()
+            .ok_or_else(|| StorageError::Unavailable("`localStorage` is disabled".to_string()))
+    }
+}
+
+#[cfg(feature = "wasm")]
+impl CacheStorage for LocalStorage {
+    fn load(&mut self) -> Result<Option<String>, StorageError> {
+        self.storage()?
+            .get_item(&self.key)
+            .map_err(|error| StorageError::Unavailable(format!("{:?}", error)))
+    }
+
+    fn save(&mut self, snapshot: &str) -> Result<(), StorageError> {
+        // (this fails if the snapshot doesn't fit)
+        self.storage()?
+            .set_item(&self.key, snapshot)
+            .map_err(|error| StorageError::Unavailable(format!("{:?}", error)))
+    }
+
+    fn clear(&mut self) -> Result<(), StorageError> {
+        self.storage()?
+            .remove_item(&self.key)
+            .map_err(|error| StorageError::Unavailable(format!("{:?}", error)))
+    }
+}
+
+#[cfg(test)]
+mod test_storage {
+    use super::*;
+    use crate::cache::store::StoreValue;
+
+    fn snapshot(version: &str) -> Snapshot {
+        Snapshot {
+            version: version.to_string(),
+            partition: "default".to_string(),
+            records: vec![SnapshotRecord {
+                key: RecordKey::new("User", "1"),
+                fields: vec![(
+                    "name".to_string(),
+                    StoreValue::Scalar(serde_json::json!("Jane")),
+                )]
+                .into_iter()
+                .collect(),
+                written_at: Some(1000.0),
+                private: None,
+            }],
+            queries: vec![],
+        }
+    }
+
+    #[test]
+    fn test_versions() {
+        let saved = serde_json::to_string(&snapshot("1")).unwrap();
+        assert_eq!(Snapshot::parse(&saved, "1").unwrap(), Some(snapshot("1")));
+        assert_eq!(Snapshot::parse(&saved, "2").unwrap(), None);
+        // snapshots from other versions don't even have to have the same format
+        assert_eq!(
+            Snapshot::parse(r#"{"version": "0", "records": 42}"#, "1").unwrap(),
+            None
+        );
+        assert!(Snapshot::parse("nonsense", "1").is_err());
+        // snapshots without any queries are still fine
+        assert_eq!(
+            Snapshot::parse(
+                r#"{"version": "1", "partition": "default", "records": []}"#,
+                "1"
+            )
+            .unwrap()
+            .unwrap()
+            .queries,
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_file_storage() {
+        let path = std::env::temp_dir()
+            .join(format!("myoxine-storage-{}", std::process::id()))
+            .join("cache.json");
+        let mut storage = FileStorage::new(&path);
+        storage.clear().unwrap();
+        assert_eq!(storage.load().unwrap(), None);
+        storage.save("{}").unwrap();
+        assert_eq!(storage.load().unwrap(), Some("{}".to_string()));
+        storage.clear().unwrap();
+        assert_eq!(storage.load().unwrap(), None);
+    }
+}
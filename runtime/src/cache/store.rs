@@ -0,0 +1,1120 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+
+//! The normalised store which `VanillaCache` keeps its data in.
+//!
+//! Rather than storing whole objects, every object which has an identity (i.e. a `__typename` and
+//! an `id`) becomes a *record*, keyed by `(__typename, id)`. A record holds the value of each of
+//! its fields; fields which contain other records hold references to them (so each object is only
+//! stored once, however many places it appears in) and value objects are stored inline. Fields
+//! which take arguments are stored once for every set of arguments they have been fetched with
+//! (e.g. `posts({"first":10})`), so that `posts(first: 10)` and `posts(first: 20)` don't overwrite
+//! each other.
+//!
+//! Results are read back by walking a selection set through the records. This means that two
+//! queries which fetch different fields of the same object both see all of the fields they asked
+//! for, and that a change to a record shows up in every query which includes it.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Display;
+
+use ast::ast::{
+    Directives, Document, Field, FragmentDefinition, OperationType, Selection, SelectionSet,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::control::Privacy;
+use crate::input::Variables;
+
+/// Identifies a record – the `__typename` and `id` of an object.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct RecordKey {
+    pub typename: String,
+    pub id: crate::Id,
+}
+
+impl RecordKey {
+    pub fn new<T, I>(typename: T, id: I) -> Self
+    where
+        T: Into<String>,
+        I: Into<crate::Id>,
+    {
+        Self {
+            typename: typename.into(),
+            id: id.into(),
+        }
+    }
+
+    /// The record which holds the fields of a root operation type (e.g. the fields fetched by
+    /// queries are stored on `RecordKey::root(OperationType::Query)`).
+    pub fn root(operation_type: OperationType) -> Self {
+        let typename = match operation_type {
+            OperationType::Query => "Query",
+            OperationType::Mutation => "Mutation",
+            OperationType::Subscription => "Subscription",
+        };
+        Self::new(typename, "ROOT")
+    }
+}
+
+impl Display for RecordKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.typename, self.id)
+    }
+}
+
+/// The value of a field, as it is stored.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum StoreValue {
+    /// Scalars, enums and `null`.
+    Scalar(Value),
+    /// Another record.
+    Reference(RecordKey),
+    /// A value object (one without an `id`), which is stored as part of whatever contains it.
+    Object(Fields),
+    List(Vec<StoreValue>),
+}
+
+/// The fields of a record (or of a value object), keyed by `field_key`.
+pub type Fields = HashMap<String, StoreValue>;
+
+/// The records which were created or changed by a write.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Changes {
+    pub created: Vec<RecordKey>,
+    pub updated: Vec<RecordKey>,
+    /// Every record which was written, whether or not it changed.
+    pub written: HashSet<RecordKey>,
+    /// Everything in `created` and `updated` (so that checking for duplicates doesn't take longer
+    /// and longer as a big response is written).
+    seen: HashSet<RecordKey>,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty() && self.updated.is_empty()
+    }
+
+    fn record(&mut self, key: &RecordKey, created: bool) {
+        // an object can appear several times in one response
+        if !self.seen.insert(key.clone()) {
+            return;
+        }
+        if created {
+            self.created.push(key.clone());
+        } else {
+            self.updated.push(key.clone());
+        }
+    }
+}
+
+/// The `@cacheControl(scope: PRIVATE)` data in a record, which `Cache::purge_private` removes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Private {
+    /// The whole record (because its type is private, or because it was fetched through a private
+    /// field).
+    Record,
+    /// Some of its fields (by their `field_key`s).
+    Fields(BTreeSet<String>),
+}
+
+/// Records, keyed by `(__typename, id)`.
+#[derive(Debug, Clone, Default)]
+pub struct Store {
+    records: HashMap<RecordKey, Fields>,
+    /// The records which contain private data (see `Privacy`), tagged when they are written.
+    private: HashMap<RecordKey, Private>,
+}
+
+impl Store {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: &RecordKey) -> Option<&Fields> {
+        self.records.get(key)
+    }
+
+    pub fn contains(&self, key: &RecordKey) -> bool {
+        self.records.contains_key(key)
+    }
+
+    /// Adds a record (replacing it, if it is already there).
+    pub fn insert(&mut self, key: RecordKey, fields: Fields) -> Option<Fields> {
+        self.records.insert(key, fields)
+    }
+
+    /// Removes a record. Anything which refers to it can't be read until it is written again.
+    pub fn remove(&mut self, key: &RecordKey) -> Option<Fields> {
+        self.private.remove(key);
+        self.records.remove(key)
+    }
+
+    /// The private data in a record, if it has any.
+    pub fn private(&self, key: &RecordKey) -> Option<&Private> {
+        self.private.get(key)
+    }
+
+    /// Every record which contains private data.
+    pub fn private_records(&self) -> impl Iterator<Item = (&RecordKey, &Private)> {
+        self.private.iter()
+    }
+
+    /// Marks some of a record's data as private (on top of anything which already was).
+    pub fn tag_private(&mut self, key: RecordKey, private: Private) {
+        match (self.private.get_mut(&key), private) {
+            (Some(Private::Record), _) => {}
+            (Some(Private::Fields(fields)), Private::Fields(more)) => fields.extend(more),
+            (_, private) => {
+                self.private.insert(key, private);
+            }
+        }
+    }
+
+    /// Removes the private data from a record – either the whole record, or just its private
+    /// fields. Returns what was removed.
+    pub fn remove_private(&mut self, key: &RecordKey) -> Option<Private> {
+        let private = self.private.remove(key)?;
+        match &private {
+            Private::Record => {
+                self.records.remove(key);
+            }
+            Private::Fields(fields) => {
+                if let Some(record) = self.records.get_mut(key) {
+                    record.retain(|field, _| !fields.contains(field));
+                }
+            }
+        }
+        Some(private)
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &RecordKey> {
+        self.records.keys()
+    }
+
+    /// The records which are different in `other` – i.e. the changes it would take to turn this
+    /// store into `other`, leaving out records which `other` doesn't have.
+    pub fn changes_to(&self, other: &Store) -> Changes {
+        let mut changes = Changes::default();
+        let mut records = other.records.iter().collect::<Vec<_>>();
+        records.sort_by_key(|(key, _)| *key);
+        for (key, fields) in records {
+            match self.records.get(key) {
+                None => changes.record(key, true),
+                Some(existing) if existing != fields => changes.record(key, false),
+                Some(_) => {}
+            }
+        }
+        changes
+    }
+
+    /// Writes an object (serialized as JSON, along with its `__typename`) into the store, using
+    /// `selection_set` to tell which of its fields are objects. Returns the key of the object's
+    /// record, or `None` if it doesn't have an identity.
+    pub fn write_object(
+        &mut self,
+        object: &Value,
+        selection_set: &SelectionSet,
+    ) -> (Option<RecordKey>, Changes) {
+        self.write_object_tagged(object, selection_set, &Privacy::default())
+    }
+
+    /// Like `write_object`, but also tags the private data in the object (see `Store::private`).
+    pub fn write_object_tagged(
+        &mut self,
+        object: &Value,
+        selection_set: &SelectionSet,
+        privacy: &Privacy,
+    ) -> (Option<RecordKey>, Changes) {
+        let variables = Variables::new();
+        let mut writer = Writer {
+            fragments: HashMap::new(),
+            variables: &variables,
+            changes: Changes::default(),
+            privacy,
+            private: false,
+        };
+        let key = match writer.normalise(self, object, Some(selection_set)).0 {
+            StoreValue::Reference(key) => Some(key),
+            _ => None,
+        };
+        (key, writer.changes)
+    }
+
+    /// Reads an object out of the store, in the same form as `write_object` takes it. Returns
+    /// `None` if any of the fields in the selection set are missing.
+    pub fn read_object(&self, key: &RecordKey, selection_set: &SelectionSet) -> Option<Value> {
+        let variables = Variables::new();
+        let reader = Reader {
+            fragments: HashMap::new(),
+            variables: &variables,
+            records: None,
+        };
+        reader.read_record(self, key, selection_set)
+    }
+
+    /// Like `read_object`, but also returns every record which was read (i.e. the records which,
+    /// if they changed, might change the object).
+    pub fn read_object_tracked(
+        &self,
+        key: &RecordKey,
+        selection_set: &SelectionSet,
+    ) -> Option<(Value, Vec<RecordKey>)> {
+        let variables = Variables::new();
+        let reader = Reader {
+            fragments: HashMap::new(),
+            variables: &variables,
+            records: Some(RefCell::new(vec![])),
+        };
+        let value = reader.read_record(self, key, selection_set)?;
+        Some((value, reader.records.unwrap_or_default().into_inner()))
+    }
+
+    /// Every record which can be reached by following references from `roots` (including the
+    /// roots themselves, if they are in the store).
+    pub fn reachable<'a, I>(&self, roots: I) -> HashSet<RecordKey>
+    where
+        I: IntoIterator<Item = &'a RecordKey>,
+    {
+        let mut reached = HashSet::new();
+        let mut queue = roots.into_iter().cloned().collect::<Vec<_>>();
+        while let Some(key) = queue.pop() {
+            if reached.contains(&key) {
+                continue;
+            }
+            let fields = match self.get(&key) {
+                Some(fields) => fields,
+                None => continue,
+            };
+            let mut values = fields.values().collect::<Vec<_>>();
+            while let Some(value) = values.pop() {
+                match value {
+                    StoreValue::Scalar(_) => {}
+                    StoreValue::Reference(key) => queue.push(key.clone()),
+                    StoreValue::Object(fields) => values.extend(fields.values()),
+                    StoreValue::List(items) => values.extend(items),
+                }
+            }
+            reached.insert(key);
+        }
+        reached
+    }
+
+    /// Writes the `data` from a response to the (first) operation in `document`.
+    pub fn write_query(
+        &mut self,
+        document: &Document,
+        variables: &Variables,
+        data: &Value,
+    ) -> Changes {
+        self.write_query_tagged(document, variables, data, &Privacy::default())
+    }
+
+    /// Like `write_query`, but also tags the private data in the response (see
+    /// `Store::private`).
+    pub fn write_query_tagged(
+        &mut self,
+        document: &Document,
+        variables: &Variables,
+        data: &Value,
+        privacy: &Privacy,
+    ) -> Changes {
+        let operation = match document.operations().next() {
+            Some(operation) => operation,
+            None => return Changes::default(),
+        };
+        let data = match data {
+            Value::Object(data) => data,
+            _ => return Changes::default(),
+        };
+        let mut writer = Writer {
+            fragments: fragments(document),
+            variables,
+            changes: Changes::default(),
+            privacy,
+            private: false,
+        };
+        let root = RecordKey::root(operation.operation_type.token);
+        let mut fields = Fields::new();
+        let mut private = BTreeSet::new();
+        writer.write_selection_set(
+            self,
+            &root.typename,
+            &mut fields,
+            &mut private,
+            data,
+            &operation.selection_set,
+        );
+        writer.merge(self, root, fields, private);
+        writer.changes
+    }
+
+    /// Reads the data for the (first) operation in `document` out of the store, in the same shape
+    /// as the `data` in a response from the server. Returns `None` unless everything the operation
+    /// asks for is in the store.
+    pub fn read_query(&self, document: &Document, variables: &Variables) -> Option<Value> {
+        let operation = document.operations().next()?;
+        let reader = Reader {
+            fragments: fragments(document),
+            variables,
+            records: None,
+        };
+        reader.read_record(
+            self,
+            &RecordKey::root(operation.operation_type.token),
+            &operation.selection_set,
+        )
+    }
+
+    /// Like `read_query`, but also returns every record which was read.
+    pub fn read_query_tracked(
+        &self,
+        document: &Document,
+        variables: &Variables,
+    ) -> Option<(Value, Vec<RecordKey>)> {
+        let operation = document.operations().next()?;
+        let reader = Reader {
+            fragments: fragments(document),
+            variables,
+            records: Some(RefCell::new(vec![])),
+        };
+        let value = reader.read_record(
+            self,
+            &RecordKey::root(operation.operation_type.token),
+            &operation.selection_set,
+        )?;
+        Some((value, reader.records.unwrap_or_default().into_inner()))
+    }
+}
+
+fn fragments(document: &Document) -> HashMap<&str, &FragmentDefinition> {
+    document
+        .fragments()
+        .map(|fragment| (fragment.fragment_name.name.0.as_str(), fragment))
+        .collect()
+}
+
+/// The key a field is stored under – its name, followed by its arguments (with the values of any
+/// variables filled in) if it has any.
+pub fn field_key(field: &Field, variables: &Variables) -> String {
+    let arguments = match &field.arguments {
+        Some(arguments) if !arguments.0.is_empty() => arguments,
+        _ => return field.name.0.clone(),
+    };
+    // the arguments are sorted, so that the order they are written in doesn't matter
+    let arguments = arguments
+        .0
+        .iter()
+        .map(|argument| {
+            (
+                argument.name.0.as_str(),
+                argument_value(&argument.value, variables),
+            )
+        })
+        .collect::<BTreeMap<_, _>>();
+    format!(
+        "{}({})",
+        field.name.0,
+        serde_json::to_string(&arguments).expect("JSON values can always be serialized")
+    )
+}
+
+/// Turns the value of an argument into JSON.
+fn argument_value(value: &ast::ast::Value, variables: &Variables) -> Value {
+    use ast::ast::Value as GraphQLValue;
+    match value {
+        GraphQLValue::Variable(variable) => variables
+            .get(&variable.0 .0)
+            .map(sorted)
+            .unwrap_or(Value::Null),
+        GraphQLValue::Int(int) => Value::from(*int),
+        GraphQLValue::Float(float) => Value::from(*float),
+        GraphQLValue::String(string) => Value::String(unquote(string)),
+        GraphQLValue::Boolean(boolean) => Value::Bool(*boolean),
+        GraphQLValue::Null => Value::Null,
+        GraphQLValue::Enum(name) => Value::String(name.0.clone()),
+        GraphQLValue::List(list) => Value::Array(
+            list.0
+                .iter()
+                .map(|value| argument_value(value, variables))
+                .collect(),
+        ),
+        GraphQLValue::Object(object) => {
+            let fields = object
+                .0
+                .iter()
+                .map(|field| {
+                    (
+                        field.name.0.clone(),
+                        argument_value(&field.value, variables),
+                    )
+                })
+                .collect::<BTreeMap<_, _>>();
+            Value::Object(fields.into_iter().collect())
+        }
+    }
+}
+
+/// Sorts the keys of every object in a value (which `serde_json` only does for us if nobody has
+/// turned on its `preserve_order` feature).
+fn sorted(value: &Value) -> Value {
+    match value {
+        Value::Object(object) => Value::Object(
+            object
+                .iter()
+                .map(|(key, value)| (key.clone(), sorted(value)))
+                .collect::<BTreeMap<_, _>>()
+                .into_iter()
+                .collect(),
+        ),
+        Value::Array(values) => Value::Array(values.iter().map(sorted).collect()),
+        other => other.clone(),
+    }
+}
+
+/// The parser keeps the quotes around strings (and any escapes inside them).
+fn unquote(string: &str) -> String {
+    if let Some(block) = string
+        .strip_prefix("\"\"\"")
+        .and_then(|string| string.strip_suffix("\"\"\""))
+    {
+        return block.to_string();
+    }
+    serde_json::from_str(string).unwrap_or_else(|_| string.trim_matches('"').to_string())
+}
+
+/// Checks the `@skip` and `@include` directives on a selection.
+fn included(directives: &Option<Directives>, variables: &Variables) -> bool {
+    let directives = match directives {
+        Some(directives) => directives,
+        None => return true,
+    };
+    directives.0.iter().all(|directive| {
+        let condition = directive
+            .arguments
+            .as_ref()
+            .and_then(|arguments| arguments.0.iter().find(|argument| argument.name.0 == "if"))
+            .map(|argument| argument_value(&argument.value, variables) == Value::Bool(true));
+        match (directive.name.0.as_str(), condition) {
+            ("skip", Some(condition)) => !condition,
+            ("include", Some(condition)) => condition,
+            _ => true,
+        }
+    })
+}
+
+/// The name a field has in a response (its alias, if it has one).
+fn response_key(field: &Field) -> &str {
+    match &field.alias {
+        Some(alias) => &alias.name.0,
+        None => &field.name.0,
+    }
+}
+
+/// The identity of an object in a response, if it has one.
+fn identify(object: &Map<String, Value>) -> Option<RecordKey> {
+    let typename = object.get("__typename")?.as_str()?;
+    let id = match object.get("id")? {
+        Value::String(id) => id.clone(),
+        Value::Number(id) => id.to_string(),
+        _ => return None,
+    };
+    Some(RecordKey::new(typename, id))
+}
+
+struct Writer<'a> {
+    fragments: HashMap<&'a str, &'a FragmentDefinition>,
+    variables: &'a Variables,
+    changes: Changes,
+    privacy: &'a Privacy,
+    /// Whether the writer is inside a private field (so every record it writes is private).
+    private: bool,
+}
+
+impl<'a> Writer<'a> {
+    /// Writes the fields in `selection_set` from `data` (an object of type `typename`) into
+    /// `fields`. Fields which are missing from `data` are skipped (they might be in a fragment on
+    /// another type). The keys of fields which contain private data are added to `private`.
+    fn write_selection_set(
+        &mut self,
+        store: &mut Store,
+        typename: &str,
+        fields: &mut Fields,
+        private: &mut BTreeSet<String>,
+        data: &Map<String, Value>,
+        selection_set: &'a SelectionSet,
+    ) {
+        for selection in &selection_set.0 {
+            match selection {
+                Selection::Field(field) => {
+                    if !included(&field.directives, self.variables) {
+                        continue;
+                    }
+                    if let Some(value) = data.get(response_key(field)) {
+                        let private_field = self.privacy.is_private_field(typename, &field.name.0);
+                        let outside = self.private;
+                        self.private |= private_field;
+                        let (value, contains_private) =
+                            self.normalise(store, value, field.selection_set.as_ref());
+                        self.private = outside;
+                        let key = field_key(field, self.variables);
+                        if private_field || contains_private {
+                            private.insert(key.clone());
+                        }
+                        fields.insert(key, value);
+                    }
+                }
+                Selection::FragmentSpread(spread) => {
+                    if !included(&spread.directives, self.variables) {
+                        continue;
+                    }
+                    if let Some(fragment) = self.fragments.get(spread.fragment_name.name.0.as_str())
+                    {
+                        self.write_selection_set(
+                            store,
+                            typename,
+                            fields,
+                            private,
+                            data,
+                            &fragment.selection_set,
+                        );
+                    }
+                }
+                Selection::InlineFragment(fragment) => {
+                    if included(&fragment.directives, self.variables) {
+                        self.write_selection_set(
+                            store,
+                            typename,
+                            fields,
+                            private,
+                            data,
+                            &fragment.selection_set,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Turns a value from a response into the form it is stored in, writing any records it
+    /// contains into the store. Also returns whether the value itself (rather than the records
+    /// it refers to) contains private data.
+    fn normalise(
+        &mut self,
+        store: &mut Store,
+        value: &Value,
+        selection_set: Option<&'a SelectionSet>,
+    ) -> (StoreValue, bool) {
+        match (value, selection_set) {
+            (Value::Array(values), _) => {
+                let mut contains_private = false;
+                let items = values
+                    .iter()
+                    .map(|value| {
+                        let (item, private) = self.normalise(store, value, selection_set);
+                        contains_private |= private;
+                        item
+                    })
+                    .collect();
+                (StoreValue::List(items), contains_private)
+            }
+            (Value::Object(object), Some(selection_set)) => {
+                // (value objects don't always have their `__typename`, but then there's no way of
+                // telling which of their fields are private anyway)
+                let typename = object
+                    .get("__typename")
+                    .and_then(Value::as_str)
+                    .unwrap_or_default();
+                let mut fields = Fields::new();
+                let mut private = BTreeSet::new();
+                self.write_selection_set(
+                    store,
+                    typename,
+                    &mut fields,
+                    &mut private,
+                    object,
+                    selection_set,
+                );
+                match identify(object) {
+                    Some(key) => {
+                        self.merge(store, key.clone(), fields, private);
+                        (StoreValue::Reference(key), false)
+                    }
+                    None => {
+                        let contains_private =
+                            !private.is_empty() || self.privacy.is_private_type(typename);
+                        (StoreValue::Object(fields), contains_private)
+                    }
+                }
+            }
+            (value, _) => (StoreValue::Scalar(value.clone()), false),
+        }
+    }
+
+    /// Merges some fields into a record (fields which weren't fetched keep their old values), and
+    /// tags it if it is private (or if any of `private` are).
+    fn merge(
+        &mut self,
+        store: &mut Store,
+        key: RecordKey,
+        fields: Fields,
+        private: BTreeSet<String>,
+    ) {
+        if self.private || self.privacy.is_private_type(&key.typename) {
+            store.tag_private(key.clone(), Private::Record);
+        } else if !private.is_empty() {
+            store.tag_private(key.clone(), Private::Fields(private));
+        }
+        self.changes.written.insert(key.clone());
+        match store.records.get_mut(&key) {
+            Some(record) => {
+                let mut changed = false;
+                for (name, value) in fields {
+                    if record.get(&name) != Some(&value) {
+                        record.insert(name, value);
+                        changed = true;
+                    }
+                }
+                if changed {
+                    self.changes.record(&key, false);
+                }
+            }
+            None => {
+                self.changes.record(&key, true);
+                store.records.insert(key, fields);
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    fragments: HashMap<&'a str, &'a FragmentDefinition>,
+    variables: &'a Variables,
+    /// If this is set, every record which is read is added to it.
+    records: Option<RefCell<Vec<RecordKey>>>,
+}
+
+impl<'a> Reader<'a> {
+    fn read_record(
+        &self,
+        store: &Store,
+        key: &RecordKey,
+        selection_set: &SelectionSet,
+    ) -> Option<Value> {
+        let fields = store.get(key)?;
+        if let Some(records) = &self.records {
+            records.borrow_mut().push(key.clone());
+        }
+        let mut output = Map::new();
+        self.read_selection_set(store, fields, &key.typename, selection_set, &mut output)?;
+        Some(Value::Object(output))
+    }
+
+    /// Reads the fields in `selection_set` into `output`, returning `None` if any are missing.
+    fn read_selection_set(
+        &self,
+        store: &Store,
+        fields: &Fields,
+        typename: &str,
+        selection_set: &SelectionSet,
+        output: &mut Map<String, Value>,
+    ) -> Option<()> {
+        for selection in &selection_set.0 {
+            let (type_condition, selection_set) = match selection {
+                Selection::Field(field) => {
+                    if !included(&field.directives, self.variables) {
+                        continue;
+                    }
+                    let value = match fields.get(&field_key(field, self.variables)) {
+                        Some(value) => {
+                            self.denormalise(store, value, field.selection_set.as_ref())?
+                        }
+                        // records don't need to have had `__typename` written explicitly
+                        None if field.name.0 == "__typename" => Value::String(typename.to_string()),
+                        None => return None,
+                    };
+                    output.insert(response_key(field).to_string(), value);
+                    continue;
+                }
+                Selection::FragmentSpread(spread) => {
+                    if !included(&spread.directives, self.variables) {
+                        continue;
+                    }
+                    let fragment = self.fragments.get(spread.fragment_name.name.0.as_str())?;
+                    (
+                        Some(&fragment.type_condition.named_type.0 .0),
+                        &fragment.selection_set,
+                    )
+                }
+                Selection::InlineFragment(fragment) => {
+                    if !included(&fragment.directives, self.variables) {
+                        continue;
+                    }
+                    (
+                        fragment
+                            .type_condition
+                            .as_ref()
+                            .map(|condition| &condition.named_type.0 .0),
+                        &fragment.selection_set,
+                    )
+                }
+            };
+            match type_condition {
+                Some(condition) if condition != typename => {
+                    // the store doesn't know about interfaces and unions, so a fragment on another
+                    // type is read if everything it asks for is there (and left out otherwise)
+                    let mut fragment_output = output.clone();
+                    if self
+                        .read_selection_set(
+                            store,
+                            fields,
+                            typename,
+                            selection_set,
+                            &mut fragment_output,
+                        )
+                        .is_some()
+                    {
+                        *output = fragment_output;
+                    }
+                }
+                _ => self.read_selection_set(store, fields, typename, selection_set, output)?,
+            }
+        }
+        Some(())
+    }
+
+    fn denormalise(
+        &self,
+        store: &Store,
+        value: &StoreValue,
+        selection_set: Option<&SelectionSet>,
+    ) -> Option<Value> {
+        Some(match (value, selection_set) {
+            (StoreValue::Scalar(value), _) => value.clone(),
+            (StoreValue::List(values), _) => Value::Array(
+                values
+                    .iter()
+                    .map(|value| self.denormalise(store, value, selection_set))
+                    .collect::<Option<_>>()?,
+            ),
+            (StoreValue::Reference(key), Some(selection_set)) => {
+                self.read_record(store, key, selection_set)?
+            }
+            (StoreValue::Object(fields), Some(selection_set)) => {
+                let typename = match fields.get("__typename") {
+                    Some(StoreValue::Scalar(Value::String(typename))) => typename.as_str(),
+                    _ => "",
+                };
+                let mut output = Map::new();
+                self.read_selection_set(store, fields, typename, selection_set, &mut output)?;
+                Value::Object(output)
+            }
+            // a selection set is needed to read an object
+            (StoreValue::Reference(_), None) | (StoreValue::Object(_), None) => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test_store {
+    use super::*;
+    use serde_json::json;
+
+    fn document(source: &str) -> Document {
+        ast::parse_string(source).expect("failed to parse")
+    }
+
+    fn variables(value: Value) -> Variables {
+        match value {
+            Value::Object(variables) => variables,
+            _ => panic!("variables should be an object"),
+        }
+    }
+
+    #[test]
+    fn test_objects_are_normalised() {
+        let mut store = Store::new();
+        let query = document(
+            "query { post(id: \"1\") { __typename id title author { __typename id name } } }",
+        );
+        let changes = store.write_query(
+            &query,
+            &Variables::new(),
+            &json!({"post": {
+                "__typename": "Post", "id": "1", "title": "Hello",
+                "author": {"__typename": "User", "id": "2", "name": "Jane"}
+            }}),
+        );
+        assert_eq!(
+            changes.created,
+            vec![
+                RecordKey::new("User", "2"),
+                RecordKey::new("Post", "1"),
+                RecordKey::root(OperationType::Query)
+            ]
+        );
+        assert_eq!(
+            store.get(&RecordKey::new("Post", "1")).unwrap()["author"],
+            StoreValue::Reference(RecordKey::new("User", "2"))
+        );
+        assert_eq!(
+            store.get(&RecordKey::root(OperationType::Query)).unwrap()["post({\"id\":\"1\"})"],
+            StoreValue::Reference(RecordKey::new("Post", "1"))
+        );
+
+        // another query updates the user, which changes the result of the first query
+        let user = document("query { user(id: \"2\") { __typename id name email } }");
+        let changes = store.write_query(
+            &user,
+            &Variables::new(),
+            &json!({"user": {"__typename": "User", "id": "2", "name": "Janet", "email": "j@x"}}),
+        );
+        assert_eq!(changes.created, vec![]);
+        assert_eq!(
+            changes.updated,
+            vec![
+                RecordKey::new("User", "2"),
+                RecordKey::root(OperationType::Query)
+            ]
+        );
+        assert_eq!(
+            store.read_query(&query, &Variables::new()).unwrap()["post"]["author"],
+            json!({"__typename": "User", "id": "2", "name": "Janet"})
+        );
+        let post = document("{ __typename id author { __typename id name } }");
+        let (_, records) = store
+            .read_object_tracked(
+                &RecordKey::new("Post", "1"),
+                &post.operations().next().unwrap().selection_set,
+            )
+            .unwrap();
+        assert_eq!(
+            records,
+            vec![RecordKey::new("Post", "1"), RecordKey::new("User", "2")]
+        );
+    }
+
+    #[test]
+    fn test_arguments_and_aliases() {
+        let mut store = Store::new();
+        let query = document(
+            "query($first: Int) { user(id: \"1\") { __typename id \
+            recent: posts(first: $first, order: NEWEST) { __typename id } \
+            posts(first: 1) { __typename id } } }",
+        );
+        let data = json!({"user": {
+            "__typename": "User", "id": "1",
+            "recent": [{"__typename": "Post", "id": "a"}, {"__typename": "Post", "id": "b"}],
+            "posts": [{"__typename": "Post", "id": "a"}]
+        }});
+        let first = variables(json!({"first": 2}));
+        store.write_query(&query, &first, &data);
+        let user = store.get(&RecordKey::new("User", "1")).unwrap();
+        assert!(user.contains_key("posts({\"first\":1})"));
+        assert!(user.contains_key("posts({\"first\":2,\"order\":\"NEWEST\"})"));
+        assert_eq!(store.read_query(&query, &first), Some(data));
+        // different arguments haven't been fetched yet
+        assert_eq!(
+            store.read_query(&query, &variables(json!({"first": 3}))),
+            None
+        );
+    }
+
+    #[test]
+    fn test_partial_reads() {
+        let mut store = Store::new();
+        let short = document("{ user(id: \"1\") { __typename id name } }");
+        let long = document("{ user(id: \"1\") { __typename id name email } }");
+        store.write_query(
+            &short,
+            &Variables::new(),
+            &json!({"user": {"__typename": "User", "id": "1", "name": "Jane"}}),
+        );
+        assert!(store.read_query(&short, &Variables::new()).is_some());
+        assert!(store.read_query(&long, &Variables::new()).is_none());
+    }
+
+    #[test]
+    fn test_fragments_and_value_objects() {
+        let mut store = Store::new();
+        let query = document(
+            "{ search(term: \"x\") { __typename ...on User { id address { city } } \
+            ...PostFields } } fragment PostFields on Post { id title }",
+        );
+        let data = json!({"search": [
+            {"__typename": "User", "id": "1", "address": {"city": "Paris"}},
+            {"__typename": "Post", "id": "2", "title": "Hi"}
+        ]});
+        store.write_query(&query, &Variables::new(), &data);
+        assert_eq!(store.len(), 3);
+        assert_eq!(
+            store.get(&RecordKey::new("User", "1")).unwrap()["address"],
+            StoreValue::Object(
+                vec![(
+                    "city".to_string(),
+                    StoreValue::Scalar(Value::String("Paris".to_string()))
+                )]
+                .into_iter()
+                .collect()
+            )
+        );
+        assert_eq!(store.read_query(&query, &Variables::new()), Some(data));
+    }
+
+    #[test]
+    fn test_writing_objects() {
+        let mut store = Store::new();
+        let selection_set = &document("{ __typename id name }")
+            .operations()
+            .next()
+            .unwrap()
+            .selection_set
+            .clone();
+        let (key, changes) = store.write_object(
+            &json!({"__typename": "User", "id": "1", "name": "Jane"}),
+            selection_set,
+        );
+        assert_eq!(key, Some(RecordKey::new("User", "1")));
+        assert_eq!(changes.created, vec![RecordKey::new("User", "1")]);
+        let (_, changes) = store.write_object(
+            &json!({"__typename": "User", "id": "1", "name": "Jane"}),
+            selection_set,
+        );
+        assert!(changes.is_empty());
+        assert!(changes.written.contains(&RecordKey::new("User", "1")));
+        assert_eq!(
+            store.read_object(&RecordKey::new("User", "1"), selection_set),
+            Some(json!({"__typename": "User", "id": "1", "name": "Jane"}))
+        );
+    }
+
+    #[test]
+    fn test_private_data_is_tagged() {
+        use super::super::control::{CacheControl, CacheHint, CacheScope};
+        const SETTINGS: CacheControl = CacheControl {
+            hint: CacheHint::NONE,
+            fields: &[(
+                "theme",
+                CacheHint {
+                    max_age: None,
+                    scope: CacheScope::Private,
+                },
+            )],
+        };
+        let mut privacy = Privacy::default();
+        privacy.add("Settings", &SETTINGS);
+        let mut store = Store::new();
+        store.write_query_tagged(
+            &document("{ user { __typename id name settings { __typename theme } } }"),
+            &Variables::new(),
+            &json!({"user": {
+                "__typename": "User",
+                "id": "1",
+                "name": "Jane",
+                "settings": {"__typename": "Settings", "theme": "dark"}
+            }}),
+            &privacy,
+        );
+        // the value object is stored inside the user, so it is the user's field which is tagged
+        let user = RecordKey::new("User", "1");
+        assert_eq!(
+            store.private(&user),
+            Some(&Private::Fields(
+                vec!["settings".to_string()].into_iter().collect()
+            ))
+        );
+        assert_eq!(store.private_records().count(), 1);
+        assert_eq!(
+            store.remove_private(&user),
+            Some(Private::Fields(
+                vec!["settings".to_string()].into_iter().collect()
+            ))
+        );
+        assert!(store.get(&user).unwrap().contains_key("name"));
+        assert!(!store.get(&user).unwrap().contains_key("settings"));
+        assert_eq!(store.remove_private(&user), None);
+    }
+
+    #[test]
+    fn test_changes_to() {
+        let query = document("{ users { __typename id name } }");
+        let mut before = Store::new();
+        before.write_query(
+            &query,
+            &Variables::new(),
+            &json!({"users": [
+                {"__typename": "User", "id": "1", "name": "Jane"},
+                {"__typename": "User", "id": "2", "name": "John"}
+            ]}),
+        );
+        let mut after = before.clone();
+        after.write_query(
+            &query,
+            &Variables::new(),
+            &json!({"users": [
+                {"__typename": "User", "id": "1", "name": "Janet"},
+                {"__typename": "User", "id": "3", "name": "Jim"}
+            ]}),
+        );
+        after.remove(&RecordKey::new("User", "2"));
+        let changes = before.changes_to(&after);
+        assert_eq!(changes.created, vec![RecordKey::new("User", "3")]);
+        assert_eq!(
+            changes.updated,
+            vec![RecordKey::new("Query", "ROOT"), RecordKey::new("User", "1")]
+        );
+    }
+
+    #[test]
+    fn test_reachable() {
+        let mut store = Store::new();
+        let query = document("{ post { __typename id author { __typename id } tags { name } } }");
+        store.write_query(
+            &query,
+            &Variables::new(),
+            &json!({"post": {
+                "__typename": "Post",
+                "id": "1",
+                "author": {"__typename": "User", "id": "2"},
+                "tags": [{"name": "rust"}]
+            }}),
+        );
+        store.write_object(
+            &json!({"__typename": "User", "id": "3"}),
+            &document("{ __typename id }")
+                .operations()
+                .next()
+                .unwrap()
+                .selection_set
+                .clone(),
+        );
+        let reachable = store.reachable(&[RecordKey::new("Post", "1")]);
+        assert_eq!(
+            reachable,
+            vec![RecordKey::new("Post", "1"), RecordKey::new("User", "2")]
+                .into_iter()
+                .collect()
+        );
+        assert_eq!(
+            store
+                .reachable(&[RecordKey::root(OperationType::Query)])
+                .len(),
+            3
+        );
+        assert!(store.reachable(&[RecordKey::new("Post", "9")]).is_empty());
+    }
+}
@@ -0,0 +1,28 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+
+//! Input objects and the variables they are sent to the server as.
+
+use serde::Serialize;
+
+/// The variables sent alongside a query (the `"variables"` key in the JSON request body).
+pub type Variables = serde_json::Map<String, serde_json::Value>;
+
+/// A trait which should be implemented on any type representing a GraphQL input object. As with
+/// `Object`, this is not intended to be implemented manually; use `#[derive(InputObject)]`.
+///
+/// The derive macro serializes fields of type `Option<T>` by leaving them out entirely when they
+/// are `None` (so that the server uses the field's default value, if it has one). To send an
+/// explicit `null` use `Option<Option<T>>` and set the field to `Some(None)`.
+pub trait InputObject: Serialize {
+    /// The name of the input object in the GraphQL schema.
+    const GRAPHQL_TYPE: &'static str;
+    /// Serializes this input object into a value which can be used as a GraphQL variable.
+    fn to_variable(&self) -> Result<serde_json::Value, serde_json::Error> {
+        serde_json::to_value(self)
+    }
+}
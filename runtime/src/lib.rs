@@ -8,12 +8,18 @@ A copy of the license can be found at the root of this Git repository.
 //! intended to make complex applications easy to build and scale. It's currently experimental and
 //! hasn't been used in the context of a serious application, but hopefully that will change soon.
 
+pub use ast;
+pub use serde;
+pub use serde_json;
 pub use yew;
 
 pub mod cache;
+pub mod input;
 pub mod network;
 pub mod objects;
 pub mod query;
 pub mod query_provider;
+pub mod schema;
+pub mod selection;
 
 pub type Id = String;
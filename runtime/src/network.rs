@@ -8,6 +8,7 @@ use crate::query::Query;
 
 use http::Request;
 use js_sys::Array;
+use thiserror::Error as ThisError;
 use yew::Callback;
 
 use std::cell::RefCell;
@@ -34,7 +35,10 @@ pub trait Network: Sized {
     /// Dispatches a query to the internet. The sentence before is phrased like that because you
     /// don't have to use GraphQL for server-client communication – it's also possible to use it for
     /// communication between clients using WebRTC.
-    fn dispatch<OUT>(&mut self, query: Query<OUT>, callback: Callback<OUT>)
+    ///
+    /// `callback` is called with the result once there is one, or with the reason there isn't
+    /// going to be.
+    fn dispatch<OUT>(&mut self, query: Query<OUT>, callback: Callback<Result<OUT, NetworkError>>)
     where
         OUT: for<'de> Deserialize<'de> + 'static;
     fn add_connection_customiser(&mut self, connection_customiser: Box<dyn CustomiseConnection>);
@@ -43,6 +47,18 @@ pub trait Network: Sized {
     fn local_key() -> &'static LocalKey<RefCell<Self>>;
 }
 
+/// Why a query didn't produce a result.
+#[derive(ThisError, Debug, Clone, PartialEq)]
+pub enum NetworkError {
+    /// The request couldn't be sent, or no response came back.
+    #[error("the request failed: {0}")]
+    Request(String),
+    /// The response couldn't be turned into the query's output (e.g. because the server sent back
+    /// errors instead of data).
+    #[error("the response couldn't be read: {0}")]
+    Response(String),
+}
+
 pub trait CustomiseConnection: Debug {
     /// Customises an HTTP request. Types implementing this trait can be passed to an implementor
     /// of `Network` which *should* call this function before dispatching the request.
@@ -59,20 +75,19 @@ pub struct VanillaNetwork {
 /// Turns a Rust request from the `http` crate into a JS `Request` type.
 fn request2js(request: Request<String>) -> yew::web_sys::Request {
     let new_request = yew::web_sys::Request::new_with_str(&request.uri().to_string()).unwrap();
-    yew::web_sys::Request::new_with_request_and_init(
-        &new_request,
-        yew::web_sys::RequestInit::new()
-            .headers(&Array::from_iter(request.headers().iter().map(
-                |(name, value)| {
-                    Array::from_iter(&[
-                        JsValue::from(name.to_string()),
-                        JsValue::from(value.to_str().unwrap()),
-                    ])
-                },
-            )))
-            .body(Some(&request.body().into())),
-    )
-    .expect("failed to build request")
+    let init = yew::web_sys::RequestInit::new();
+    init.set_method(request.method().as_str());
+    init.set_headers(&Array::from_iter(request.headers().iter().map(
+        |(name, value)| {
+            Array::from_iter(&[
+                JsValue::from(name.to_string()),
+                JsValue::from(value.to_str().unwrap()),
+            ])
+        },
+    )));
+    init.set_body(&request.body().into());
+    yew::web_sys::Request::new_with_request_and_init(&new_request, &init)
+        .expect("failed to build request")
 }
 
 thread_local! {
@@ -88,12 +103,14 @@ impl VanillaNetwork {
 }
 
 impl Network for VanillaNetwork {
-    fn dispatch<OUT>(&mut self, query: Query<OUT>, callback: Callback<OUT>)
+    fn dispatch<OUT>(&mut self, query: Query<OUT>, callback: Callback<Result<OUT, NetworkError>>)
     where
         OUT: for<'de> Deserialize<'de> + 'static,
     {
         let mut request = Request::builder()
-            .body(query.to_string())
+            .method("POST")
+            .header("Content-Type", "application/json")
+            .body(query.request_body())
             .expect("failed to build request – this is an internal error and should be reported to https://github.com/d3bate/myoxine");
         if let Some(connection_customiser) = &self.connection_customiser {
             connection_customiser.customise(&mut request);
@@ -102,9 +119,18 @@ impl Network for VanillaNetwork {
         let future = JsFuture::from(yew::utils::window().fetch_with_request(&request));
         wasm_bindgen_futures::spawn_local({
             async move {
-                // fear not, proper error management should be imminent
-                let result = future.await.expect("failed to complete the request");
-                let output = result.into_serde::<OUT>().expect("failed to serialize");
+                let output = match future.await {
+                    Ok(result) => js_sys::JSON::stringify(&result)
+                        .ok()
+                        .and_then(|string| string.as_string())
+                        .ok_or_else(|| NetworkError::Response("it wasn't JSON".to_string()))
+                        .and_then(|string| {
+                            query
+                                .deserialize_response(&string)
+                                .map_err(|error| NetworkError::Response(error.to_string()))
+                        }),
+                    Err(error) => Err(NetworkError::Request(format!("{:?}", error))),
+                };
                 callback.emit(output);
             }
         });
@@ -119,6 +145,60 @@ impl Network for VanillaNetwork {
     }
 }
 
+/// A network which doesn't send anything, but keeps the queries it is given so that tests can
+/// respond to them (or make them fail).
+#[cfg(test)]
+pub(crate) mod test_network {
+    use super::*;
+
+    type Pending = Box<dyn FnOnce(Result<serde_json::Value, NetworkError>)>;
+
+    #[derive(Default)]
+    pub struct TestNetwork {
+        pending: Vec<Pending>,
+    }
+
+    thread_local! {
+        static TEST_NETWORK: RefCell<TestNetwork> = RefCell::new(TestNetwork::default());
+    }
+
+    impl Network for TestNetwork {
+        fn dispatch<OUT>(
+            &mut self,
+            query: Query<OUT>,
+            callback: Callback<Result<OUT, NetworkError>>,
+        ) where
+            OUT: for<'de> Deserialize<'de> + 'static,
+        {
+            self.pending.push(Box::new(move |result| {
+                callback.emit(result.map(|data| query.deserialize_data(data).unwrap()))
+            }));
+        }
+
+        fn add_connection_customiser(&mut self, _: Box<dyn CustomiseConnection>) {}
+
+        fn local_key() -> &'static LocalKey<RefCell<Self>> {
+            &TEST_NETWORK
+        }
+    }
+
+    /// Takes every query which has been sent (in the order they were sent), so that they can be
+    /// responded to one by one.
+    pub fn take_pending() -> Vec<Pending> {
+        TEST_NETWORK.with(|network| std::mem::take(&mut network.borrow_mut().pending))
+    }
+
+    /// Responds to every query which has been sent with `data`, returning how many there were.
+    pub fn respond(data: serde_json::Value) -> usize {
+        let pending = take_pending();
+        let sent = pending.len();
+        for respond in pending {
+            respond(Ok(data.clone()));
+        }
+        sent
+    }
+}
+
 #[cfg(test)]
 mod test_vanilla_network {
     #[test]
@@ -1,8 +1,10 @@
-use std::{fmt::Write};
+use std::fmt::Write;
 
 use serde::{Deserialize, Serialize};
 
+use crate::cache::control::CacheControl;
 use crate::query::Query;
+use crate::selection::{FieldsSelection, Selection};
 
 pub struct Unit {}
 
@@ -14,7 +16,12 @@ impl Write for Unit {
 
 /// A trait which should be implemented on any type representing a GraphQL object. This trait is not
 /// intended for manual implementation; you should instead use our derive macro.
-pub trait Object: for<'de> Deserialize<'de> + Serialize + 'static {
+pub trait Object: for<'de> Deserialize<'de> + Serialize + Selection + 'static {
+    /// The name of the object in the GraphQL schema (which is also its `__typename`).
+    const GRAPHQL_TYPE: &'static str;
+    /// The type's `@cacheControl` hints (if it has any), which say how long objects of this type
+    /// can be cached for.
+    const CACHE_CONTROL: CacheControl = CacheControl::NONE;
     /// This function returns the id of an object. In most cases this will just return the field on
     /// the object used to represent your GraphQL type as a Rust object.
     fn id(&self) -> &crate::Id;
@@ -23,9 +30,35 @@ pub trait Object: for<'de> Deserialize<'de> + Serialize + 'static {
     /// Note that Myoxine makes some assumptions about what you have named the refetch query. This
     /// is in line with the Relay GraphQL schema definition. You may need to rewrite your schema if
     /// it does not fulfil the necessary criteria.
-    fn refetch_object(&self) -> Query<Self>;
+    fn refetch_object(&self) -> Query<Self> {
+        self.refetch_fields(&Self::Fields::all())
+    }
+    /// Refetches only some of the fields of an object (so that components only fetch what they
+    /// render). The fields which aren't requested keep the values they have in `self`, so the
+    /// query still produces a complete object.
+    fn refetch_fields(&self, fields: &Self::Fields) -> Query<Self>;
+    /// Fetches the object with the given id (e.g. one which isn't in the cache yet). Like
+    /// `refetch_object`, this goes through the `node` field of the Relay schema definition.
+    fn fetch(id: &crate::Id) -> Query<Self> {
+        let document = ast::parse_string(format!(
+            "query($id: ID!) {{ node(id: $id) {{ ... on {} {} }} }}",
+            Self::GRAPHQL_TYPE,
+            Self::selection_set()
+        ))
+        .expect("generated selection sets should be valid (please report this)");
+        Query::new(document)
+            .with_response_path(&["node"])
+            .with_variable("id", id)
+            .expect("ids can always be serialized")
+    }
 }
 
+/// A GraphQL object which has no identity of its own (i.e. one which doesn't implement the `Node`
+/// interface), such as an address or an amount of money. Value objects are always stored as part of
+/// whichever object contains them, rather than as separate entries in the cache. Use
+/// `#[derive(ValueObject)]` to implement this.
+pub trait ValueObject: for<'de> Deserialize<'de> + Serialize + Selection + 'static {}
+
 /// This trait is designed to handle collections of objects.
 pub trait ObjectCollection<O>
 where
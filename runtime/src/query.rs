@@ -1,7 +1,11 @@
+use std::fmt::Display;
 use std::marker::PhantomData;
 
 use ast::prelude::Document;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::input::Variables;
 
 /*
 Built with love and the hope that you'll use this software for good by d3bate.
@@ -10,30 +14,241 @@ This file is distributed subject to the terms of the Affero General Public Licen
 A copy of the license can be found at the root of this Git repository.
 */
 
-/// A query containing a string containing the query to be dispatched to the server.
-pub struct Query<OUT>(pub Document, PhantomData<OUT>)
+/// A query containing a string containing the query to be dispatched to the server, and the
+/// variables to be sent along with it.
+///
+/// The last item describes how the response should be turned into an `OUT` (see
+/// `Query::with_response_path` and `Query::with_base`).
+pub struct Query<OUT>(pub Document, pub Variables, PhantomData<OUT>, ResponseShape)
 where
     OUT: for<'de> Deserialize<'de>;
 
+#[derive(Default)]
+struct ResponseShape {
+    /// The path (a list of field names, starting inside `data`) to the part of the response which
+    /// should be deserialized. Empty means the whole of `data`.
+    path: Vec<String>,
+    /// A value which the response is merged into (for queries which only fetch some fields).
+    base: Option<Value>,
+}
+
 impl<OUT> Query<OUT>
 where
     OUT: for<'de> Deserialize<'de>,
 {
     /// Constructs a new query.
     pub fn new(query: Document) -> Self {
-        Self(query, PhantomData)
+        Self(
+            query,
+            Variables::new(),
+            PhantomData,
+            ResponseShape::default(),
+        )
+    }
+    /// Sets the path (starting inside `data`) to the part of the response which should be
+    /// deserialized as the output type. For example, a query like `query { node(id: $id) { ... } }`
+    /// would use `&["node"]`, so that the object itself rather than `{"node": ...}` is deserialized.
+    pub fn with_response_path(mut self, path: &[&str]) -> Self {
+        self.3.path = path.iter().map(|field| field.to_string()).collect();
+        self
+    }
+    /// Merges the response into `base` before deserializing it. This is used by queries which only
+    /// request some of the fields of an object – the fields which weren't requested keep the values
+    /// they have in `base`.
+    pub fn with_base(mut self, base: Value) -> Self {
+        self.3.base = Some(base);
+        self
+    }
+    /// Sets the value of one of the query's variables (e.g. `$id`, which would be passed as `"id"`).
+    ///
+    /// Input objects should be passed in as they are; they know how to serialize themselves.
+    pub fn with_variable<V>(mut self, name: &str, value: &V) -> Result<Self, serde_json::Error>
+    where
+        V: Serialize + ?Sized,
+    {
+        self.1
+            .insert(name.to_string(), serde_json::to_value(value)?);
+        Ok(self)
+    }
+    /// The document containing the query.
+    pub fn document(&self) -> &Document {
+        &self.0
+    }
+    /// The variables which will be sent along with this query.
+    pub fn variables(&self) -> &Variables {
+        &self.1
+    }
+    /// The same query, but producing the whole of the `data` in the response (as JSON), e.g. so
+    /// that it can be written into the cache.
+    pub fn raw(&self) -> Query<Value> {
+        Query(
+            self.0.clone(),
+            self.1.clone(),
+            PhantomData,
+            ResponseShape::default(),
+        )
+    }
+    /// Produces the JSON body which should be sent to a GraphQL server over HTTP.
+    pub fn request_body(&self) -> String {
+        serde_json::json!({
+            "query": self.0.to_string(),
+            "variables": self.1,
+        })
+        .to_string()
     }
     /// Deserialized a JSON stream from the server into the output type of the query.
     pub fn deserialize(result: String) -> Result<OUT, serde_json::Error> {
         serde_json::from_str(&result)
     }
+    /// Deserializes a response from a GraphQL server (i.e. something like `{"data": ...}`) into the
+    /// output type of the query, following the response path. If the server returned errors instead
+    /// of data, those are returned (as a `serde_json::Error`, so that only one error type is needed).
+    pub fn deserialize_response(&self, response: &str) -> Result<OUT, serde_json::Error> {
+        self.deserialize_data(Self::response_data(response)?)
+    }
+    /// Extracts the `data` from a response from a GraphQL server, returning the errors the server
+    /// sent if there isn't any.
+    pub fn response_data(response: &str) -> Result<Value, serde_json::Error> {
+        let mut response: serde_json::Value = serde_json::from_str(response)?;
+        match response.get_mut("data").map(serde_json::Value::take) {
+            Some(serde_json::Value::Null) | None => {
+                let errors = response
+                    .get("errors")
+                    .and_then(|errors| errors.as_array())
+                    .map(|errors| {
+                        errors
+                            .iter()
+                            .filter_map(|error| error.get("message")?.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    })
+                    .unwrap_or_default();
+                Err(serde::de::Error::custom(format!(
+                    "the response contained no data (errors: [{}])",
+                    errors
+                )))
+            }
+            Some(data) => Ok(data),
+        }
+    }
+    /// Deserializes the `data` from a response (or data read from the cache, which has the same
+    /// shape) into the output type of the query, following the response path.
+    pub fn deserialize_data(&self, mut value: Value) -> Result<OUT, serde_json::Error> {
+        for field in &self.3.path {
+            value = match value.get_mut(field) {
+                Some(value) => value.take(),
+                None => return Err(serde::de::Error::missing_field("response path")),
+            };
+        }
+        if let Some(base) = &self.3.base {
+            let mut base = base.clone();
+            merge(&mut base, value);
+            value = base;
+        }
+        serde_json::from_value(value)
+    }
+}
+
+/// Merges `value` into `base`. Objects are merged field by field (recursively); anything else in
+/// `value` replaces what is in `base`.
+fn merge(base: &mut Value, value: Value) {
+    match (base, value) {
+        (Value::Object(base), Value::Object(value)) => {
+            for (key, value) in value {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, value) => *base = value,
+    }
+}
+
+impl<OUT> Clone for Query<OUT>
+where
+    OUT: for<'de> Deserialize<'de>,
+{
+    fn clone(&self) -> Self {
+        Self(
+            self.0.clone(),
+            self.1.clone(),
+            PhantomData,
+            ResponseShape {
+                path: self.3.path.clone(),
+                base: self.3.base.clone(),
+            },
+        )
+    }
 }
 
-impl<OUT> ToString for Query<OUT>
+impl<OUT> Display for Query<OUT>
 where
     OUT: for<'de> Deserialize<'de>,
 {
-    fn to_string(&self) -> String {
-        self.0.to_string()
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test_query {
+    use super::*;
+
+    #[test]
+    fn test_request_body_contains_variables() {
+        let document = ast::parse_string(
+            "mutation($input: CreateUserInput!) { createUser(input: $input) { id } }",
+        )
+        .unwrap();
+        let query = Query::<()>::new(document)
+            .with_variable("input", &serde_json::json!({"username": "jane"}))
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_str(&query.request_body()).unwrap();
+        assert_eq!(body["variables"]["input"]["username"], "jane");
+        assert!(body["query"].as_str().unwrap().contains("createUser"));
+    }
+
+    #[test]
+    fn test_response_path() {
+        let document = ast::parse_string("query($id: ID!) { node(id: $id) { id } }").unwrap();
+        let query = Query::<serde_json::Value>::new(document).with_response_path(&["node"]);
+        assert_eq!(
+            query
+                .deserialize_response(r#"{"data": {"node": {"id": "1"}}}"#)
+                .unwrap(),
+            serde_json::json!({"id": "1"})
+        );
+        assert!(query
+            .deserialize_response(r#"{"data": null, "errors": [{"message": "oops"}]}"#)
+            .unwrap_err()
+            .to_string()
+            .contains("oops"));
+    }
+
+    #[test]
+    fn test_responses_are_merged_into_base() {
+        let document = ast::parse_string("query($id: ID!) { node(id: $id) { id } }").unwrap();
+        let query = Query::<serde_json::Value>::new(document)
+            .with_response_path(&["node"])
+            .with_base(serde_json::json!({
+                "id": "1",
+                "username": "old",
+                "author": {"id": "2", "name": "old"}
+            }));
+        assert_eq!(
+            query
+                .deserialize_response(
+                    r#"{"data": {"node": {"username": "new", "author": {"name": "new"}}}}"#
+                )
+                .unwrap(),
+            serde_json::json!({
+                "id": "1",
+                "username": "new",
+                "author": {"id": "2", "name": "new"}
+            })
+        );
     }
 }
@@ -1,9 +1,11 @@
 use std::{marker::PhantomData, rc::Rc};
 
+use crate::cache::fetch::FetchPolicy;
 use crate::cache::Event;
 use crate::cache::{Cache, VanillaCache};
 use crate::network::{Network, VanillaNetwork};
 use crate::objects::Object;
+use crate::query::Query;
 use serde::Deserialize;
 use std::fmt::Debug;
 use wasm_bindgen::__rt::core::fmt::Formatter;
@@ -17,11 +19,13 @@ where
     CHILD: Component + Clone,
     CHILD::Properties: From<Rc<OUT>> + Debug,
 {
-    #[allow(dead_code)]
     link: ComponentLink<Self>,
     subscription_id: u64,
     props: QueryProviderProps<OUT, CHILD>,
     item: Option<Rc<OUT>>,
+    /// The id of the item which is shown (or which is being loaded, if it was removed from the
+    /// cache).
+    showing: Option<crate::Id>,
     _out: PhantomData<OUT>,
     _network: PhantomData<NETWORK>,
     _cache: PhantomData<CACHE>,
@@ -47,8 +51,14 @@ where
     CHILD: Component + Clone,
     CHILD::Properties: From<Rc<OUT>> + Debug,
 {
-    render: Rc<dyn Fn(OUT) -> Html>,
-    children: yew::ChildrenWithProps<CHILD>,
+    pub render: Rc<dyn Fn(OUT) -> Html>,
+    pub children: yew::ChildrenWithProps<CHILD>,
+    /// The id of the item to show. It is read from the cache if it is there (e.g. because the cache
+    /// was restored from a snapshot made on the server), and fetched if the fetch policy says so.
+    #[prop_or_default]
+    pub id: Option<crate::Id>,
+    #[prop_or_default]
+    pub fetch_policy: FetchPolicy,
 }
 
 impl<OUT, CHILD> Debug for QueryProviderProps<OUT, CHILD>
@@ -62,8 +72,10 @@ where
     }
 }
 
-pub enum QueryProviderMsg {
-    Update,
+pub enum QueryProviderMsg<OUT> {
+    Update(Event, Rc<OUT>),
+    /// The item was fetched from the server (and has been written to the cache).
+    Fetched(Rc<OUT>),
 }
 
 impl<OUT, CHILD, NETWORK, CACHE> Component for QueryProvider<OUT, CHILD, NETWORK, CACHE>
@@ -74,7 +86,7 @@ where
     CHILD: Component + Clone,
     CHILD::Properties: From<Rc<OUT>> + Debug,
 {
-    type Message = QueryProviderMsg;
+    type Message = QueryProviderMsg<OUT>;
 
     type Properties = QueryProviderProps<OUT, CHILD>;
 
@@ -83,51 +95,268 @@ where
             let mut cache = cache.borrow_mut();
 
             cache.subscribe::<OUT>(
-                &|&_| true,
-                link.callback(|_| Self::Message::Update),
-                Event::Update,
+                Box::new(|_| true),
+                link.callback(|(event, item)| Self::Message::Update(event, item)),
+                &[Event::Create, Event::Update, Event::Delete],
             )
         });
-        Self {
+        let mut provider = Self {
             link,
             item: None,
+            showing: None,
             subscription_id,
             props,
             _out: PhantomData,
             _cache: PhantomData,
             _network: PhantomData,
-        }
+        };
+        provider.load();
+        provider
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
-            Self::Message::Update => true,
+            Self::Message::Update(event, item) => {
+                if self.showing.as_ref() != Some(item.id()) {
+                    return false;
+                }
+                // (the cache isn't touched here, because this can be called while something is
+                // writing to it – the item's id hasn't changed, so it is still pinned)
+                match event {
+                    // e.g. because the cache was reset, in which case the item is loaded again (if
+                    // the fetch policy allows it – otherwise it is shown again as soon as it turns
+                    // up in the cache)
+                    Event::Delete => {
+                        self.item = None;
+                        refetch_deleted::<OUT, NETWORK, CACHE>(
+                            &item,
+                            self.props.fetch_policy,
+                            self.link.callback(QueryProviderMsg::Fetched),
+                        );
+                    }
+                    _ => self.item = Some(item),
+                }
+                true
+            }
+            // (writing the item to the cache only tells this component about it if something
+            // changed, which isn't the case if it was already there)
+            Self::Message::Fetched(item) => {
+                if self.showing.as_ref() != Some(item.id()) {
+                    return false;
+                }
+                self.item = Some(item);
+                true
+            }
         }
     }
 
-    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
-        false
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        let reload = props.id != self.props.id;
+        self.props = props;
+        if reload {
+            self.load();
+        }
+        reload
     }
 
     fn view(&self) -> Html {
         if let Some(item) = &self.item {
-            return html! {
+            html! {
                 {for self.props.children.iter().map(|mut child| {
                     child.props = From::from(item.clone());
                     child
                 })}
-            };
+            }
         } else {
-            return html! {
+            html! {
                 <h1>{"Loading..."}</h1>
-            };
+            }
         }
     }
 
     fn destroy(&mut self) {
+        self.show(None);
         CACHE::local_key().with(|cache| {
             let mut cache = cache.borrow_mut();
             cache.unsubscribe(self.subscription_id);
         });
     }
 }
+
+impl<OUT, CHILD, NETWORK, CACHE> QueryProvider<OUT, CHILD, NETWORK, CACHE>
+where
+    OUT: for<'de> Deserialize<'de> + 'static + Clone + Object,
+    NETWORK: Network + 'static,
+    CACHE: Cache + 'static,
+    CHILD: Component + Clone,
+    CHILD::Properties: From<Rc<OUT>> + Debug,
+{
+    /// Shows the item whose id was passed in the props, reading it from the cache and fetching it
+    /// from the server as the fetch policy says.
+    fn load(&mut self) {
+        let id = match self.props.id.clone() {
+            Some(id) => id,
+            None => return self.show(None),
+        };
+        let policy = self.props.fetch_policy;
+        let cached = if policy.reads_cache() {
+            CACHE::local_key().with(|cache| cache.borrow().retrieve::<OUT>(&id))
+        } else {
+            None
+        };
+        let fetch = policy.fetches(cached.is_some());
+        match cached {
+            Some(item) => self.show(Some(item)),
+            // (the item is still shown as soon as it turns up in the cache)
+            None => {
+                self.show(None);
+                self.pin(Some(id.clone()));
+            }
+        }
+        if fetch {
+            fetch_item::<OUT, NETWORK, CACHE>(
+                OUT::fetch(&id),
+                self.link.callback(QueryProviderMsg::Fetched),
+            );
+        }
+    }
+
+    /// Changes the item which is shown.
+    fn show(&mut self, item: Option<Rc<OUT>>) {
+        self.pin(item.as_ref().map(|item| item.id().clone()));
+        self.item = item;
+    }
+
+    /// Changes the id of the item which is shown. Whatever is being shown is pinned in the cache,
+    /// so that it isn't evicted while it is on the screen.
+    fn pin(&mut self, id: Option<crate::Id>) {
+        if id != self.showing {
+            CACHE::local_key().with(|cache| {
+                let mut cache = cache.borrow_mut();
+                if let Some(id) = &id {
+                    cache.pin::<OUT>(id);
+                }
+                if let Some(previous) = &self.showing {
+                    cache.unpin::<OUT>(previous);
+                }
+            });
+        }
+        self.showing = id;
+    }
+}
+
+/// Fetches an item from the server, and puts it in the cache (and then sends it to `fetched`).
+fn fetch_item<OUT, NETWORK, CACHE>(query: Query<OUT>, fetched: Callback<Rc<OUT>>)
+where
+    OUT: Object + Clone,
+    NETWORK: Network + 'static,
+    CACHE: Cache + 'static,
+{
+    NETWORK::local_key().with(|network| {
+        network.borrow_mut().dispatch(
+            query,
+            Callback::from(move |item: Result<OUT, _>| {
+                // (if it failed, the loading state is shown until the item turns up some other way)
+                if let Ok(item) = item {
+                    let item = Rc::new(item);
+                    CACHE::local_key().with(|cache| cache.borrow_mut().cache((*item).clone()));
+                    fetched.emit(item)
+                }
+            }),
+        )
+    });
+}
+
+/// Fetches an item which was deleted from the cache again, unless `policy` says that things which
+/// aren't in the cache shouldn't be fetched.
+fn refetch_deleted<OUT, NETWORK, CACHE>(item: &OUT, policy: FetchPolicy, fetched: Callback<Rc<OUT>>)
+where
+    OUT: Object + Clone,
+    NETWORK: Network + 'static,
+    CACHE: Cache + 'static,
+{
+    if policy.fetches(false) {
+        fetch_item::<OUT, NETWORK, CACHE>(item.refetch_object(), fetched);
+    }
+}
+
+#[cfg(test)]
+mod test_query_provider {
+    use super::*;
+    use crate::cache::VANILLA_CACHE;
+    use crate::network::test_network::{respond, take_pending, TestNetwork};
+    use std::cell::RefCell;
+
+    #[derive(serde::Serialize, Deserialize, Debug, Clone, PartialEq)]
+    #[serde(tag = "__typename")]
+    struct User {
+        id: String,
+        name: String,
+    }
+
+    impl crate::selection::Selection for User {
+        type Fields = ();
+        fn selection_set_for(_: &()) -> String {
+            "{ __typename id name }".to_string()
+        }
+    }
+
+    impl Object for User {
+        const GRAPHQL_TYPE: &'static str = "User";
+        fn id(&self) -> &crate::Id {
+            &self.id
+        }
+        fn refetch_fields(&self, _: &()) -> Query<Self> {
+            Self::fetch(&self.id)
+        }
+    }
+
+    #[test]
+    fn test_deleted_items_are_refetched_as_the_policy_says() {
+        let user = User {
+            id: "1".to_string(),
+            name: "Jane".to_string(),
+        };
+        let fetched = Rc::new(RefCell::new(vec![]));
+        let refetch = |policy| {
+            refetch_deleted::<_, TestNetwork, VanillaCache>(
+                &user,
+                policy,
+                Callback::from({
+                    let fetched = fetched.clone();
+                    move |user: Rc<User>| fetched.borrow_mut().push(user.name.clone())
+                }),
+            );
+            take_pending().len()
+        };
+        // `CacheOnly` never goes to the network, so the provider just shows the loading state
+        assert_eq!(refetch(FetchPolicy::CacheOnly), 0);
+        assert_eq!(refetch(FetchPolicy::NetworkOnly), 1);
+        assert_eq!(refetch(FetchPolicy::CacheAndNetwork), 1);
+        assert_eq!(refetch(FetchPolicy::CacheFirst), 1);
+
+        // the refetched item is cached, and then passed back to the provider
+        refetch_deleted::<_, TestNetwork, VanillaCache>(
+            &user,
+            FetchPolicy::CacheFirst,
+            Callback::from({
+                let fetched = fetched.clone();
+                move |user: Rc<User>| fetched.borrow_mut().push(user.name.clone())
+            }),
+        );
+        assert_eq!(
+            respond(
+                serde_json::json!({"node": {"__typename": "User", "id": "1", "name": "Janet"}})
+            ),
+            1
+        );
+        assert_eq!(*fetched.borrow(), vec!["Janet"]);
+        assert_eq!(
+            VANILLA_CACHE.with(|cache| cache.borrow().retrieve::<User>(&"1".to_string())),
+            Some(Rc::new(User {
+                id: "1".to_string(),
+                name: "Janet".to_string(),
+            }))
+        );
+    }
+}
@@ -0,0 +1,75 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+
+//! Metadata about a GraphQL schema which is available at runtime. These types are emitted by the
+//! `graphql_schema!` macro (as constants), so you shouldn't need to construct them yourself.
+
+use crate::cache::control::CacheControl;
+
+/// The different sorts of types which can be defined in a GraphQL schema.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TypeKind {
+    Scalar,
+    Object,
+    Interface,
+    Union,
+    Enum,
+    InputObject,
+}
+
+/// A field on an object, interface or input object.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FieldMeta {
+    /// The name of the field as it appears in the schema.
+    pub name: &'static str,
+    /// The type of the field, written as it would be in the schema (e.g. `[String!]!`).
+    pub graphql_type: &'static str,
+}
+
+/// A type defined in the schema.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TypeMeta {
+    pub name: &'static str,
+    pub kind: TypeKind,
+    /// The fields of an object, interface or input object (empty for other kinds of type).
+    pub fields: &'static [FieldMeta],
+    /// The objects which a value of this type could be at runtime (for unions, interfaces and
+    /// objects; empty otherwise).
+    pub possible_types: &'static [&'static str],
+    /// The values of an enum (empty for other kinds of type).
+    pub enum_values: &'static [&'static str],
+    /// The `@cacheControl` hints of an object and of all of its fields (`CacheControl::NONE` for
+    /// other kinds of type) – see `Cache::use_schema`.
+    pub cache_control: CacheControl,
+}
+
+impl TypeMeta {
+    /// Finds a field by name.
+    pub fn field(&self, name: &str) -> Option<&FieldMeta> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+}
+
+/// A whole schema.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SchemaMeta {
+    /// The path to the file the schema was generated from.
+    pub path: &'static str,
+    pub types: &'static [TypeMeta],
+    /// The root operation types. If the schema has no `schema { ... }` block these are `Query`,
+    /// `Mutation` and `Subscription` (if those types exist).
+    pub query_type: Option<&'static str>,
+    pub mutation_type: Option<&'static str>,
+    pub subscription_type: Option<&'static str>,
+}
+
+impl SchemaMeta {
+    /// Finds a type by name.
+    pub fn get_type(&self, name: &str) -> Option<&TypeMeta> {
+        self.types.iter().find(|type_meta| type_meta.name == name)
+    }
+}
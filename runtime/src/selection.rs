@@ -0,0 +1,84 @@
+/*
+Built with love and the hope that you'll use this software for good by d3bate.
+
+This file is distributed subject to the terms of the Affero General Public License.
+A copy of the license can be found at the root of this Git repository.
+*/
+
+//! Selection sets, which describe the fields that need to be requested from the server in order to
+//! deserialize a type.
+
+use std::fmt::Debug;
+
+/// A trait for types which know which fields to ask the server for. This is implemented by our
+/// derive macros (`Object`, `Union` and `Interface`); you shouldn't need to implement it yourself.
+///
+/// Note that GraphQL doesn't allow recursive selection sets, so a type which (directly or
+/// indirectly) contains itself can't implement this trait sensibly.
+pub trait Selection {
+    /// Says which fields should be requested. For objects this is the generated `<Type>Fields`
+    /// struct, which has a `bool` for every field (or an `Option` containing the nested type's
+    /// `Fields`, for fields which are objects).
+    type Fields: FieldsSelection;
+    /// Returns the selection set (including the surrounding braces) for the chosen fields, for
+    /// example `{ __typename id username }`.
+    fn selection_set_for(fields: &Self::Fields) -> String;
+    /// Returns the selection set for every field of this type.
+    fn selection_set() -> String {
+        Self::selection_set_for(&Self::Fields::all())
+    }
+}
+
+/// Implemented by the `<Type>Fields` structs which `#[derive(Object)]` generates. Toggling the
+/// fields of one of these changes which fields are requested from the server, for example
+///
+/// ```ignore
+/// // only ask for the username
+/// let fields = UserFields {
+///     username: true,
+///     ..UserFields::none()
+/// };
+/// let query = user.refetch_fields(&fields);
+/// ```
+pub trait FieldsSelection: Clone + Debug + PartialEq {
+    /// Selects every field (including every field of nested objects).
+    fn all() -> Self;
+    /// Selects no fields (`__typename` is always requested, though).
+    fn none() -> Self;
+}
+
+/// Used for types (such as unions and interfaces) whose fields can't be chosen individually.
+impl FieldsSelection for () {
+    fn all() -> Self {}
+    fn none() -> Self {}
+}
+
+impl<T> Selection for Option<T>
+where
+    T: Selection,
+{
+    type Fields = T::Fields;
+    fn selection_set_for(fields: &Self::Fields) -> String {
+        T::selection_set_for(fields)
+    }
+}
+
+impl<T> Selection for Vec<T>
+where
+    T: Selection,
+{
+    type Fields = T::Fields;
+    fn selection_set_for(fields: &Self::Fields) -> String {
+        T::selection_set_for(fields)
+    }
+}
+
+impl<T> Selection for Box<T>
+where
+    T: Selection,
+{
+    type Fields = T::Fields;
+    fn selection_set_for(fields: &Self::Fields) -> String {
+        T::selection_set_for(fields)
+    }
+}